@@ -0,0 +1,18 @@
+CREATE TABLE changes (
+    id bigserial PRIMARY KEY,
+    change_type varchar(64) NOT NULL,
+    project_id bigint NOT NULL,
+    version_id bigint NULL,
+    created timestamptz DEFAULT clock_timestamp() NOT NULL,
+    -- Ids are handed out when a change is inserted, not when its transaction
+    -- commits. Changes are only given their position in the feed once the
+    -- transaction which made them is done, so that clients can't move past a
+    -- change which is still about to be committed.
+    xact_id xid8 DEFAULT pg_current_xact_id() NOT NULL,
+    position bigint NULL UNIQUE
+);
+
+CREATE SEQUENCE changes_position_seq;
+
+CREATE INDEX changes_project_id ON changes (project_id);
+CREATE INDEX changes_unpositioned ON changes (id) WHERE position IS NULL;
@@ -0,0 +1,12 @@
+CREATE TABLE dumps (
+    id bigserial PRIMARY KEY,
+    file_id varchar(2048) NOT NULL,
+    file_name varchar(2048) NOT NULL,
+    url varchar(2048) NOT NULL,
+    size bigint NOT NULL,
+    sha1 varchar(40) NOT NULL,
+    sha512 varchar(128) NOT NULL,
+    projects integer NOT NULL,
+    versions integer NOT NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
@@ -0,0 +1,24 @@
+-- version_id has no foreign key on purpose: sessions for deleted versions are
+-- left to expire, so that their chunks get removed from the file host as well
+CREATE TABLE upload_sessions (
+    id bigint PRIMARY KEY,
+    version_id bigint NOT NULL,
+    user_id bigint REFERENCES users NOT NULL,
+    filename varchar(2048) NOT NULL,
+    size bigint NOT NULL,
+    received bigint DEFAULT 0 NOT NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires timestamptz DEFAULT CURRENT_TIMESTAMP + interval '1 day' NOT NULL
+);
+
+CREATE INDEX upload_sessions_user_id ON upload_sessions (user_id);
+
+CREATE TABLE upload_session_chunks (
+    session_id bigint REFERENCES upload_sessions ON DELETE CASCADE NOT NULL,
+    chunk_offset bigint NOT NULL,
+    size integer NOT NULL,
+    sha1 varchar(40) NOT NULL,
+    file_id varchar(2048) NOT NULL,
+    file_name varchar(2048) NOT NULL,
+    PRIMARY KEY (session_id, chunk_offset)
+);
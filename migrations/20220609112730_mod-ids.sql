@@ -0,0 +1,10 @@
+-- Maps the mod ids declared in manifests (fabric.mod.json, mods.toml, ...)
+-- to the projects whose files declared them
+CREATE TABLE mod_ids (
+    mod_id varchar(255) NOT NULL,
+    project_id bigint REFERENCES mods ON UPDATE CASCADE NOT NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (mod_id, project_id)
+);
+
+CREATE INDEX mod_ids_project_id ON mod_ids (project_id);
@@ -0,0 +1,12 @@
+-- Findings from validating uploaded files, such as missing manifests or
+-- mod files which only contain source code
+CREATE TABLE file_validation_findings (
+    id serial PRIMARY KEY,
+    file_id bigint REFERENCES files ON UPDATE CASCADE NOT NULL,
+    severity varchar(32) NOT NULL,
+    code varchar(64) NOT NULL,
+    message varchar(2048) NOT NULL,
+    path varchar(2048) NULL
+);
+
+CREATE INDEX file_validation_findings_file_id ON file_validation_findings (file_id);
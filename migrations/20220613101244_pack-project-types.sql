@@ -0,0 +1,20 @@
+INSERT INTO project_types (name) VALUES ('resourcepack');
+INSERT INTO project_types (name) VALUES ('datapack');
+INSERT INTO project_types (name) VALUES ('shader');
+
+INSERT INTO loaders (loader) VALUES ('minecraft') ON CONFLICT DO NOTHING;
+INSERT INTO loaders (loader) VALUES ('iris') ON CONFLICT DO NOTHING;
+INSERT INTO loaders (loader) VALUES ('optifine') ON CONFLICT DO NOTHING;
+
+-- Resource packs and data packs are loaded by the game itself, shader packs
+-- by a shader loader
+INSERT INTO loaders_project_types (joining_loader_id, joining_project_type_id)
+SELECT l.id, pt.id
+FROM loaders l, project_types pt
+WHERE (l.loader, pt.name) IN (
+    ('minecraft', 'resourcepack'),
+    ('minecraft', 'datapack'),
+    ('iris', 'shader'),
+    ('optifine', 'shader')
+)
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,16 @@
+INSERT INTO loaders (loader) VALUES ('bukkit') ON CONFLICT DO NOTHING;
+INSERT INTO loaders (loader) VALUES ('spigot') ON CONFLICT DO NOTHING;
+INSERT INTO loaders (loader) VALUES ('paper') ON CONFLICT DO NOTHING;
+INSERT INTO loaders (loader) VALUES ('purpur') ON CONFLICT DO NOTHING;
+INSERT INTO loaders (loader) VALUES ('bungeecord') ON CONFLICT DO NOTHING;
+INSERT INTO loaders (loader) VALUES ('waterfall') ON CONFLICT DO NOTHING;
+INSERT INTO loaders (loader) VALUES ('velocity') ON CONFLICT DO NOTHING;
+
+-- Server plugins are published as mods, with the platform as their loader
+INSERT INTO loaders_project_types (joining_loader_id, joining_project_type_id)
+SELECT l.id, pt.id
+FROM loaders l, project_types pt
+WHERE l.loader IN (
+    'bukkit', 'spigot', 'paper', 'purpur', 'bungeecord', 'waterfall', 'velocity'
+) AND pt.name = 'mod'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,18 @@
+-- Versions with files flagged by the upload scanner are hidden until a
+-- moderator releases them
+ALTER TABLE versions ADD COLUMN quarantined boolean NOT NULL DEFAULT FALSE;
+
+CREATE INDEX versions_quarantined ON versions (date_published) WHERE quarantined;
+
+-- Findings of the upload scanner are only shown to moderators, so that the
+-- authors of malicious files can't learn what gave them away
+CREATE TABLE file_scan_findings (
+    id serial PRIMARY KEY,
+    file_id bigint REFERENCES files ON UPDATE CASCADE NOT NULL,
+    severity varchar(32) NOT NULL,
+    code varchar(64) NOT NULL,
+    message varchar(2048) NOT NULL,
+    path varchar(2048) NULL
+);
+
+CREATE INDEX file_scan_findings_file_id ON file_scan_findings (file_id);
@@ -0,0 +1,15 @@
+-- Jars bundled inside of uploaded files, such as Fabric's Jar-in-Jar and
+-- Forge's JarJar. Hashes are stored the same way as in the hashes table.
+CREATE TABLE nested_files (
+    id bigserial PRIMARY KEY,
+    file_id bigint REFERENCES files ON UPDATE CASCADE NOT NULL,
+    path varchar(2048) NOT NULL,
+    mod_id varchar(255) NULL,
+    version_number varchar(255) NULL,
+    sha1 bytea NOT NULL,
+    sha512 bytea NOT NULL
+);
+
+CREATE INDEX nested_files_file_id ON nested_files (file_id);
+CREATE INDEX nested_files_sha1 ON nested_files (sha1);
+CREATE INDEX nested_files_sha512 ON nested_files (sha512);
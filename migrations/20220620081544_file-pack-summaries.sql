@@ -0,0 +1,14 @@
+-- An overview of what each uploaded modpack file installs, for moderators
+-- reviewing the pack
+CREATE TABLE file_pack_summaries (
+    file_id bigint PRIMARY KEY REFERENCES files ON UPDATE CASCADE,
+    file_count integer NOT NULL,
+    total_file_size bigint NOT NULL,
+    override_count integer NOT NULL,
+    client_override_count integer NOT NULL,
+    server_override_count integer NOT NULL,
+    override_jar_count integer NOT NULL,
+    minecraft_version varchar(255) NOT NULL,
+    loader varchar(255) NULL,
+    loader_version varchar(255) NULL
+);
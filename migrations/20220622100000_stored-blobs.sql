@@ -0,0 +1,17 @@
+-- Files stored under the hash of their contents, shared by every version
+-- file with the same contents
+CREATE TABLE stored_blobs (
+    sha512 varchar(128) PRIMARY KEY,
+    file_id varchar(1024) NOT NULL,
+    file_name varchar(1024) NOT NULL,
+    size bigint NOT NULL,
+    ref_count integer NOT NULL DEFAULT 0,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX stored_blobs_unreferenced ON stored_blobs (created) WHERE ref_count <= 0;
+
+-- Files uploaded before content addressed storage was enabled don't have a blob
+ALTER TABLE files ADD COLUMN blob_sha512 varchar(128) NULL;
+
+CREATE INDEX files_blob_sha512 ON files (blob_sha512);
@@ -0,0 +1,15 @@
+-- The copies of stored files on the secondary file hosts
+CREATE TABLE file_replicas (
+    file_name varchar(1024) NOT NULL,
+    replica varchar(64) NOT NULL,
+    status varchar(32) NOT NULL,
+    -- The ID of the file on the replica, once it is stored there
+    file_id varchar(1024) NULL,
+    content_type varchar(255) NOT NULL,
+    attempts integer NOT NULL DEFAULT 0,
+    last_error text NULL,
+    updated timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (file_name, replica)
+);
+
+CREATE INDEX file_replicas_unsynced ON file_replicas (updated) WHERE status != 'stored';
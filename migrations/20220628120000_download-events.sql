@@ -0,0 +1,23 @@
+-- Every counted download, so that downloads can be analyzed over time
+CREATE TABLE downloads (
+    id bigserial PRIMARY KEY,
+    version_id bigint REFERENCES versions ON UPDATE CASCADE NOT NULL,
+    date timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    -- A SHA1 hash of the downloader IP address and the pepper. Downloads
+    -- reported without the downloader's address can't be linked to each
+    -- other and have none.
+    identifier varchar(40) NULL
+);
+
+CREATE INDEX downloads_version_date ON downloads (version_id, date);
+CREATE INDEX downloads_date ON downloads (date);
+
+-- The pepper addresses are hashed with, which is shared by all instances so
+-- that repeated downloads are recognized across restarts
+CREATE TABLE download_pepper (
+    id int PRIMARY KEY DEFAULT 1 CHECK (id = 1),
+    pepper varchar(64) NOT NULL
+);
+
+INSERT INTO download_pepper (pepper)
+VALUES (replace(gen_random_uuid()::text, '-', ''));
@@ -0,0 +1,32 @@
+-- Every follow and unfollow of a project, so that follower gains and losses
+-- can be analyzed over time
+CREATE TABLE follow_events (
+    id bigserial PRIMARY KEY,
+    mod_id bigint REFERENCES mods ON UPDATE CASCADE NOT NULL,
+    followed boolean NOT NULL,
+    date timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX follow_events_date ON follow_events (date);
+
+-- Daily totals of the download and follow events, which are kept after the
+-- events themselves are pruned
+CREATE TABLE download_rollups (
+    version_id bigint REFERENCES versions ON UPDATE CASCADE NOT NULL,
+    day date NOT NULL,
+    downloads integer NOT NULL,
+    PRIMARY KEY (version_id, day)
+);
+
+CREATE TABLE follower_rollups (
+    mod_id bigint REFERENCES mods ON UPDATE CASCADE NOT NULL,
+    day date NOT NULL,
+    gained integer NOT NULL,
+    lost integer NOT NULL,
+    PRIMARY KEY (mod_id, day)
+);
+
+-- Owners have every permission, including the new one to view analytics
+UPDATE team_members
+SET permissions = permissions | 256
+WHERE role = 'Owner';
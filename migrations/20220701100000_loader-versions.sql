@@ -0,0 +1,7 @@
+-- The known versions of the mod loaders modpacks can depend on, indexed from
+-- the loaders' metadata along with the game versions
+CREATE TABLE loader_versions (
+    loader varchar(64) NOT NULL,
+    version varchar(255) NOT NULL,
+    PRIMARY KEY (loader, version)
+);
@@ -0,0 +1,11 @@
+-- The completed reconciliations of stored files, so that restarts don't
+-- postpone or repeat them
+CREATE TABLE reconciliation_runs (
+    id bigserial PRIMARY KEY,
+    started timestamptz NOT NULL,
+    dry_run boolean NOT NULL,
+    listed_files integer NOT NULL,
+    orphaned_files integer NOT NULL,
+    missing_files integer NOT NULL,
+    deleted_files integer NOT NULL
+);
@@ -0,0 +1,3 @@
+-- Files served by the local file host are looked up by their URL to find
+-- out whether they can be downloaded without a signed URL
+CREATE INDEX files_url ON files (url);
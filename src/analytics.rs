@@ -0,0 +1,102 @@
+//! Periodic rollups of the download and follow events into daily totals,
+//! which the analytics of projects are read from. Events are deleted after
+//! `ANALYTICS_EVENT_RETENTION` days (90 by default), since only the totals
+//! are needed after that.
+use crate::scheduler::Scheduler;
+use crate::util::env::parse_var;
+use log::{info, warn};
+use sqlx::PgPool;
+
+/// The least number of days events are kept for, so that the days which are
+/// rolled up again still have all of their events
+const MIN_EVENT_RETENTION: i32 = 3;
+
+pub fn schedule_analytics_rollups(scheduler: &mut Scheduler, pool: PgPool) {
+    let rollup_interval = std::time::Duration::from_secs(
+        parse_var("ANALYTICS_ROLLUP_INTERVAL").unwrap_or(3600),
+    );
+    let event_retention = parse_var("ANALYTICS_EVENT_RETENTION")
+        .unwrap_or(90)
+        .max(MIN_EVENT_RETENTION);
+
+    scheduler.run(rollup_interval, move || {
+        let pool_ref = pool.clone();
+        async move {
+            info!("Rolling up analytics");
+            let result = rollup(&pool_ref, event_retention).await;
+            if let Err(e) = result {
+                warn!("Rolling up analytics failed: {}", e);
+            }
+            info!("Done rolling up analytics");
+        }
+    });
+}
+
+/// Counts the events since the day before the latest rolled up day, since
+/// that day may have been incomplete and downloads are recorded with a delay
+async fn rollup(
+    pool: &PgPool,
+    event_retention: i32,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query!(
+        "
+        INSERT INTO download_rollups (version_id, day, downloads)
+        SELECT version_id, (date AT TIME ZONE 'UTC')::date, COUNT(*)
+        FROM downloads
+        WHERE date >= COALESCE(
+            (SELECT MAX(day) - 1 FROM download_rollups)::timestamp AT TIME ZONE 'UTC',
+            '-infinity'
+        )
+        GROUP BY 1, 2
+        ON CONFLICT (version_id, day) DO UPDATE
+        SET downloads = EXCLUDED.downloads
+        "
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        "
+        INSERT INTO follower_rollups (mod_id, day, gained, lost)
+        SELECT
+            mod_id, (date AT TIME ZONE 'UTC')::date,
+            COUNT(*) FILTER (WHERE followed), COUNT(*) FILTER (WHERE NOT followed)
+        FROM follow_events
+        WHERE date >= COALESCE(
+            (SELECT MAX(day) - 1 FROM follower_rollups)::timestamp AT TIME ZONE 'UTC',
+            '-infinity'
+        )
+        GROUP BY 1, 2
+        ON CONFLICT (mod_id, day) DO UPDATE
+        SET gained = EXCLUDED.gained, lost = EXCLUDED.lost
+        "
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM downloads
+        WHERE date < NOW() - make_interval(days => $1)
+        ",
+        event_retention,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        "
+        DELETE FROM follow_events
+        WHERE date < NOW() - make_interval(days => $1)
+        ",
+        event_retention,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
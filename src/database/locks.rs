@@ -0,0 +1,76 @@
+//! Advisory locks for work which mustn't run on several instances at once
+
+use sqlx::{Connection, PgPool};
+use std::future::Future;
+
+/// The keys of the advisory locks, which have to be unique across the whole
+/// database
+#[derive(Clone, Copy)]
+pub enum AdvisoryLock {
+    /// Handing out positions in the changes feed
+    ChangePositions = 1,
+    /// Writing a catalogue dump
+    Dump = 2,
+    /// Computing missing file hashes
+    HashBackfill = 3,
+}
+
+impl AdvisoryLock {
+    /// Takes the lock until the transaction ends, without waiting for it.
+    /// Returns whether the lock was taken.
+    pub async fn try_lock(
+        self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<bool, sqlx::Error> {
+        let locked = sqlx::query!(
+            "SELECT pg_try_advisory_xact_lock($1) locked",
+            self as i64,
+        )
+        .fetch_one(&mut *transaction)
+        .await?
+        .locked;
+
+        Ok(locked == Some(true))
+    }
+
+    /// Runs `job` while holding the lock on a connection of its own, so that
+    /// no transaction has to be kept open while it runs. Returns `None`
+    /// without running `job` if the lock is held somewhere else.
+    pub async fn run<F, T>(
+        self,
+        pool: &PgPool,
+        job: F,
+    ) -> Result<Option<T>, sqlx::Error>
+    where
+        F: Future<Output = T>,
+    {
+        let mut conn = pool.acquire().await?;
+
+        let locked = sqlx::query!(
+            "SELECT pg_try_advisory_lock($1) locked",
+            self as i64,
+        )
+        .fetch_one(&mut *conn)
+        .await?
+        .locked;
+
+        if locked != Some(true) {
+            return Ok(None);
+        }
+
+        let result = job.await;
+
+        let unlocked =
+            sqlx::query!("SELECT pg_advisory_unlock($1)", self as i64)
+                .execute(&mut *conn)
+                .await;
+
+        // The lock is released with the session, so a connection which
+        // might still hold it mustn't go back into the pool
+        if unlocked.is_err() {
+            conn.detach().close().await.ok();
+        }
+
+        Ok(Some(result))
+    }
+}
@@ -1,3 +1,4 @@
+pub mod locks;
 pub mod models;
 mod postgres_database;
 pub use models::Project;
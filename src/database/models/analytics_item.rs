@@ -0,0 +1,199 @@
+use super::ids::{ProjectId, UserId, VersionId};
+use time::Date;
+
+/// The downloads of a version on one day
+pub struct VersionDownloads {
+    pub version_id: VersionId,
+    pub day: Date,
+    pub downloads: i64,
+}
+
+/// The downloads of the versions with a game version or loader on one day
+pub struct GroupedDownloads {
+    pub name: String,
+    pub day: Date,
+    pub downloads: i64,
+}
+
+/// The followers a project gained and lost on one day
+pub struct DailyFollowers {
+    pub day: Date,
+    pub gained: i32,
+    pub lost: i32,
+}
+
+/// Records that a project was followed or unfollowed
+pub async fn record_follow(
+    project_id: ProjectId,
+    followed: bool,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        INSERT INTO follow_events (mod_id, followed)
+        VALUES ($1, $2)
+        ",
+        project_id as ProjectId,
+        followed,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Records that a user unfollowed every project they follow
+pub async fn record_unfollow_all(
+    user_id: UserId,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        INSERT INTO follow_events (mod_id, followed)
+        SELECT mod_id, FALSE FROM mod_follows
+        WHERE follower_id = $1
+        ",
+        user_id as UserId,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Gets the daily downloads of each version of a project since `since`
+pub async fn get_version_downloads<'a, E>(
+    project_id: ProjectId,
+    since: Date,
+    exec: E,
+) -> Result<Vec<VersionDownloads>, sqlx::Error>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query!(
+        "
+        SELECT dr.version_id, dr.day, dr.downloads FROM download_rollups dr
+        INNER JOIN versions v ON v.id = dr.version_id
+        WHERE v.mod_id = $1 AND dr.day >= $2
+        ORDER BY dr.day
+        ",
+        project_id as ProjectId,
+        since,
+    )
+    .fetch_all(exec)
+    .await?;
+
+    Ok(result
+        .into_iter()
+        .map(|x| VersionDownloads {
+            version_id: VersionId(x.version_id),
+            day: x.day,
+            downloads: i64::from(x.downloads),
+        })
+        .collect())
+}
+
+/// Gets the daily downloads of a project's versions for each game version
+/// since `since`. Versions for several game versions count for each of them.
+pub async fn get_game_version_downloads<'a, E>(
+    project_id: ProjectId,
+    since: Date,
+    exec: E,
+) -> Result<Vec<GroupedDownloads>, sqlx::Error>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query!(
+        r#"
+        SELECT gv.version name, dr.day, SUM(dr.downloads) "downloads!"
+        FROM download_rollups dr
+        INNER JOIN versions v ON v.id = dr.version_id
+        INNER JOIN game_versions_versions gvv ON gvv.joining_version_id = v.id
+        INNER JOIN game_versions gv ON gv.id = gvv.game_version_id
+        WHERE v.mod_id = $1 AND dr.day >= $2
+        GROUP BY gv.version, dr.day
+        ORDER BY dr.day
+        "#,
+        project_id as ProjectId,
+        since,
+    )
+    .fetch_all(exec)
+    .await?;
+
+    Ok(result
+        .into_iter()
+        .map(|x| GroupedDownloads {
+            name: x.name,
+            day: x.day,
+            downloads: x.downloads,
+        })
+        .collect())
+}
+
+/// Gets the daily downloads of a project's versions for each loader since
+/// `since`. Versions for several loaders count for each of them.
+pub async fn get_loader_downloads<'a, E>(
+    project_id: ProjectId,
+    since: Date,
+    exec: E,
+) -> Result<Vec<GroupedDownloads>, sqlx::Error>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query!(
+        r#"
+        SELECT l.loader name, dr.day, SUM(dr.downloads) "downloads!"
+        FROM download_rollups dr
+        INNER JOIN versions v ON v.id = dr.version_id
+        INNER JOIN loaders_versions lv ON lv.version_id = v.id
+        INNER JOIN loaders l ON l.id = lv.loader_id
+        WHERE v.mod_id = $1 AND dr.day >= $2
+        GROUP BY l.loader, dr.day
+        ORDER BY dr.day
+        "#,
+        project_id as ProjectId,
+        since,
+    )
+    .fetch_all(exec)
+    .await?;
+
+    Ok(result
+        .into_iter()
+        .map(|x| GroupedDownloads {
+            name: x.name,
+            day: x.day,
+            downloads: x.downloads,
+        })
+        .collect())
+}
+
+/// Gets the followers a project gained and lost on each day since `since`
+pub async fn get_followers<'a, E>(
+    project_id: ProjectId,
+    since: Date,
+    exec: E,
+) -> Result<Vec<DailyFollowers>, sqlx::Error>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query!(
+        "
+        SELECT day, gained, lost FROM follower_rollups
+        WHERE mod_id = $1 AND day >= $2
+        ORDER BY day
+        ",
+        project_id as ProjectId,
+        since,
+    )
+    .fetch_all(exec)
+    .await?;
+
+    Ok(result
+        .into_iter()
+        .map(|x| DailyFollowers {
+            day: x.day,
+            gained: x.gained,
+            lost: x.lost,
+        })
+        .collect())
+}
@@ -0,0 +1,222 @@
+use super::ids::{ProjectId, VersionId};
+use time::OffsetDateTime;
+
+/// A file stored under the hash of its contents, shared by every version
+/// file with the same contents
+pub struct StoredBlob {
+    /// The SHA512 hash of the contents as a hex string
+    pub sha512: String,
+    pub file_id: String,
+    pub file_name: String,
+    pub size: i64,
+    pub ref_count: i32,
+}
+
+pub struct StoredBlobBuilder {
+    pub sha512: String,
+    pub file_id: String,
+    pub file_name: String,
+    pub size: i64,
+}
+
+impl StoredBlobBuilder {
+    /// Records a newly stored blob without any references. This happens
+    /// outside of the transaction adding the file which uses the blob, so
+    /// that the blob is known even if that transaction fails; blobs which are
+    /// never referenced are deleted by the reconciliation. Returns the path
+    /// the contents are stored at, which is another one if the same contents
+    /// were recorded concurrently.
+    pub async fn insert<'a, E>(self, exec: E) -> Result<String, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            INSERT INTO stored_blobs (sha512, file_id, file_name, size, ref_count)
+            VALUES ($1, $2, $3, $4, 0)
+            ON CONFLICT (sha512) DO UPDATE
+            SET file_name = stored_blobs.file_name
+            RETURNING file_name
+            ",
+            self.sha512,
+            self.file_id,
+            self.file_name,
+            self.size,
+        )
+        .fetch_one(exec)
+        .await?;
+
+        Ok(result.file_name)
+    }
+}
+
+impl StoredBlob {
+    /// Adds a reference to the blob with these contents, if it is stored.
+    /// The row stays locked until the transaction ends, so the blob can't be
+    /// deleted in the meantime.
+    pub async fn add_reference(
+        sha512: &str,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<StoredBlob>, sqlx::Error> {
+        let result = sqlx::query!(
+            "
+            UPDATE stored_blobs
+            SET ref_count = ref_count + 1
+            WHERE sha512 = $1
+            RETURNING sha512, file_id, file_name, size, ref_count
+            ",
+            sha512,
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        Ok(result.map(|b| StoredBlob {
+            sha512: b.sha512,
+            file_id: b.file_id,
+            file_name: b.file_name,
+            size: b.size,
+            ref_count: b.ref_count,
+        }))
+    }
+
+    /// Removes a reference to a blob. Blobs without references are kept
+    /// until they are deleted with `take_unreferenced`.
+    pub async fn remove_reference(
+        sha512: &str,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "
+            UPDATE stored_blobs
+            SET ref_count = GREATEST(ref_count - 1, 0)
+            WHERE sha512 = $1
+            ",
+            sha512,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes the references of all files of a version
+    pub async fn release_version(
+        id: VersionId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "
+            UPDATE stored_blobs
+            SET ref_count = GREATEST(ref_count - f.count, 0)
+            FROM (
+                SELECT blob_sha512, COUNT(*)::integer count FROM files
+                WHERE version_id = $1 AND blob_sha512 IS NOT NULL
+                GROUP BY blob_sha512
+            ) f
+            WHERE stored_blobs.sha512 = f.blob_sha512
+            ",
+            id as VersionId,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets the hashes of the blobs the files of a version are stored in
+    pub async fn hashes_of_version<'a, E>(
+        id: VersionId,
+        exec: E,
+    ) -> Result<Vec<String>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT DISTINCT blob_sha512 FROM files
+            WHERE version_id = $1 AND blob_sha512 IS NOT NULL
+            ",
+            id as VersionId,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(result.into_iter().filter_map(|x| x.blob_sha512).collect())
+    }
+
+    /// Gets the hashes of the blobs the files of a project are stored in
+    pub async fn hashes_of_project<'a, E>(
+        id: ProjectId,
+        exec: E,
+    ) -> Result<Vec<String>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT DISTINCT f.blob_sha512 FROM files f
+            INNER JOIN versions v ON v.id = f.version_id
+            WHERE v.mod_id = $1 AND f.blob_sha512 IS NOT NULL
+            ",
+            id as ProjectId,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(result.into_iter().filter_map(|x| x.blob_sha512).collect())
+    }
+
+    /// Gets the hashes of all blobs stored before `cutoff` which no file
+    /// references anymore. Newer blobs may still be about to be referenced
+    /// by the upload which stored them.
+    pub async fn list_unreferenced<'a, E>(
+        cutoff: OffsetDateTime,
+        exec: E,
+    ) -> Result<Vec<String>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT sha512 FROM stored_blobs
+            WHERE ref_count <= 0 AND created < $1
+            ",
+            cutoff,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(result.into_iter().map(|x| x.sha512).collect())
+    }
+
+    /// Deletes the records of the given blobs which no file references
+    /// anymore. The rows stay locked until the transaction ends, so the
+    /// stored files should be deleted before committing it; otherwise an
+    /// upload of the same contents could reuse a blob which is being deleted.
+    pub async fn take_unreferenced(
+        sha512s: &[String],
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Vec<StoredBlob>, sqlx::Error> {
+        let result = sqlx::query!(
+            "
+            DELETE FROM stored_blobs
+            WHERE sha512 = ANY($1) AND ref_count <= 0
+            RETURNING sha512, file_id, file_name, size, ref_count
+            ",
+            sha512s,
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+
+        Ok(result
+            .into_iter()
+            .map(|b| StoredBlob {
+                sha512: b.sha512,
+                file_id: b.file_id,
+                file_name: b.file_name,
+                size: b.size,
+                ref_count: b.ref_count,
+            })
+            .collect())
+    }
+}
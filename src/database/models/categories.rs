@@ -490,6 +490,28 @@ impl GameVersion {
         Ok(result.map(|r| GameVersionId(r.id)))
     }
 
+    /// Gets the name of a game version from the name with its dots written
+    /// as underscores, as it is in Maven group IDs
+    pub async fn get_name_from_underscored<'a, E>(
+        underscored: &str,
+        exec: E,
+    ) -> Result<Option<String>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT version FROM game_versions
+            WHERE REPLACE(version, '.', '_') = $1
+            ",
+            underscored
+        )
+        .fetch_optional(exec)
+        .await?;
+
+        Ok(result.map(|r| r.version))
+    }
+
     pub async fn get_name<'a, E>(
         id: GameVersionId,
         exec: E,
@@ -1319,3 +1341,53 @@ impl<'a> ProjectTypeBuilder<'a> {
         Ok(ProjectTypeId(result.id))
     }
 }
+
+/// The known versions of the mod loaders modpacks can depend on, by the names
+/// of their pack dependencies such as `fabric-loader`
+pub struct LoaderVersion;
+
+impl LoaderVersion {
+    pub async fn list<'a, E>(
+        loader: &str,
+        exec: E,
+    ) -> Result<Vec<String>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT version FROM loader_versions
+            WHERE loader = $1
+            ",
+            loader
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(result.into_iter().map(|x| x.version).collect())
+    }
+
+    /// Adds the versions of a loader which aren't known yet
+    pub async fn insert_many<'a, E>(
+        loader: &str,
+        versions: &[String],
+        exec: E,
+    ) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            INSERT INTO loader_versions (loader, version)
+            SELECT $1, version FROM UNNEST($2::varchar[]) version
+            ON CONFLICT DO NOTHING
+            ",
+            loader,
+            versions
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+use super::ids::*;
+use crate::database::locks::AdvisoryLock;
+use crate::models::projects::ProjectStatus;
+use futures::TryStreamExt;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+/// Projects with these statuses can be viewed by anyone (see `is_authorized`)
+const PUBLIC_STATUSES: [ProjectStatus; 3] = [
+    ProjectStatus::Approved,
+    ProjectStatus::Archived,
+    ProjectStatus::Unlisted,
+];
+
+pub struct ChangeBuilder {
+    pub change_type: String,
+    pub project_id: ProjectId,
+    pub version_id: Option<VersionId>,
+}
+
+impl ChangeBuilder {
+    pub fn project(project_id: ProjectId, change_type: &str) -> Self {
+        ChangeBuilder {
+            change_type: change_type.to_string(),
+            project_id,
+            version_id: None,
+        }
+    }
+
+    pub fn version(
+        project_id: ProjectId,
+        version_id: VersionId,
+        change_type: &str,
+    ) -> Self {
+        ChangeBuilder {
+            change_type: change_type.to_string(),
+            project_id,
+            version_id: Some(version_id),
+        }
+    }
+
+    /// Records the change if the project can be viewed by everyone, so that
+    /// the feed doesn't reveal projects which were never public. Projects
+    /// being hidden or deleted have to be recorded before their status
+    /// changes or they're removed.
+    pub async fn insert(
+        self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), sqlx::error::Error> {
+        let statuses = PUBLIC_STATUSES
+            .iter()
+            .map(|x| x.as_str().to_string())
+            .collect::<Vec<_>>();
+
+        sqlx::query!(
+            "
+            INSERT INTO changes (change_type, project_id, version_id)
+            SELECT $1, $2, $3
+            WHERE EXISTS (
+                SELECT 1 FROM mods m
+                INNER JOIN statuses s ON s.id = m.status
+                WHERE m.id = $2 AND s.status = ANY($4)
+            )
+            ",
+            self.change_type,
+            self.project_id as ProjectId,
+            self.version_id.map(|x| x.0),
+            &statuses,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        Ok(())
+    }
+}
+
+pub struct Change {
+    pub position: i64,
+    pub change_type: String,
+    pub project_id: ProjectId,
+    pub version_id: Option<VersionId>,
+    pub created: OffsetDateTime,
+}
+
+impl Change {
+    /// Gives the changes whose transactions are done their positions in the
+    /// feed. Positions are handed out by one transaction at a time, so they
+    /// become visible in order. If another transaction is already handing
+    /// them out, this returns right away.
+    pub async fn assign_positions(pool: &PgPool) -> Result<(), sqlx::Error> {
+        let mut transaction = pool.begin().await?;
+
+        if AdvisoryLock::ChangePositions
+            .try_lock(&mut transaction)
+            .await?
+        {
+            // Every transaction older than the oldest running one is done
+            sqlx::query!(
+                "
+                UPDATE changes c
+                SET position = p.position
+                FROM (
+                    SELECT id, nextval('changes_position_seq') position
+                    FROM (
+                        SELECT id FROM changes
+                        WHERE position IS NULL
+                        AND xact_id < pg_snapshot_xmin(pg_current_snapshot())
+                        ORDER BY id
+                    ) done
+                ) p
+                WHERE c.id = p.id
+                "
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Gets the changes after the given position, oldest first
+    pub async fn get_since<'a, E>(
+        since: i64,
+        limit: i64,
+        exec: E,
+    ) -> Result<Vec<Change>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            SELECT c.position AS \"position!\", c.change_type, c.project_id,
+                c.version_id, c.created
+            FROM changes c
+            WHERE c.position > $1
+            ORDER BY c.position ASC
+            LIMIT $2
+            ",
+            since,
+            limit,
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async {
+            Ok(e.right().map(|c| Change {
+                position: c.position,
+                change_type: c.change_type,
+                project_id: ProjectId(c.project_id),
+                version_id: c.version_id.map(VersionId),
+                created: c.created,
+            }))
+        })
+        .try_collect::<Vec<Change>>()
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn pool() -> PgPool {
+        PgPool::connect(&dotenv::var("DATABASE_URL").unwrap())
+            .await
+            .unwrap()
+    }
+
+    fn project_id() -> i64 {
+        i64::from(rand::random::<u32>())
+    }
+
+    /// Inserts a change without checking whether its project is public
+    async fn insert_change<'a, E>(project_id: i64, exec: E)
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            INSERT INTO changes (change_type, project_id)
+            VALUES ('updated', $1)
+            ",
+            project_id,
+        )
+        .execute(exec)
+        .await
+        .unwrap();
+    }
+
+    async fn position(pool: &PgPool, project_id: i64) -> Option<i64> {
+        Change::assign_positions(pool).await.unwrap();
+
+        Change::get_since(0, i64::MAX, pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|x| x.project_id == ProjectId(project_id))
+            .map(|x| x.position)
+    }
+
+    #[actix_rt::test]
+    #[ignore = "needs a migrated database at DATABASE_URL"]
+    async fn changes_wait_for_earlier_transactions() {
+        let pool = pool().await;
+        let slow_project = project_id();
+        let fast_project = project_id();
+
+        // The slow change gets the lower id, but commits last
+        let mut slow = pool.begin().await.unwrap();
+        insert_change(slow_project, &mut *slow).await;
+        insert_change(fast_project, &pool).await;
+
+        assert_eq!(position(&pool, fast_project).await, None);
+
+        slow.commit().await.unwrap();
+
+        let slow_position = position(&pool, slow_project).await.unwrap();
+        let fast_position = position(&pool, fast_project).await.unwrap();
+        assert!(slow_position < fast_position);
+
+        sqlx::query!(
+            "DELETE FROM changes WHERE project_id = ANY($1)",
+            &[slow_project, fast_project][..],
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    #[actix_rt::test]
+    #[ignore = "needs a migrated database at DATABASE_URL"]
+    async fn changes_of_private_projects_are_not_recorded() {
+        let pool = pool().await;
+        let project = project_id();
+        let mut transaction = pool.begin().await.unwrap();
+
+        ChangeBuilder::project(ProjectId(project), "deleted")
+            .insert(&mut transaction)
+            .await
+            .unwrap();
+
+        let count = sqlx::query!(
+            "SELECT COUNT(*) count FROM changes WHERE project_id = $1",
+            project,
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .unwrap()
+        .count;
+        assert_eq!(count, Some(0));
+    }
+}
@@ -0,0 +1,128 @@
+use futures::TryStreamExt;
+use time::OffsetDateTime;
+
+pub struct DumpBuilder {
+    pub file_id: String,
+    pub file_name: String,
+    pub url: String,
+    pub size: i64,
+    pub sha1: String,
+    pub sha512: String,
+    pub projects: i32,
+    pub versions: i32,
+}
+
+impl DumpBuilder {
+    pub async fn insert<'a, E>(self, exec: E) -> Result<(), sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            INSERT INTO dumps (
+                file_id, file_name, url, size, sha1, sha512,
+                projects, versions
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6,
+                $7, $8
+            )
+            ",
+            self.file_id,
+            self.file_name,
+            self.url,
+            self.size,
+            self.sha1,
+            self.sha512,
+            self.projects,
+            self.versions,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+}
+
+pub struct Dump {
+    pub id: i64,
+    pub file_id: String,
+    pub file_name: String,
+    pub url: String,
+    pub size: i64,
+    pub sha1: String,
+    pub sha512: String,
+    pub projects: i32,
+    pub versions: i32,
+    pub created: OffsetDateTime,
+}
+
+impl Dump {
+    /// Lists all dumps, newest first
+    pub async fn list<'a, E>(exec: E) -> Result<Vec<Dump>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            SELECT id, file_id, file_name, url, size, sha1, sha512,
+                projects, versions, created
+            FROM dumps
+            ORDER BY created DESC
+            "
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async {
+            Ok(e.right().map(|d| Dump {
+                id: d.id,
+                file_id: d.file_id,
+                file_name: d.file_name,
+                url: d.url,
+                size: d.size,
+                sha1: d.sha1,
+                sha512: d.sha512,
+                projects: d.projects,
+                versions: d.versions,
+                created: d.created,
+            }))
+        })
+        .try_collect::<Vec<Dump>>()
+        .await
+    }
+
+    /// Gets the time the newest dump was created at, if there are any
+    pub async fn get_latest_created<'a, E>(
+        exec: E,
+    ) -> Result<Option<OffsetDateTime>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT MAX(created) created
+            FROM dumps
+            "
+        )
+        .fetch_one(exec)
+        .await?;
+
+        Ok(result.created)
+    }
+
+    pub async fn remove<'a, E>(id: i64, exec: E) -> Result<(), sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            DELETE FROM dumps
+            WHERE id = $1
+            ",
+            id,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+}
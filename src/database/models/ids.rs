@@ -102,6 +102,14 @@ generate_ids!(
     NotificationId
 );
 
+generate_ids!(
+    pub generate_upload_session_id,
+    UploadSessionId,
+    8,
+    "SELECT EXISTS(SELECT 1 FROM upload_sessions WHERE id=$1)",
+    UploadSessionId
+);
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Type)]
 #[sqlx(transparent)]
 pub struct UserId(pub i64);
@@ -168,6 +176,10 @@ pub struct NotificationId(pub i64);
 #[sqlx(transparent)]
 pub struct NotificationActionId(pub i32);
 
+#[derive(Copy, Clone, Debug, Type)]
+#[sqlx(transparent)]
+pub struct UploadSessionId(pub i64);
+
 use crate::models::ids;
 
 impl From<ids::ProjectId> for ProjectId {
@@ -230,3 +242,13 @@ impl From<NotificationId> for ids::NotificationId {
         ids::NotificationId(id.0 as u64)
     }
 }
+impl From<ids::UploadSessionId> for UploadSessionId {
+    fn from(id: ids::UploadSessionId) -> Self {
+        UploadSessionId(id.0 as i64)
+    }
+}
+impl From<UploadSessionId> for ids::UploadSessionId {
+    fn from(id: UploadSessionId) -> Self {
+        ids::UploadSessionId(id.0 as u64)
+    }
+}
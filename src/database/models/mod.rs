@@ -3,12 +3,18 @@
 
 use thiserror::Error;
 
+pub mod analytics_item;
+pub mod blob_item;
 pub mod categories;
+pub mod change_item;
+pub mod dump_item;
 pub mod ids;
 pub mod notification_item;
 pub mod project_item;
+pub mod replica_item;
 pub mod report_item;
 pub mod team_item;
+pub mod upload_session_item;
 pub mod user_item;
 pub mod version_item;
 
@@ -130,6 +130,13 @@ impl ProjectBuilder {
         };
         project_struct.insert(&mut *transaction).await?;
 
+        super::change_item::ChangeBuilder::project(
+            self.project_id,
+            crate::models::changes::ChangeType::Created.as_str(),
+        )
+        .insert(&mut *transaction)
+        .await?;
+
         for mut version in self.initial_versions {
             version.project_id = self.project_id;
             version.insert(&mut *transaction).await?;
@@ -388,6 +395,26 @@ impl Project {
         .execute(&mut *transaction)
         .await?;
 
+        sqlx::query!(
+            "
+            DELETE FROM follow_events
+            WHERE mod_id = $1
+            ",
+            id as ProjectId,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM follower_rollups
+            WHERE mod_id = $1
+            ",
+            id as ProjectId,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
         sqlx::query!(
             "
             DELETE FROM mod_follows
@@ -453,6 +480,22 @@ impl Project {
         .execute(&mut *transaction)
         .await?;
 
+        sqlx::query!(
+            "
+            DELETE FROM mod_ids WHERE project_id = $1
+            ",
+            id as ProjectId,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        super::change_item::ChangeBuilder::project(
+            id,
+            crate::models::changes::ChangeType::Deleted.as_str(),
+        )
+        .insert(&mut *transaction)
+        .await?;
+
         sqlx::query!(
             "
             DELETE FROM mods
@@ -624,7 +667,7 @@ impl Project {
                 "
                 SELECT id
                 FROM versions
-                WHERE mod_id = $1
+                WHERE mod_id = $1 AND NOT quarantined
                 ",
                 id as ProjectId,
             ).fetch_all(executor),
@@ -0,0 +1,202 @@
+/// The state of the copy of a stored file on a secondary file host
+pub struct FileReplica {
+    pub file_name: String,
+    pub replica: String,
+    pub status: ReplicaStatus,
+    pub file_id: Option<String>,
+    pub content_type: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReplicaStatus {
+    /// The replica holds a copy of the file
+    Stored,
+    /// The file still has to be copied to the replica
+    Pending,
+    /// The file was deleted, but the replica still has to delete its copy
+    Deleting,
+}
+
+impl ReplicaStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReplicaStatus::Stored => "stored",
+            ReplicaStatus::Pending => "pending",
+            ReplicaStatus::Deleting => "deleting",
+        }
+    }
+
+    pub fn from_str(string: &str) -> ReplicaStatus {
+        match string {
+            "stored" => ReplicaStatus::Stored,
+            "deleting" => ReplicaStatus::Deleting,
+            _ => ReplicaStatus::Pending,
+        }
+    }
+}
+
+impl FileReplica {
+    /// Records the state of a replica's copy of a file. Failed attempts are
+    /// counted, successful ones reset the count.
+    pub async fn set_status<'a, E>(
+        file_name: &str,
+        replica: &str,
+        status: ReplicaStatus,
+        file_id: Option<&str>,
+        content_type: &str,
+        error: Option<&str>,
+        exec: E,
+    ) -> Result<(), sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            INSERT INTO file_replicas (
+                file_name, replica, status, file_id, content_type,
+                attempts, last_error
+            )
+            VALUES (
+                $1, $2, $3, $4, $5,
+                CASE WHEN $6::text IS NULL THEN 0 ELSE 1 END, $6
+            )
+            ON CONFLICT (file_name, replica) DO UPDATE
+            SET status = EXCLUDED.status,
+                file_id = COALESCE(EXCLUDED.file_id, file_replicas.file_id),
+                attempts = CASE WHEN $6::text IS NULL THEN 0
+                    ELSE file_replicas.attempts + 1 END,
+                last_error = EXCLUDED.last_error,
+                updated = NOW()
+            ",
+            file_name,
+            replica,
+            status.as_str(),
+            file_id,
+            content_type,
+            error,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get<'a, E>(
+        file_name: &str,
+        replica: &str,
+        exec: E,
+    ) -> Result<Option<FileReplica>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT file_name, replica, status, file_id, content_type,
+                attempts, last_error
+            FROM file_replicas
+            WHERE file_name = $1 AND replica = $2
+            ",
+            file_name,
+            replica,
+        )
+        .fetch_optional(exec)
+        .await?;
+
+        Ok(result.map(|r| FileReplica {
+            file_name: r.file_name,
+            replica: r.replica,
+            status: ReplicaStatus::from_str(&r.status),
+            file_id: r.file_id,
+            content_type: r.content_type,
+            attempts: r.attempts,
+            last_error: r.last_error,
+        }))
+    }
+
+    /// Gets the names of the replicas which hold a copy of each file
+    pub async fn get_stored_replicas<'a, E>(
+        file_names: &[String],
+        exec: E,
+    ) -> Result<Vec<(String, String)>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT file_name, replica FROM file_replicas
+            WHERE file_name = ANY($1) AND status = $2
+            ",
+            file_names,
+            ReplicaStatus::Stored.as_str(),
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(result
+            .into_iter()
+            .map(|r| (r.file_name, r.replica))
+            .collect())
+    }
+
+    /// Gets the copies which still have to be uploaded or deleted, least
+    /// recently attempted first
+    pub async fn list_unsynced<'a, E>(
+        limit: i64,
+        exec: E,
+    ) -> Result<Vec<FileReplica>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT file_name, replica, status, file_id, content_type,
+                attempts, last_error
+            FROM file_replicas
+            WHERE status != $1
+            ORDER BY updated
+            LIMIT $2
+            ",
+            ReplicaStatus::Stored.as_str(),
+            limit,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(result
+            .into_iter()
+            .map(|r| FileReplica {
+                file_name: r.file_name,
+                replica: r.replica,
+                status: ReplicaStatus::from_str(&r.status),
+                file_id: r.file_id,
+                content_type: r.content_type,
+                attempts: r.attempts,
+                last_error: r.last_error,
+            })
+            .collect())
+    }
+
+    pub async fn remove<'a, E>(
+        file_name: &str,
+        replica: &str,
+        exec: E,
+    ) -> Result<(), sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            DELETE FROM file_replicas
+            WHERE file_name = $1 AND replica = $2
+            ",
+            file_name,
+            replica,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+}
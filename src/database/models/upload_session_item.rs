@@ -0,0 +1,281 @@
+use super::ids::*;
+use futures::TryStreamExt;
+use time::OffsetDateTime;
+
+pub struct UploadSessionBuilder {
+    pub id: UploadSessionId,
+    pub version_id: VersionId,
+    pub user_id: UserId,
+    pub filename: String,
+    pub size: i64,
+}
+
+impl UploadSessionBuilder {
+    pub async fn insert(
+        self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<UploadSessionId, sqlx::Error> {
+        sqlx::query!(
+            "
+            INSERT INTO upload_sessions (
+                id, version_id, user_id, filename, size
+            )
+            VALUES (
+                $1, $2, $3, $4, $5
+            )
+            ",
+            self.id as UploadSessionId,
+            self.version_id as VersionId,
+            self.user_id as UserId,
+            self.filename,
+            self.size,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        Ok(self.id)
+    }
+}
+
+pub struct UploadSession {
+    pub id: UploadSessionId,
+    pub version_id: VersionId,
+    pub user_id: UserId,
+    pub filename: String,
+    pub size: i64,
+    pub received: i64,
+    pub created: OffsetDateTime,
+    pub expires: OffsetDateTime,
+}
+
+pub struct UploadSessionChunk {
+    pub offset: i64,
+    pub size: i32,
+    pub sha1: String,
+    pub file_id: String,
+    pub file_name: String,
+}
+
+impl UploadSession {
+    /// Gets a session which has not expired yet
+    pub async fn get<'a, E>(
+        id: UploadSessionId,
+        exec: E,
+    ) -> Result<Option<UploadSession>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT id, version_id, user_id, filename, size, received,
+                created, expires
+            FROM upload_sessions
+            WHERE id = $1 AND expires > NOW()
+            ",
+            id as UploadSessionId,
+        )
+        .fetch_optional(exec)
+        .await?;
+
+        Ok(result.map(|s| UploadSession {
+            id: UploadSessionId(s.id),
+            version_id: VersionId(s.version_id),
+            user_id: UserId(s.user_id),
+            filename: s.filename,
+            size: s.size,
+            received: s.received,
+            created: s.created,
+            expires: s.expires,
+        }))
+    }
+
+    /// Gets a session which has not expired yet like `get`, locking it until
+    /// the transaction ends so that it can't be finalized twice at once
+    pub async fn get_for_update(
+        id: UploadSessionId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<UploadSession>, sqlx::Error> {
+        let result = sqlx::query!(
+            "
+            SELECT id, version_id, user_id, filename, size, received,
+                created, expires
+            FROM upload_sessions
+            WHERE id = $1 AND expires > NOW()
+            FOR UPDATE
+            ",
+            id as UploadSessionId,
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        Ok(result.map(|s| UploadSession {
+            id: UploadSessionId(s.id),
+            version_id: VersionId(s.version_id),
+            user_id: UserId(s.user_id),
+            filename: s.filename,
+            size: s.size,
+            received: s.received,
+            created: s.created,
+            expires: s.expires,
+        }))
+    }
+
+    /// Gets how many sessions a user has along with the total size of their
+    /// files. Sessions which expired count until they're removed, since their
+    /// chunks are still stored. The user is locked until the transaction
+    /// ends, so that sessions created concurrently can't exceed any limits.
+    pub async fn get_reserved(
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(i64, i64), sqlx::Error> {
+        sqlx::query!(
+            "
+            SELECT id FROM users
+            WHERE id = $1
+            FOR UPDATE
+            ",
+            user_id as UserId,
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        let result = sqlx::query!(
+            r#"
+            SELECT COUNT(*) "sessions!", COALESCE(SUM(size), 0)::bigint "size!"
+            FROM upload_sessions
+            WHERE user_id = $1
+            "#,
+            user_id as UserId,
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        Ok((result.sessions, result.size))
+    }
+
+    /// Advances the received byte count by `size` if no data was received
+    /// past `offset` yet, locking the session until the transaction ends.
+    /// Returns false if the offset doesn't match the received data.
+    pub async fn reserve_chunk(
+        id: UploadSessionId,
+        offset: i64,
+        size: i64,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "
+            UPDATE upload_sessions
+            SET received = received + $3
+            WHERE id = $1 AND received = $2
+            ",
+            id as UploadSessionId,
+            offset,
+            size,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn insert_chunk(
+        id: UploadSessionId,
+        chunk: &UploadSessionChunk,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "
+            INSERT INTO upload_session_chunks (
+                session_id, chunk_offset, size, sha1, file_id, file_name
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6
+            )
+            ",
+            id as UploadSessionId,
+            chunk.offset,
+            chunk.size,
+            chunk.sha1,
+            chunk.file_id,
+            chunk.file_name,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets the chunks of a session, ordered by their offset
+    pub async fn get_chunks<'a, E>(
+        id: UploadSessionId,
+        exec: E,
+    ) -> Result<Vec<UploadSessionChunk>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            SELECT chunk_offset, size, sha1, file_id, file_name
+            FROM upload_session_chunks
+            WHERE session_id = $1
+            ORDER BY chunk_offset
+            ",
+            id as UploadSessionId,
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async {
+            Ok(e.right().map(|c| UploadSessionChunk {
+                offset: c.chunk_offset,
+                size: c.size,
+                sha1: c.sha1,
+                file_id: c.file_id,
+                file_name: c.file_name,
+            }))
+        })
+        .try_collect::<Vec<UploadSessionChunk>>()
+        .await
+    }
+
+    /// Lists the ids of all sessions which have expired
+    pub async fn get_expired<'a, E>(
+        exec: E,
+    ) -> Result<Vec<UploadSessionId>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            SELECT id FROM upload_sessions
+            WHERE expires < NOW()
+            "
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async {
+            Ok(e.right().map(|s| UploadSessionId(s.id)))
+        })
+        .try_collect::<Vec<UploadSessionId>>()
+        .await
+    }
+
+    /// Removes a session and its chunks from the database. The chunks have
+    /// to be deleted from the file host separately.
+    pub async fn remove<'a, E>(
+        id: UploadSessionId,
+        exec: E,
+    ) -> Result<(), sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            DELETE FROM upload_sessions
+            WHERE id = $1
+            ",
+            id as UploadSessionId,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+}
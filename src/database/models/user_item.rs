@@ -304,6 +304,8 @@ impl User {
         .execute(&mut *transaction)
         .await?;
 
+        super::analytics_item::record_unfollow_all(id, transaction).await?;
+
         sqlx::query!(
             "
             DELETE FROM mod_follows
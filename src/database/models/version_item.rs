@@ -1,5 +1,6 @@
 use super::ids::*;
 use super::DatabaseError;
+use crate::models::projects::FindingSeverity;
 use futures::TryStreamExt;
 use std::collections::HashMap;
 use time::OffsetDateTime;
@@ -44,7 +45,7 @@ impl DependencyBuilder {
                         SELECT DISTINCT ON(v.id) v.id, v.date_published FROM versions v
                         INNER JOIN game_versions_versions gvv ON gvv.joining_version_id = v.id AND gvv.game_version_id IN (SELECT game_version_id FROM game_versions_versions WHERE joining_version_id = $2)
                         INNER JOIN loaders_versions lv ON lv.version_id = v.id AND lv.loader_id IN (SELECT loader_id FROM loaders_versions WHERE version_id = $2)
-                        WHERE v.mod_id = $1
+                        WHERE v.mod_id = $1 AND NOT v.quarantined
                     ) AS version
                     ORDER BY version.date_published DESC
                     LIMIT 1
@@ -83,6 +84,17 @@ pub struct VersionFileBuilder {
     pub hashes: Vec<HashBuilder>,
     pub primary: bool,
     pub size: u32,
+    pub findings: Vec<ValidationFinding>,
+    /// What the upload scanner found in the file, which only moderators get
+    /// to see
+    pub scan_findings: Vec<ValidationFinding>,
+    pub nested_files: Vec<NestedFileBuilder>,
+    pub pack_summary: Option<PackSummary>,
+    /// The SHA512 hash of the stored blob, if the file is stored by the
+    /// hash of its contents
+    pub blob_sha512: Option<String>,
+    /// The mod id declared by the file's manifest
+    pub mod_id: Option<String>,
 }
 
 impl VersionFileBuilder {
@@ -95,15 +107,16 @@ impl VersionFileBuilder {
 
         sqlx::query!(
             "
-            INSERT INTO files (id, version_id, url, filename, is_primary, size)
-            VALUES ($1, $2, $3, $4, $5, $6)
+            INSERT INTO files (id, version_id, url, filename, is_primary, size, blob_sha512)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
             ",
             file_id as FileId,
             version_id as VersionId,
             self.url,
             self.filename,
             self.primary,
-            self.size as i32
+            self.size as i32,
+            self.blob_sha512,
         )
         .execute(&mut *transaction)
         .await?;
@@ -122,10 +135,126 @@ impl VersionFileBuilder {
             .await?;
         }
 
+        // Versions with files flagged by the upload scanner are hidden until
+        // a moderator reviews them
+        if self
+            .scan_findings
+            .iter()
+            .any(|x| x.severity == FindingSeverity::Error.as_str())
+        {
+            sqlx::query!(
+                "
+                UPDATE versions
+                SET quarantined = TRUE
+                WHERE id = $1
+                ",
+                version_id as VersionId,
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        if let Some(mod_id) = self.mod_id {
+            sqlx::query!(
+                "
+                INSERT INTO mod_ids (mod_id, project_id)
+                SELECT $1, mod_id FROM versions WHERE id = $2
+                ON CONFLICT DO NOTHING
+                ",
+                mod_id,
+                version_id as VersionId,
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        for finding in self.findings {
+            sqlx::query!(
+                "
+                INSERT INTO file_validation_findings (file_id, severity, code, message, path)
+                VALUES ($1, $2, $3, $4, $5)
+                ",
+                file_id as FileId,
+                finding.severity,
+                finding.code,
+                finding.message,
+                finding.path,
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        for finding in self.scan_findings {
+            sqlx::query!(
+                "
+                INSERT INTO file_scan_findings (file_id, severity, code, message, path)
+                VALUES ($1, $2, $3, $4, $5)
+                ",
+                file_id as FileId,
+                finding.severity,
+                finding.code,
+                finding.message,
+                finding.path,
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        if let Some(summary) = self.pack_summary {
+            sqlx::query!(
+                "
+                INSERT INTO file_pack_summaries (
+                    file_id, file_count, total_file_size, override_count,
+                    client_override_count, server_override_count, override_jar_count,
+                    minecraft_version, loader, loader_version
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                ",
+                file_id as FileId,
+                summary.file_count,
+                summary.total_file_size,
+                summary.override_count,
+                summary.client_override_count,
+                summary.server_override_count,
+                summary.override_jar_count,
+                summary.minecraft_version,
+                summary.loader,
+                summary.loader_version,
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        for nested_file in self.nested_files {
+            sqlx::query!(
+                "
+                INSERT INTO nested_files (file_id, path, mod_id, version_number, sha1, sha512)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ",
+                file_id as FileId,
+                nested_file.path,
+                nested_file.mod_id,
+                nested_file.version_number,
+                nested_file.sha1,
+                nested_file.sha512,
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
         Ok(file_id)
     }
 }
 
+/// A jar bundled inside of a version file
+pub struct NestedFileBuilder {
+    pub path: String,
+    pub mod_id: Option<String>,
+    pub version_number: Option<String>,
+    pub sha1: Vec<u8>,
+    pub sha512: Vec<u8>,
+}
+
 pub struct HashBuilder {
     pub algorithm: String,
     pub hash: Vec<u8>,
@@ -152,6 +281,14 @@ impl VersionBuilder {
 
         version.insert(&mut *transaction).await?;
 
+        super::change_item::ChangeBuilder::version(
+            self.project_id,
+            self.version_id,
+            crate::models::changes::ChangeType::Created.as_str(),
+        )
+        .insert(&mut *transaction)
+        .await?;
+
         sqlx::query!(
             "
             UPDATE mods
@@ -312,6 +449,26 @@ impl Version {
         .execute(&mut *transaction)
         .await?;
 
+        sqlx::query!(
+            "
+            DELETE FROM downloads
+            WHERE version_id = $1
+            ",
+            id as VersionId,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM download_rollups
+            WHERE version_id = $1
+            ",
+            id as VersionId,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
         let game_versions: Vec<i32> = sqlx::query!(
             "
                 SELECT game_version_id id FROM game_versions_versions
@@ -359,7 +516,7 @@ impl Version {
         let files = sqlx::query!(
             "
             SELECT files.id, files.url, files.filename, files.is_primary FROM files
-            WHERE files.version_id = $1
+            WHERE files.version_id = $1 AND files.blob_sha512 IS NULL
             ",
             id as VersionId,
         )
@@ -387,6 +544,66 @@ impl Version {
             );
         }
 
+        // Blobs are deleted once they lose their last reference, after the
+        // transaction is committed
+        super::blob_item::StoredBlob::release_version(id, transaction).await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM file_pack_summaries
+            WHERE EXISTS(
+                SELECT 1 FROM files WHERE
+                    (files.version_id = $1) AND
+                    (file_pack_summaries.file_id = files.id)
+            )
+            ",
+            id as VersionId
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM nested_files
+            WHERE EXISTS(
+                SELECT 1 FROM files WHERE
+                    (files.version_id = $1) AND
+                    (nested_files.file_id = files.id)
+            )
+            ",
+            id as VersionId
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM file_validation_findings
+            WHERE EXISTS(
+                SELECT 1 FROM files WHERE
+                    (files.version_id = $1) AND
+                    (file_validation_findings.file_id = files.id)
+            )
+            ",
+            id as VersionId
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM file_scan_findings
+            WHERE EXISTS(
+                SELECT 1 FROM files WHERE
+                    (files.version_id = $1) AND
+                    (file_scan_findings.file_id = files.id)
+            )
+            ",
+            id as VersionId
+        )
+        .execute(&mut *transaction)
+        .await?;
+
         sqlx::query!(
             "
             DELETE FROM hashes
@@ -469,6 +686,14 @@ impl Version {
         .execute(&mut *transaction)
         .await?;
 
+        super::change_item::ChangeBuilder::version(
+            ProjectId(project_id.mod_id),
+            id,
+            crate::models::changes::ChangeType::Deleted.as_str(),
+        )
+        .insert(&mut *transaction)
+        .await?;
+
         // delete version
 
         sqlx::query!(
@@ -499,7 +724,7 @@ impl Version {
                 INNER JOIN game_versions gv on gvv.game_version_id = gv.id AND (cardinality($2::varchar[]) = 0 OR gv.version = ANY($2::varchar[]))
                 INNER JOIN loaders_versions lv ON lv.version_id = v.id
                 INNER JOIN loaders l on lv.loader_id = l.id AND (cardinality($3::varchar[]) = 0 OR l.loader = ANY($3::varchar[]))
-                WHERE v.mod_id = $1
+                WHERE v.mod_id = $1 AND NOT v.quarantined
             ) AS version
             ORDER BY version.date_published ASC
             ",
@@ -603,7 +828,16 @@ impl Version {
     where
         E: sqlx::Executor<'a, Database = sqlx::Postgres> + Copy,
     {
-        let (version, game_versions, loaders, files, hashes, dependencies) = futures::join!(
+        let (
+            version,
+            game_versions,
+            loaders,
+            files,
+            hashes,
+            findings,
+            pack_summaries,
+            dependencies,
+        ) = futures::join!(
             sqlx::query!(
                 "
                 SELECT v.id id, v.mod_id mod_id, v.author_id author_id, v.name version_name, v.version_number version_number,
@@ -652,6 +886,28 @@ impl Version {
                 ",
                 id as VersionId,
             ).fetch_all(executor),
+            sqlx::query!(
+                "
+                SELECT fvf.file_id file_id, fvf.severity severity, fvf.code code, fvf.message message, fvf.path path
+                FROM files f
+                INNER JOIN file_validation_findings fvf ON fvf.file_id = f.id
+                WHERE f.version_id = $1
+                ORDER BY fvf.id
+                ",
+                id as VersionId,
+            ).fetch_all(executor),
+            sqlx::query!(
+                "
+                SELECT fps.file_id file_id, fps.file_count file_count, fps.total_file_size total_file_size,
+                fps.override_count override_count, fps.client_override_count client_override_count,
+                fps.server_override_count server_override_count, fps.override_jar_count override_jar_count,
+                fps.minecraft_version minecraft_version, fps.loader loader, fps.loader_version loader_version
+                FROM files f
+                INNER JOIN file_pack_summaries fps ON fps.file_id = f.id
+                WHERE f.version_id = $1
+                ",
+                id as VersionId,
+            ).fetch_all(executor),
             sqlx::query!(
                 "
                 SELECT dependency_id, mod_dependency_id, dependency_file_name, dependency_type
@@ -676,6 +932,42 @@ impl Version {
                 }
             }
 
+            let mut findings_map: HashMap<FileId, Vec<ValidationFinding>> =
+                HashMap::new();
+
+            for finding in findings? {
+                findings_map
+                    .entry(FileId(finding.file_id))
+                    .or_default()
+                    .push(ValidationFinding {
+                        severity: finding.severity,
+                        code: finding.code,
+                        message: finding.message,
+                        path: finding.path,
+                    });
+            }
+
+            let mut pack_summaries_map: HashMap<FileId, PackSummary> =
+                pack_summaries?
+                    .into_iter()
+                    .map(|x| {
+                        (
+                            FileId(x.file_id),
+                            PackSummary {
+                                file_count: x.file_count,
+                                total_file_size: x.total_file_size,
+                                override_count: x.override_count,
+                                client_override_count: x.client_override_count,
+                                server_override_count: x.server_override_count,
+                                override_jar_count: x.override_jar_count,
+                                minecraft_version: x.minecraft_version,
+                                loader: x.loader,
+                                loader_version: x.loader_version,
+                            },
+                        )
+                    })
+                    .collect();
+
             Ok(Some(QueryVersion {
                 id: VersionId(v.id),
                 project_id: ProjectId(v.mod_id),
@@ -698,6 +990,10 @@ impl Version {
                             .clone(),
                         primary: x.is_primary,
                         size: x.size as u32,
+                        findings: findings_map
+                            .remove(&FileId(x.id))
+                            .unwrap_or_default(),
+                        pack_summary: pack_summaries_map.remove(&FileId(x.id)),
                     })
                     .collect(),
                 game_versions: game_versions?
@@ -751,6 +1047,27 @@ pub struct FileHash {
     pub hash: Vec<u8>,
 }
 
+#[derive(Clone)]
+pub struct ValidationFinding {
+    pub severity: String,
+    pub code: String,
+    pub message: String,
+    pub path: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct PackSummary {
+    pub file_count: i32,
+    pub total_file_size: i64,
+    pub override_count: i32,
+    pub client_override_count: i32,
+    pub server_override_count: i32,
+    pub override_jar_count: i32,
+    pub minecraft_version: String,
+    pub loader: Option<String>,
+    pub loader_version: Option<String>,
+}
+
 #[derive(Clone)]
 pub struct QueryVersion {
     pub id: VersionId,
@@ -787,4 +1104,6 @@ pub struct QueryFile {
     pub hashes: HashMap<String, Vec<u8>>,
     pub primary: bool,
     pub size: u32,
+    pub findings: Vec<ValidationFinding>,
+    pub pack_summary: Option<PackSummary>,
 }
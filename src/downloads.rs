@@ -0,0 +1,428 @@
+//! Counting of the downloads reported by the CDN. Downloads are buffered in
+//! memory and written to the database in batches, so that popular files
+//! don't cause two row updates per download. Repeated downloads of a version
+//! from the same address and downloads by bots aren't counted, as far as the
+//! CDN reports the address and user agent of the downloader. Repeated
+//! downloads are recognized from the recorded downloads, so this holds
+//! across restarts and instances. Downloads which are still queued when the
+//! server stops are written on shutdown, but are lost if the process is
+//! killed.
+use crate::database::models::{ProjectId, VersionId};
+use crate::scheduler::Scheduler;
+use crate::util::env::{parse_strings_from_var, parse_var};
+use crate::Pepper;
+use log::{info, warn};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+/// Parts of the user agents of crawlers and link previews, which are never
+/// counted. More can be added with `DOWNLOAD_BOT_USER_AGENTS`.
+const DEFAULT_BOT_USER_AGENTS: [&str; 10] = [
+    "bot",
+    "crawl",
+    "spider",
+    "slurp",
+    "headless",
+    "preview",
+    "facebookexternalhit",
+    "python-requests",
+    "go-http-client",
+    "wget",
+];
+
+struct Download {
+    version_id: VersionId,
+    project_id: ProjectId,
+    /// `None` if the address of the downloader isn't known
+    identifier: Option<String>,
+    date: OffsetDateTime,
+}
+
+pub struct DownloadQueue {
+    /// How long repeated downloads of a version by the same address are
+    /// ignored for
+    dedup_window: Duration,
+    /// Lowercase parts of user agents which aren't counted
+    bot_user_agents: Vec<String>,
+    pending: Mutex<Vec<Download>>,
+    /// When each address last downloaded each version
+    recent: Mutex<HashMap<(String, i64), Instant>>,
+}
+
+impl DownloadQueue {
+    pub fn new(dedup_window: Duration, bot_user_agents: Vec<String>) -> Self {
+        DownloadQueue {
+            dedup_window,
+            bot_user_agents: bot_user_agents
+                .into_iter()
+                .map(|x| x.to_lowercase())
+                .collect(),
+            pending: Mutex::new(Vec::new()),
+            recent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Ignores repeated downloads for `DOWNLOAD_DEDUP_WINDOW` seconds (1 hour
+    /// by default)
+    pub fn from_env() -> Self {
+        let mut bot_user_agents = DEFAULT_BOT_USER_AGENTS
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+        bot_user_agents.extend(
+            parse_strings_from_var("DOWNLOAD_BOT_USER_AGENTS")
+                .unwrap_or_default(),
+        );
+
+        Self::new(
+            Duration::from_secs(
+                parse_var("DOWNLOAD_DEDUP_WINDOW").unwrap_or(3600),
+            ),
+            bot_user_agents,
+        )
+    }
+
+    pub fn is_bot(&self, user_agent: &str) -> bool {
+        let user_agent = user_agent.to_lowercase();
+        self.bot_user_agents
+            .iter()
+            .any(|x| user_agent.contains(x.as_str()))
+    }
+
+    /// Hashes an IP address, so that it isn't stored
+    pub fn identifier(ip: &str, pepper: &Pepper) -> String {
+        sha1::Sha1::from(format!("{}{}", ip, pepper.pepper)).hexdigest()
+    }
+
+    /// Queues a download to be counted, unless the same address downloaded
+    /// the version recently through this instance. Downloads without a known
+    /// address are always counted. Returns whether it was queued.
+    pub fn add(
+        &self,
+        version_id: VersionId,
+        project_id: ProjectId,
+        identifier: Option<String>,
+    ) -> bool {
+        if let Some(identifier) = &identifier {
+            let now = Instant::now();
+            let key = (identifier.clone(), version_id.0);
+
+            let mut recent = self.recent.lock().unwrap();
+            if let Some(last) = recent.get(&key) {
+                if now.duration_since(*last) < self.dedup_window {
+                    return false;
+                }
+            }
+            recent.insert(key, now);
+        }
+
+        self.pending.lock().unwrap().push(Download {
+            version_id,
+            project_id,
+            identifier,
+            date: OffsetDateTime::now_utc(),
+        });
+
+        true
+    }
+
+    /// Writes the queued downloads to the database, returning how many were
+    /// counted. If that fails, they stay queued for the next flush.
+    pub async fn flush(&self, pool: &PgPool) -> Result<usize, sqlx::Error> {
+        let now = Instant::now();
+        self.recent
+            .lock()
+            .unwrap()
+            .retain(|_, last| now.duration_since(*last) < self.dedup_window);
+
+        let downloads = std::mem::take(&mut *self.pending.lock().unwrap());
+        if downloads.is_empty() {
+            return Ok(0);
+        }
+
+        match write_downloads(&downloads, self.dedup_window, pool).await {
+            Ok(count) => Ok(count),
+            Err(e) => {
+                self.pending.lock().unwrap().extend(downloads);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Records the downloads which weren't made by the same address within
+/// `dedup_window` before, and counts them. Returns how many were counted.
+async fn write_downloads(
+    downloads: &[Download],
+    dedup_window: Duration,
+    pool: &PgPool,
+) -> Result<usize, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    // Versions may have been deleted since they were downloaded
+    let counted = sqlx::query!(
+        "
+        INSERT INTO downloads (version_id, date, identifier)
+        SELECT x.version_id, x.date, NULLIF(x.identifier, '')
+        FROM UNNEST($1::bigint[], $2::timestamptz[], $3::varchar[])
+            AS x(version_id, date, identifier)
+        INNER JOIN versions v ON v.id = x.version_id
+        WHERE x.identifier = '' OR NOT EXISTS (
+            SELECT 1 FROM downloads d
+            WHERE d.version_id = x.version_id
+            AND d.identifier = x.identifier
+            AND d.date > x.date - make_interval(secs => $4)
+        )
+        RETURNING version_id
+        ",
+        &downloads.iter().map(|x| x.version_id.0).collect::<Vec<_>>(),
+        &downloads.iter().map(|x| x.date).collect::<Vec<_>>(),
+        &downloads
+            .iter()
+            .map(|x| x.identifier.clone().unwrap_or_default())
+            .collect::<Vec<_>>(),
+        dedup_window.as_secs_f64(),
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    let project_ids = downloads
+        .iter()
+        .map(|x| (x.version_id.0, x.project_id.0))
+        .collect::<HashMap<_, _>>();
+    let mut version_counts: HashMap<i64, i32> = HashMap::new();
+    let mut project_counts: HashMap<i64, i32> = HashMap::new();
+    for download in &counted {
+        *version_counts.entry(download.version_id).or_default() += 1;
+        if let Some(project_id) = project_ids.get(&download.version_id) {
+            *project_counts.entry(*project_id).or_default() += 1;
+        }
+    }
+
+    let (version_ids, version_counts): (Vec<_>, Vec<_>) =
+        version_counts.into_iter().unzip();
+    let (project_ids, project_counts): (Vec<_>, Vec<_>) =
+        project_counts.into_iter().unzip();
+
+    sqlx::query!(
+        "
+        UPDATE versions v
+        SET downloads = v.downloads + x.count
+        FROM UNNEST($1::bigint[], $2::integer[]) AS x(id, count)
+        WHERE v.id = x.id
+        ",
+        &version_ids,
+        &version_counts,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        "
+        UPDATE mods m
+        SET downloads = m.downloads + x.count
+        FROM UNNEST($1::bigint[], $2::integer[]) AS x(id, count)
+        WHERE m.id = x.id
+        ",
+        &project_ids,
+        &project_counts,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(counted.len())
+}
+
+/// Gets the pepper addresses are hashed with, which is created along with the
+/// table downloads are recorded in
+pub async fn get_pepper(pool: &PgPool) -> Result<Pepper, sqlx::Error> {
+    let pepper = sqlx::query!("SELECT pepper FROM download_pepper")
+        .fetch_one(pool)
+        .await?
+        .pepper;
+
+    Ok(Pepper { pepper })
+}
+
+pub fn schedule_download_flushes(
+    scheduler: &mut Scheduler,
+    pool: PgPool,
+    queue: Arc<DownloadQueue>,
+) {
+    let flush_interval = std::time::Duration::from_secs(
+        parse_var("DOWNLOAD_FLUSH_INTERVAL").unwrap_or(30),
+    );
+
+    scheduler.run(flush_interval, move || {
+        let pool_ref = pool.clone();
+        let queue_ref = queue.clone();
+        async move {
+            match queue_ref.flush(&pool_ref).await {
+                Ok(0) => {}
+                Ok(count) => info!("Counted {} downloads", count),
+                Err(e) => warn!("Counting downloads failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_pepper, DownloadQueue};
+    use crate::database::models::{ProjectId, VersionId};
+    use crate::Pepper;
+    use sqlx::PgPool;
+    use std::time::Duration;
+
+    fn queue(dedup_window: u64) -> DownloadQueue {
+        DownloadQueue::new(
+            Duration::from_secs(dedup_window),
+            vec!["Bot".to_string()],
+        )
+    }
+
+    #[test]
+    fn deduplicates_downloads() {
+        let downloads = queue(3600);
+
+        let a = || Some("a".to_string());
+        assert!(downloads.add(VersionId(1), ProjectId(1), a()));
+        assert!(!downloads.add(VersionId(1), ProjectId(1), a()));
+        assert!(downloads.add(VersionId(2), ProjectId(1), a()));
+        assert!(downloads.add(VersionId(1), ProjectId(1), Some("b".into())));
+        assert_eq!(downloads.pending.lock().unwrap().len(), 3);
+
+        let downloads = queue(0);
+        assert!(downloads.add(VersionId(1), ProjectId(1), a()));
+        assert!(downloads.add(VersionId(1), ProjectId(1), a()));
+    }
+
+    #[test]
+    fn counts_downloads_from_unknown_addresses() {
+        let downloads = queue(3600);
+
+        assert!(downloads.add(VersionId(1), ProjectId(1), None));
+        assert!(downloads.add(VersionId(1), ProjectId(1), None));
+        assert_eq!(downloads.pending.lock().unwrap().len(), 2);
+        assert!(downloads.recent.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn filters_bots() {
+        let downloads = queue(3600);
+
+        assert!(downloads.is_bot("Mozilla/5.0 (compatible; Googlebot/2.1)"));
+        assert!(!downloads.is_bot("PrismLauncher/5.0"));
+    }
+
+    #[test]
+    fn hashes_addresses() {
+        let pepper = Pepper {
+            pepper: "pepper".to_string(),
+        };
+        let identifier = DownloadQueue::identifier("127.0.0.1", &pepper);
+
+        assert_eq!(identifier.len(), 40);
+        assert_ne!(identifier, DownloadQueue::identifier("127.0.0.2", &pepper));
+    }
+
+    async fn pool() -> PgPool {
+        PgPool::connect(&dotenv::var("DATABASE_URL").unwrap())
+            .await
+            .unwrap()
+    }
+
+    /// Inserts a version of a new project by a new user, returning their ids
+    async fn insert_version(pool: &PgPool) -> (ProjectId, VersionId) {
+        let id = i64::from(rand::random::<u32>());
+
+        sqlx::query!(
+            "
+            WITH author AS (
+                INSERT INTO users (id, username) VALUES ($1, md5(random()::text))
+            ), team AS (
+                INSERT INTO teams (id) VALUES ($1)
+            ), project AS (
+                INSERT INTO mods (id, team_id, title, description, status, project_type)
+                SELECT $1, $1, 'Test', 'Test', s.id, pt.id
+                FROM statuses s, project_types pt
+                WHERE s.status = 'approved' AND pt.name = 'mod'
+            )
+            INSERT INTO versions (id, mod_id, author_id, name, version_number, version_type)
+            VALUES ($1, $1, $1, 'Test', '1.0.0', 'release')
+            ",
+            id,
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+
+        (ProjectId(id), VersionId(id))
+    }
+
+    async fn remove_version(pool: &PgPool, version_id: VersionId) {
+        sqlx::query!(
+            "
+            WITH downloads AS (
+                DELETE FROM downloads WHERE version_id = $1
+            ), version AS (
+                DELETE FROM versions WHERE id = $1
+            ), project AS (
+                DELETE FROM mods WHERE id = $1
+            ), team AS (
+                DELETE FROM teams WHERE id = $1
+            )
+            DELETE FROM users WHERE id = $1
+            ",
+            version_id.0,
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[actix_rt::test]
+    #[ignore = "needs a migrated database at DATABASE_URL"]
+    async fn deduplicates_downloads_across_instances() {
+        let pool = pool().await;
+        let (project_id, version_id) = insert_version(&pool).await;
+        let identifier = Some(format!("{:032x}", rand::random::<u128>()));
+
+        let first = queue(3600);
+        assert!(first.add(version_id, project_id, identifier.clone()));
+        assert_eq!(first.flush(&pool).await.unwrap(), 1);
+
+        // Another instance, or this one after a restart
+        let second = queue(3600);
+        assert!(second.add(version_id, project_id, identifier.clone()));
+        assert!(second.add(version_id, project_id, None));
+        assert_eq!(second.flush(&pool).await.unwrap(), 1);
+
+        let downloads = sqlx::query!(
+            "SELECT downloads FROM versions WHERE id = $1",
+            version_id.0,
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap()
+        .downloads;
+        assert_eq!(downloads, 2);
+
+        remove_version(&pool, version_id).await;
+    }
+
+    #[actix_rt::test]
+    #[ignore = "needs a migrated database at DATABASE_URL"]
+    async fn peppers_are_persisted() {
+        let pool = pool().await;
+
+        let pepper = get_pepper(&pool).await.unwrap().pepper;
+        assert_eq!(pepper.len(), 32);
+        assert_eq!(get_pepper(&pool).await.unwrap().pepper, pepper);
+    }
+}
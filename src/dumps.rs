@@ -0,0 +1,218 @@
+//! Periodic exports of the public catalogue, so that third-party tools can
+//! download everything at once instead of scraping the API page by page.
+use crate::database::locks::AdvisoryLock;
+use crate::database::models::dump_item::{Dump, DumpBuilder};
+use crate::database::models::{self, ProjectId};
+use crate::file_hosting::{FileHost, FileHostingError};
+use crate::models::projects::{Project, ProjectStatus, Version};
+use crate::scheduler::Scheduler;
+use crate::util::env::parse_var;
+use crate::util::spool::SpoolWriter;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::TryStreamExt;
+use log::{info, warn};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::io::Write;
+use std::sync::Arc;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+// The number of projects which are fetched from the database at once
+const DUMP_CHUNK_SIZE: usize = 100;
+
+/// How often (in seconds) to check whether a dump is due. This bounds how
+/// long a restart can delay a dump.
+const DUMP_CHECK_INTERVAL: u64 = 3600;
+
+#[derive(Error, Debug)]
+pub enum DumpError {
+    #[error("Database Error: {0}")]
+    Sqlx(#[from] sqlx::error::Error),
+    #[error("Error while uploading dump: {0}")]
+    FileHosting(#[from] FileHostingError),
+    #[error("Error while serializing JSON: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("Error while compressing dump: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Environment Error")]
+    Env(#[from] dotenv::Error),
+}
+
+/// A single line of a dump
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum DumpEntry {
+    Project(Project),
+    Version(Version),
+}
+
+pub fn schedule_dumps(
+    scheduler: &mut Scheduler,
+    pool: PgPool,
+    file_host: Arc<dyn FileHost + Send + Sync>,
+) {
+    let dump_interval = std::time::Duration::from_secs(
+        parse_var("DUMP_INTERVAL").unwrap_or(86400),
+    );
+    let check_interval =
+        dump_interval.min(std::time::Duration::from_secs(DUMP_CHECK_INTERVAL));
+
+    scheduler.run(check_interval, move || {
+        let pool_ref = pool.clone();
+        let file_host_ref = file_host.clone();
+        async move {
+            // Only one instance may check for and write a dump at a time, as
+            // they would all find it due otherwise
+            let result = AdvisoryLock::Dump
+                .run(
+                    &pool_ref,
+                    dump_if_due(&pool_ref, &*file_host_ref, dump_interval),
+                )
+                .await;
+
+            match result {
+                Ok(Some(Ok(()))) | Ok(None) => {}
+                Ok(Some(Err(e))) => {
+                    warn!("Creating catalogue dump failed: {}", e)
+                }
+                Err(e) => warn!("Creating catalogue dump failed: {}", e),
+            }
+        }
+    });
+}
+
+async fn dump_if_due(
+    pool: &PgPool,
+    file_host: &(dyn FileHost + Send + Sync),
+    dump_interval: std::time::Duration,
+) -> Result<(), DumpError> {
+    if !is_dump_due(pool, dump_interval).await? {
+        return Ok(());
+    }
+
+    info!("Creating catalogue dump");
+    create_dump(pool, file_host).await?;
+    info!("Done creating catalogue dump");
+
+    Ok(())
+}
+
+/// Whether the newest dump is at least `dump_interval` old. This is read
+/// from the database so that restarting doesn't postpone or repeat dumps.
+async fn is_dump_due(
+    pool: &PgPool,
+    dump_interval: std::time::Duration,
+) -> Result<bool, sqlx::Error> {
+    let latest = Dump::get_latest_created(pool).await?;
+
+    Ok(latest.map_or(true, |x| OffsetDateTime::now_utc() - x >= dump_interval))
+}
+
+async fn create_dump(
+    pool: &PgPool,
+    file_host: &(dyn FileHost + Send + Sync),
+) -> Result<(), DumpError> {
+    let cdn_url = dotenv::var("CDN_URL")?;
+
+    let statuses = [ProjectStatus::Approved, ProjectStatus::Archived]
+        .iter()
+        .map(|x| x.as_str().to_string())
+        .collect::<Vec<_>>();
+
+    let project_ids = sqlx::query!(
+        "
+        SELECT m.id FROM mods m
+        INNER JOIN statuses s ON s.id = m.status
+        WHERE s.status = ANY($1)
+        ORDER BY m.id
+        ",
+        &statuses,
+    )
+    .fetch_many(pool)
+    .try_filter_map(|e| async { Ok(e.right().map(|m| ProjectId(m.id))) })
+    .try_collect::<Vec<ProjectId>>()
+    .await?;
+
+    // The compressed dump is moved to a temporary file after every chunk, so
+    // that it's never held in memory as a whole
+    let mut spool = SpoolWriter::new().await?;
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let mut project_count = 0;
+    let mut version_count = 0;
+
+    for chunk in project_ids.chunks(DUMP_CHUNK_SIZE) {
+        let projects =
+            models::Project::get_many_full(chunk.to_vec(), pool).await?;
+
+        for project in projects {
+            let versions =
+                models::Version::get_many_full(project.versions.clone(), pool)
+                    .await?;
+
+            write_entry(&mut encoder, &DumpEntry::Project(project.into()))?;
+            project_count += 1;
+
+            for version in versions {
+                write_entry(&mut encoder, &DumpEntry::Version(version.into()))?;
+                version_count += 1;
+            }
+        }
+
+        let compressed = std::mem::take(encoder.get_mut());
+        spool.write(compressed.into()).await?;
+    }
+
+    spool.write(encoder.finish()?.into()).await?;
+    let data = spool.finish()?;
+
+    let file_name = format!(
+        "dumps/catalogue-{}.jsonl.gz",
+        OffsetDateTime::now_utc().format("%Y%m%d%H%M%S")
+    );
+    let upload_data = data
+        .upload(file_host, "application/gzip", &file_name)
+        .await?;
+
+    DumpBuilder {
+        file_id: upload_data.file_id,
+        url: format!("{}/{}", cdn_url, file_name),
+        file_name,
+        size: data.size() as i64,
+        sha1: upload_data.content_sha1,
+        sha512: upload_data.content_sha512,
+        projects: project_count,
+        versions: version_count,
+    }
+    .insert(pool)
+    .await?;
+
+    remove_old_dumps(pool, file_host).await
+}
+
+fn write_entry(
+    encoder: &mut GzEncoder<Vec<u8>>,
+    entry: &DumpEntry,
+) -> Result<(), DumpError> {
+    serde_json::to_writer(&mut *encoder, entry)?;
+    encoder.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Deletes all but the newest `DUMP_RETENTION` dumps (7 by default)
+async fn remove_old_dumps(
+    pool: &PgPool,
+    file_host: &(dyn FileHost + Send + Sync),
+) -> Result<(), DumpError> {
+    let retention = parse_var("DUMP_RETENTION").unwrap_or(7);
+
+    for dump in Dump::list(pool).await?.into_iter().skip(retention) {
+        file_host
+            .delete_file_version(&dump.file_id, &dump.file_name)
+            .await?;
+        Dump::remove(dump.id, pool).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,180 @@
+//! Computing the hashes of version files which were uploaded before every
+//! hash Maven clients ask for was computed on upload. Files are streamed from
+//! the file host one at a time, so this runs in the background instead of
+//! when a hash is requested.
+use crate::database::locks::AdvisoryLock;
+use crate::file_hosting::{FileHost, FileHostingError};
+use crate::scheduler::Scheduler;
+use crate::util::cdn::stored_file_name;
+use crate::util::env::parse_var;
+use log::{info, warn};
+use sha2::Digest;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+/// The number of files which are fetched from the database at once
+const BACKFILL_CHUNK_SIZE: i64 = 100;
+/// The number of files which are downloaded in a single run at most, so that
+/// a backlog is worked off over several runs
+const MAX_FILES_PER_RUN: usize = 1000;
+/// How long the URLs files are downloaded from stay valid
+const DOWNLOAD_URL_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Error, Debug)]
+pub enum HashBackfillError {
+    #[error("Database Error: {0}")]
+    Sqlx(#[from] sqlx::error::Error),
+    #[error("Error while reading stored file: {0}")]
+    FileHosting(#[from] FileHostingError),
+    #[error("Error while downloading stored file: {0}")]
+    Download(#[from] reqwest::Error),
+}
+
+pub fn schedule_hash_backfill(
+    scheduler: &mut Scheduler,
+    pool: PgPool,
+    file_host: Arc<dyn FileHost + Send + Sync>,
+) {
+    let backfill_interval = std::time::Duration::from_secs(
+        parse_var("HASH_BACKFILL_INTERVAL").unwrap_or(3600),
+    );
+
+    scheduler.run(backfill_interval, move || {
+        let pool_ref = pool.clone();
+        let file_host_ref = file_host.clone();
+        async move {
+            // Runs can take longer than the interval, and mustn't overlap
+            // with each other or with runs on other instances
+            let result = AdvisoryLock::HashBackfill
+                .run(&pool_ref, backfill_hashes(&pool_ref, &*file_host_ref))
+                .await;
+
+            match result {
+                Ok(None) | Ok(Some(Ok(0))) => {}
+                Ok(Some(Ok(count))) => {
+                    info!("Computed the hashes of {} files", count)
+                }
+                Ok(Some(Err(e))) => {
+                    warn!("Computing missing file hashes failed: {}", e)
+                }
+                Err(e) => warn!("Computing missing file hashes failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Computes the MD5 and SHA256 hashes of files which are missing one of them,
+/// returning how many files were hashed. Files which can't be read are
+/// skipped and tried again on the next run.
+async fn backfill_hashes(
+    pool: &PgPool,
+    file_host: &(dyn FileHost + Send + Sync),
+) -> Result<usize, HashBackfillError> {
+    let mut last_id = 0;
+    let mut count = 0;
+    let mut downloaded = 0;
+
+    while downloaded < MAX_FILES_PER_RUN {
+        let files = sqlx::query!(
+            "
+            SELECT f.id, f.url FROM files f
+            WHERE f.id > $1 AND (
+                NOT EXISTS (SELECT 1 FROM hashes h WHERE h.file_id = f.id AND h.algorithm = 'md5')
+                OR NOT EXISTS (SELECT 1 FROM hashes h WHERE h.file_id = f.id AND h.algorithm = 'sha256')
+            )
+            ORDER BY f.id
+            LIMIT $2
+            ",
+            last_id,
+            BACKFILL_CHUNK_SIZE,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        if files.is_empty() {
+            break;
+        }
+
+        for file in files {
+            if downloaded >= MAX_FILES_PER_RUN {
+                break;
+            }
+            last_id = file.id;
+
+            match hash_file(file.id, &file.url, pool, file_host).await {
+                Ok(HashResult::Hashed) => {
+                    downloaded += 1;
+                    count += 1;
+                }
+                Ok(HashResult::NotStored) => {}
+                Err(e) => {
+                    downloaded += 1;
+                    warn!("Computing the hashes of {} failed: {}", file.url, e)
+                }
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+enum HashResult {
+    Hashed,
+    /// The file isn't stored by us, so there is nothing to hash
+    NotStored,
+}
+
+async fn hash_file(
+    file_id: i64,
+    url: &str,
+    pool: &PgPool,
+    file_host: &(dyn FileHost + Send + Sync),
+) -> Result<HashResult, HashBackfillError> {
+    let file_name = match stored_file_name(url) {
+        Some(file_name) => file_name,
+        None => return Ok(HashResult::NotStored),
+    };
+    let stored_file = match file_host.head_file("", &file_name).await? {
+        Some(stored_file) => stored_file,
+        None => return Ok(HashResult::NotStored),
+    };
+
+    // The file is streamed, as it could be too large to be held in memory
+    let download_url = file_host
+        .signed_download_url(
+            &stored_file.file_id,
+            &file_name,
+            DOWNLOAD_URL_EXPIRY,
+        )
+        .await?;
+    let mut response = reqwest::get(&download_url).await?.error_for_status()?;
+
+    let mut md5 = md5::Md5::new();
+    let mut sha256 = sha2::Sha256::new();
+    while let Some(chunk) = response.chunk().await? {
+        md5.update(&chunk);
+        sha256.update(&chunk);
+    }
+
+    let md5 = format!("{:x}", md5.finalize());
+    let sha256 = format!("{:x}", sha256.finalize());
+
+    // Hashes are stored as the bytes of their hex strings
+    sqlx::query!(
+        "
+        INSERT INTO hashes (file_id, algorithm, hash)
+        SELECT $1, x.algorithm, x.hash
+        FROM UNNEST($2::varchar[], $3::bytea[]) AS x(algorithm, hash)
+        ON CONFLICT DO NOTHING
+        ",
+        file_id,
+        &["md5".to_string(), "sha256".to_string()][..],
+        &[md5.into_bytes(), sha256.into_bytes()][..],
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(HashResult::Hashed)
+}
@@ -1,33 +1,366 @@
-use super::{DeleteFileData, FileHost, FileHostingError, UploadFileData};
+use super::{
+    DeleteFileData, FileHost, FileHostingError, FileMetadata, FileStream,
+    ListedFile, StreamHasher, UploadFileData,
+};
 use async_trait::async_trait;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
 use reqwest::Response;
 use serde::Deserialize;
 use sha2::Digest;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
 
 mod authorization;
 mod delete;
+mod download;
+#[cfg(test)]
+mod fake_server;
+mod large_file;
+mod list;
 mod upload;
 
+/// The longest time Backblaze allows download authorizations to be valid
+const MAX_DOWNLOAD_AUTHORIZATION_DURATION: u64 = 7 * 24 * 60 * 60;
+
+const DEFAULT_API_URL: &str = "https://api.backblazeb2.com";
+
+/// How often a request is tried before its error is returned
+const MAX_ATTEMPTS: u32 = 5;
+
+/// How many unused upload URLs are kept for later uploads
+const MAX_POOLED_UPLOAD_URLS: usize = 8;
+
+/// How a failed request should be handled
+#[derive(Debug, PartialEq)]
+enum Failure {
+    /// The authorization token expired, so the account has to be authorized
+    /// again
+    Unauthorized,
+    /// Backblaze is busy or unreachable, so the request can be retried later
+    Unavailable,
+    Other,
+}
+
+impl Failure {
+    fn of(error: &FileHostingError) -> Self {
+        match error {
+            FileHostingError::BackblazeError(e) => {
+                match (e["status"].as_u64(), e["code"].as_str()) {
+                    (_, Some("expired_auth_token"))
+                    | (_, Some("bad_auth_token")) => Failure::Unauthorized,
+                    (Some(status), _)
+                        if status == 408 || status == 429 || status >= 500 =>
+                    {
+                        Failure::Unavailable
+                    }
+                    _ => Failure::Other,
+                }
+            }
+            FileHostingError::HttpError(e)
+                if e.is_timeout() || e.is_connect() || e.is_request() =>
+            {
+                Failure::Unavailable
+            }
+            _ => Failure::Other,
+        }
+    }
+}
+
 pub struct BackblazeHost {
-    upload_url_data: authorization::UploadUrlData,
-    authorization_data: authorization::AuthorizationData,
+    api_url: String,
+    key_id: String,
+    key: String,
+    bucket_id: String,
+    bucket_name: String,
+    /// Replaced whenever Backblaze reports the token as expired
+    authorization_data: Mutex<authorization::AuthorizationData>,
+    /// Each upload URL can only be used by one upload at a time, so uploads
+    /// take one out of the pool and put it back once they succeeded
+    upload_urls: Mutex<Vec<authorization::UploadUrlData>>,
+    /// How long to wait before retrying unavailable requests for the first
+    /// time, doubled for each further attempt
+    retry_delay: Duration,
 }
 
 impl BackblazeHost {
-    pub async fn new(key_id: &str, key: &str, bucket_id: &str) -> Self {
+    pub async fn new(
+        key_id: &str,
+        key: &str,
+        bucket_id: &str,
+    ) -> Result<Self, FileHostingError> {
+        Self::connect(
+            DEFAULT_API_URL,
+            key_id,
+            key,
+            bucket_id,
+            Duration::from_secs(1),
+        )
+        .await
+    }
+
+    async fn connect(
+        api_url: &str,
+        key_id: &str,
+        key: &str,
+        bucket_id: &str,
+        retry_delay: Duration,
+    ) -> Result<Self, FileHostingError> {
         let authorization_data =
-            authorization::authorize_account(key_id, key).await.unwrap();
-        let upload_url_data =
-            authorization::get_upload_url(&authorization_data, bucket_id)
+            authorization::authorize_account(api_url, key_id, key).await?;
+        let bucket_name =
+            authorization::get_bucket_name(&authorization_data, bucket_id)
+                .await?;
+
+        Ok(BackblazeHost {
+            api_url: api_url.to_string(),
+            key_id: key_id.to_string(),
+            key: key.to_string(),
+            bucket_id: bucket_id.to_string(),
+            bucket_name,
+            authorization_data: Mutex::new(authorization_data),
+            upload_urls: Mutex::new(Vec::new()),
+            retry_delay,
+        })
+    }
+
+    fn authorization(&self) -> authorization::AuthorizationData {
+        self.authorization_data.lock().unwrap().clone()
+    }
+
+    async fn reauthorize(&self) -> Result<(), FileHostingError> {
+        let authorization_data = authorization::authorize_account(
+            &self.api_url,
+            &self.key_id,
+            &self.key,
+        )
+        .await?;
+        *self.authorization_data.lock().unwrap() = authorization_data;
+
+        Ok(())
+    }
+
+    /// Decides whether a failed request is retried, authorizing again or
+    /// waiting first if needed. `attempt` is the number of the failed
+    /// attempt, starting at 1.
+    async fn should_retry(
+        &self,
+        error: &FileHostingError,
+        attempt: u32,
+        reauthorize: bool,
+    ) -> Result<bool, FileHostingError> {
+        if attempt >= MAX_ATTEMPTS {
+            return Ok(false);
+        }
+
+        match Failure::of(error) {
+            Failure::Unauthorized => {
+                if reauthorize {
+                    self.reauthorize().await?;
+                }
+                Ok(true)
+            }
+            Failure::Unavailable => {
+                actix_rt::time::sleep(self.retry_delay * 2u32.pow(attempt - 1))
+                    .await;
+                Ok(true)
+            }
+            Failure::Other => Ok(false),
+        }
+    }
+
+    /// Sends a request with the current authorization, retrying it if the
+    /// token expired or Backblaze is unavailable
+    async fn with_authorization<T, F, Fut>(
+        &self,
+        mut request: F,
+    ) -> Result<T, FileHostingError>
+    where
+        F: FnMut(authorization::AuthorizationData) -> Fut,
+        Fut: Future<Output = Result<T, FileHostingError>>,
+    {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let error = match request(self.authorization()).await {
+                Ok(result) => return Ok(result),
+                Err(e) => e,
+            };
+
+            if !self.should_retry(&error, attempt, true).await? {
+                return Err(error);
+            }
+        }
+    }
+
+    async fn take_upload_url(
+        &self,
+    ) -> Result<authorization::UploadUrlData, FileHostingError> {
+        let pooled = self.upload_urls.lock().unwrap().pop();
+        match pooled {
+            Some(url_data) => Ok(url_data),
+            None => {
+                self.with_authorization(|authorization_data| async move {
+                    authorization::get_upload_url(
+                        &authorization_data,
+                        &self.bucket_id,
+                    )
+                    .await
+                })
                 .await
-                .unwrap();
+            }
+        }
+    }
+
+    fn return_upload_url(&self, url_data: authorization::UploadUrlData) {
+        let mut upload_urls = self.upload_urls.lock().unwrap();
+        if upload_urls.len() < MAX_POOLED_UPLOAD_URLS {
+            upload_urls.push(url_data);
+        }
+    }
+
+    /// Uploads a file with a pooled upload URL. Backblaze asks for a new
+    /// upload URL after any failed upload, so failed URLs are dropped.
+    async fn upload_with_pool(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        file_bytes: Bytes,
+    ) -> Result<upload::UploadFileData, FileHostingError> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let url_data = self.take_upload_url().await?;
+            let error = match upload::upload_file(
+                &url_data,
+                content_type,
+                file_name,
+                file_bytes.clone(),
+            )
+            .await
+            {
+                Ok(upload_data) => {
+                    self.return_upload_url(url_data);
+                    return Ok(upload_data);
+                }
+                Err(e) => e,
+            };
+
+            // Upload URLs have their own tokens, so the new upload URL is
+            // enough if the old one expired
+            if !self.should_retry(&error, attempt, false).await? {
+                return Err(error);
+            }
+        }
+    }
 
-        BackblazeHost {
-            upload_url_data,
-            authorization_data,
+    /// Uploads a part of a large file, getting a new part upload URL if the
+    /// current one fails
+    async fn upload_part_with_retries(
+        &self,
+        file_id: &str,
+        url_data: &mut large_file::UploadPartUrlData,
+        part_number: u32,
+        part_bytes: Bytes,
+    ) -> Result<large_file::UploadPartData, FileHostingError> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let error = match large_file::upload_part(
+                url_data,
+                part_number,
+                part_bytes.clone(),
+            )
+            .await
+            {
+                Ok(part) => return Ok(part),
+                Err(e) => e,
+            };
+
+            if !self.should_retry(&error, attempt, false).await? {
+                return Err(error);
+            }
+            *url_data = self.get_upload_part_url(file_id).await?;
         }
     }
+
+    async fn get_upload_part_url(
+        &self,
+        file_id: &str,
+    ) -> Result<large_file::UploadPartUrlData, FileHostingError> {
+        self.with_authorization(|authorization_data| async move {
+            large_file::get_upload_part_url(&authorization_data, file_id).await
+        })
+        .await
+    }
+
+    async fn upload_parts(
+        &self,
+        file_id: &str,
+        part_size: usize,
+        content_length: u64,
+        mut stream: FileStream,
+    ) -> Result<UploadFileData, FileHostingError> {
+        let mut url_data = self.get_upload_part_url(file_id).await?;
+
+        let mut hasher = StreamHasher::new();
+        let mut part_sha1s = Vec::new();
+        let mut buffer = BytesMut::with_capacity(part_size);
+
+        loop {
+            let chunk = stream.next().await.transpose()?;
+            if let Some(chunk) = &chunk {
+                hasher.update(chunk);
+                buffer.extend_from_slice(chunk);
+            }
+
+            while buffer.len() >= part_size
+                || (chunk.is_none() && !buffer.is_empty())
+            {
+                let len = buffer.len().min(part_size);
+                let part = self
+                    .upload_part_with_retries(
+                        file_id,
+                        &mut url_data,
+                        part_sha1s.len() as u32 + 1,
+                        buffer.split_to(len).freeze(),
+                    )
+                    .await?;
+                part_sha1s.push(part.content_sha1);
+            }
+
+            if chunk.is_none() {
+                break;
+            }
+        }
+
+        let hashes = hasher.finish(content_length)?;
+
+        let part_sha1s = &part_sha1s;
+        let upload_data = self
+            .with_authorization(|authorization_data| async move {
+                large_file::finish_large_file(
+                    &authorization_data,
+                    file_id,
+                    part_sha1s,
+                )
+                .await
+            })
+            .await?;
+
+        // Backblaze doesn't compute the SHA1 hash of large files
+        Ok(UploadFileData {
+            file_id: upload_data.file_id,
+            file_name: upload_data.file_name,
+            content_length: hashes.content_length,
+            content_sha512: hashes.content_sha512,
+            content_sha1: hashes.content_sha1,
+            content_md5: upload_data.content_md5,
+            content_type: upload_data.content_type,
+            upload_timestamp: upload_data.upload_timestamp,
+        })
+    }
 }
 
 #[async_trait]
@@ -40,13 +373,9 @@ impl FileHost for BackblazeHost {
     ) -> Result<UploadFileData, FileHostingError> {
         let content_sha512 = format!("{:x}", sha2::Sha512::digest(&file_bytes));
 
-        let upload_data = upload::upload_file(
-            &self.upload_url_data,
-            content_type,
-            file_name,
-            file_bytes,
-        )
-        .await?;
+        let upload_data = self
+            .upload_with_pool(content_type, file_name, file_bytes)
+            .await?;
         Ok(UploadFileData {
             file_id: upload_data.file_id,
             file_name: upload_data.file_name,
@@ -59,39 +388,215 @@ impl FileHost for BackblazeHost {
         })
     }
 
-    /*
-        async fn upload_file_streaming(
-            &self,
-            content_type: &str,
-            file_name: &str,
-            stream: reqwest::Body
-        ) -> Result<UploadFileData, FileHostingError> {
-            use futures::stream::StreamExt;
+    /// Uploads files larger than the minimum part size through the large
+    /// file API, keeping at most one part in memory at a time
+    async fn upload_file_streaming(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        content_length: u64,
+        mut stream: FileStream,
+    ) -> Result<UploadFileData, FileHostingError> {
+        let part_size =
+            self.authorization().absolute_minimum_part_size.max(1) as usize;
 
-            let mut data = Vec::new();
+        // Large files need at least two parts, so smaller files are
+        // uploaded in one go. Streams longer than they claim to be are
+        // rejected as soon as they exceed that length, so that no more
+        // than one part is ever held in memory.
+        if content_length <= part_size as u64 {
+            let mut data = BytesMut::new();
             while let Some(chunk) = stream.next().await {
-                data.extend_from_slice(&chunk.map_err(|e| FileHostingError::Other(e))?);
+                data.extend_from_slice(&chunk?);
+                if data.len() as u64 > content_length {
+                    return Err(FileHostingError::ContentLengthMismatch {
+                        expected: content_length,
+                        received: data.len() as u64,
+                    });
+                }
+            }
+            if data.len() as u64 != content_length {
+                return Err(FileHostingError::ContentLengthMismatch {
+                    expected: content_length,
+                    received: data.len() as u64,
+                });
+            }
+
+            return self
+                .upload_file(content_type, file_name, data.freeze())
+                .await;
+        }
+
+        let large_file = self
+            .with_authorization(|authorization_data| async move {
+                large_file::start_large_file(
+                    &authorization_data,
+                    &self.bucket_id,
+                    content_type,
+                    file_name,
+                )
+                .await
+            })
+            .await?;
+
+        let result = self
+            .upload_parts(
+                &large_file.file_id,
+                part_size,
+                content_length,
+                stream,
+            )
+            .await;
+
+        if result.is_err() {
+            let file_id = &large_file.file_id;
+            let cancel_result = self
+                .with_authorization(|authorization_data| async move {
+                    large_file::cancel_large_file(&authorization_data, file_id)
+                        .await
+                })
+                .await;
+
+            // The upload's own error is the one worth reporting
+            if let Err(e) = cancel_result {
+                log::warn!(
+                    "Cancelling large file upload {} failed: {}",
+                    file_id,
+                    e
+                );
             }
-            self.upload_file(content_type, file_name, data).await
         }
-    */
+
+        result
+    }
 
     async fn delete_file_version(
         &self,
         file_id: &str,
         file_name: &str,
     ) -> Result<DeleteFileData, FileHostingError> {
-        let delete_data = delete::delete_file_version(
-            &self.authorization_data,
-            file_id,
-            file_name,
-        )
-        .await?;
+        let delete_data = self
+            .with_authorization(|authorization_data| async move {
+                delete::delete_file_version(
+                    &authorization_data,
+                    file_id,
+                    file_name,
+                )
+                .await
+            })
+            .await?;
         Ok(DeleteFileData {
             file_id: delete_data.file_id,
             file_name: delete_data.file_name,
         })
     }
+
+    async fn get_file(
+        &self,
+        file_id: &str,
+        _file_name: &str,
+    ) -> Result<Bytes, FileHostingError> {
+        self.with_authorization(|authorization_data| async move {
+            download::download_file_by_id(&authorization_data, file_id).await
+        })
+        .await
+    }
+
+    async fn list_files(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<ListedFile>, FileHostingError> {
+        let files = self
+            .with_authorization(|authorization_data| async move {
+                list::list_file_names(
+                    &authorization_data,
+                    &self.bucket_id,
+                    prefix,
+                )
+                .await
+            })
+            .await?;
+
+        // Backblaze reports upload times in milliseconds
+        Ok(files
+            .into_iter()
+            .filter_map(|x| {
+                Some(ListedFile {
+                    file_id: x.file_id?,
+                    file_name: x.file_name,
+                    content_length: x.content_length,
+                    upload_timestamp: x.upload_timestamp / 1000,
+                })
+            })
+            .collect())
+    }
+
+    async fn head_file(
+        &self,
+        _file_id: &str,
+        file_name: &str,
+    ) -> Result<Option<FileMetadata>, FileHostingError> {
+        let file = self
+            .with_authorization(|authorization_data| async move {
+                list::find_file_name(
+                    &authorization_data,
+                    &self.bucket_id,
+                    file_name,
+                )
+                .await
+            })
+            .await?;
+
+        // Backblaze doesn't know the SHA1 hash of large files, and gives new
+        // IDs to replaced files
+        Ok(file.and_then(|x| {
+            let file_id = x.file_id?;
+            Some(FileMetadata {
+                etag: Some(file_id.clone()),
+                file_id,
+                file_name: x.file_name,
+                content_length: x.content_length,
+                content_type: x.content_type,
+                content_sha1: x.content_sha1.filter(|x| x != "none"),
+            })
+        }))
+    }
+
+    async fn signed_download_url(
+        &self,
+        _file_id: &str,
+        file_name: &str,
+        expires_in: std::time::Duration,
+    ) -> Result<String, FileHostingError> {
+        let valid_duration = expires_in
+            .as_secs()
+            .clamp(1, MAX_DOWNLOAD_AUTHORIZATION_DURATION);
+        let authorization = self
+            .with_authorization(|authorization_data| async move {
+                download::get_download_authorization(
+                    &authorization_data,
+                    &self.bucket_id,
+                    file_name,
+                    valid_duration,
+                )
+                .await
+            })
+            .await?;
+
+        let encoded_name = file_name
+            .split('/')
+            .map(|x| urlencoding::encode(x).into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        Ok(format!(
+            "{}/file/{}/{}?Authorization={}",
+            self.authorization().download_url,
+            self.bucket_name,
+            encoded_name,
+            urlencoding::encode(&authorization.authorization_token)
+        ))
+    }
 }
 
 pub async fn process_response<T>(
@@ -103,6 +608,131 @@ where
     if response.status().is_success() {
         Ok(response.json().await?)
     } else {
-        Err(FileHostingError::BackblazeError(response.json().await?))
+        Err(process_error(response).await)
+    }
+}
+
+/// Reads the error Backblaze responded with. Errors which don't come from
+/// Backblaze itself, such as those of proxies, aren't JSON, so their status
+/// is kept to decide whether to retry the request.
+pub async fn process_error(response: Response) -> FileHostingError {
+    let status = response.status().as_u16();
+    match response.text().await {
+        Ok(body) => FileHostingError::BackblazeError(
+            serde_json::from_str(&body).unwrap_or_else(|_| {
+                serde_json::json!({
+                    "status": status,
+                    "code": "unknown",
+                    "message": body,
+                })
+            }),
+        ),
+        Err(e) => FileHostingError::HttpError(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fake_server::{self, FakeB2};
+    use super::{BackblazeHost, Failure};
+    use crate::file_hosting::{FileHost, FileHostingError};
+    use bytes::Bytes;
+    use std::time::Duration;
+
+    async fn connect(server: &FakeB2) -> BackblazeHost {
+        BackblazeHost::connect(
+            &server.url,
+            "key-id",
+            "key",
+            fake_server::BUCKET_ID,
+            Duration::from_secs(0),
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn upload(
+        host: &BackblazeHost,
+        file_name: &str,
+    ) -> Result<String, FileHostingError> {
+        host.upload_file("text/plain", file_name, Bytes::from("contents"))
+            .await
+            .map(|x| x.file_name)
+    }
+
+    #[test]
+    fn classifies_errors() {
+        let error = |status: u16, code: &str| {
+            FileHostingError::BackblazeError(serde_json::json!({
+                "status": status,
+                "code": code,
+            }))
+        };
+
+        assert_eq!(
+            Failure::of(&error(401, "expired_auth_token")),
+            Failure::Unauthorized
+        );
+        assert_eq!(
+            Failure::of(&error(503, "service_unavailable")),
+            Failure::Unavailable
+        );
+        assert_eq!(Failure::of(&error(400, "bad_request")), Failure::Other);
+    }
+
+    #[actix_rt::test]
+    async fn reuses_upload_urls() {
+        let server = fake_server::start();
+        let host = connect(&server).await;
+
+        assert_eq!(upload(&host, "first.txt").await.unwrap(), "first.txt");
+        assert_eq!(upload(&host, "second.txt").await.unwrap(), "second.txt");
+
+        let state = server.state.lock().unwrap();
+        assert_eq!(state.upload_url_requests, 1);
+        assert_eq!(state.uploaded_files, vec!["first.txt", "second.txt"]);
+    }
+
+    #[actix_rt::test]
+    async fn reauthorizes_expired_tokens() {
+        let server = fake_server::start();
+        let host = connect(&server).await;
+        upload(&host, "first.txt").await.unwrap();
+
+        // The pooled upload URL and the account token both expire
+        server.state.lock().unwrap().expire_tokens();
+        upload(&host, "second.txt").await.unwrap();
+
+        let state = server.state.lock().unwrap();
+        assert_eq!(state.authorizations, 2);
+        assert_eq!(state.upload_url_requests, 2);
+        assert_eq!(state.uploaded_files, vec!["first.txt", "second.txt"]);
+    }
+
+    #[actix_rt::test]
+    async fn retries_unavailable_uploads() {
+        let server = fake_server::start();
+        let host = connect(&server).await;
+
+        server.state.lock().unwrap().unavailable_uploads = 2;
+        upload(&host, "file.txt").await.unwrap();
+
+        let state = server.state.lock().unwrap();
+        // Every failed upload URL is replaced
+        assert_eq!(state.upload_url_requests, 3);
+        assert_eq!(state.uploaded_files, vec!["file.txt"]);
+    }
+
+    #[actix_rt::test]
+    async fn gives_up_eventually() {
+        let server = fake_server::start();
+        let host = connect(&server).await;
+
+        server.state.lock().unwrap().unavailable_uploads = 100;
+        assert!(matches!(
+            upload(&host, "file.txt").await,
+            Err(FileHostingError::BackblazeError(_))
+        ));
+        assert!(server.state.lock().unwrap().uploaded_files.is_empty());
     }
 }
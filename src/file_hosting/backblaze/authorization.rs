@@ -30,7 +30,10 @@ pub struct UploadUrlData {
     pub authorization_token: String,
 }
 
+/// Authorizes with the API at `api_url`, which is normally
+/// `https://api.backblazeb2.com`. The returned token is valid for 24 hours.
 pub async fn authorize_account(
+    api_url: &str,
     key_id: &str,
     application_key: &str,
 ) -> Result<AuthorizationData, FileHostingError> {
@@ -38,7 +41,7 @@ pub async fn authorize_account(
     let formatted_key = format!("Basic {}", base64::encode(combined_key));
 
     let response = reqwest::Client::new()
-        .get("https://api.backblazeb2.com/b2api/v2/b2_authorize_account")
+        .get(&format!("{}/b2api/v2/b2_authorize_account", api_url))
         .header(reqwest::header::CONTENT_TYPE, "application/json")
         .header(reqwest::header::AUTHORIZATION, formatted_key)
         .send()
@@ -75,3 +78,55 @@ pub async fn get_upload_url(
 
     super::process_response(response).await
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct BucketData {
+    bucket_id: String,
+    bucket_name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ListBucketsData {
+    buckets: Vec<BucketData>,
+}
+
+/// Gets the name of a bucket, which files are downloaded by
+pub async fn get_bucket_name(
+    authorization_data: &AuthorizationData,
+    bucket_id: &str,
+) -> Result<String, FileHostingError> {
+    let response = reqwest::Client::new()
+        .post(&format!(
+            "{}/b2api/v2/b2_list_buckets",
+            authorization_data.api_url
+        ))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(
+            reqwest::header::AUTHORIZATION,
+            &authorization_data.authorization_token,
+        )
+        .body(
+            serde_json::json!({
+                "accountId": authorization_data.account_id,
+                "bucketId": bucket_id,
+            })
+            .to_string(),
+        )
+        .send()
+        .await?;
+
+    let data: ListBucketsData = super::process_response(response).await?;
+
+    data.buckets
+        .into_iter()
+        .find(|x| x.bucket_id == bucket_id)
+        .map(|x| x.bucket_name)
+        .ok_or_else(|| {
+            FileHostingError::BackblazeError(serde_json::json!({
+                "code": "bad_bucket_id",
+                "message": format!("Bucket {} does not exist", bucket_id),
+            }))
+        })
+}
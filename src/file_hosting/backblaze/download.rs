@@ -0,0 +1,68 @@
+use super::authorization::AuthorizationData;
+use crate::file_hosting::FileHostingError;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadAuthorizationData {
+    pub bucket_id: String,
+    pub file_name_prefix: String,
+    pub authorization_token: String,
+}
+
+pub async fn download_file_by_id(
+    authorization_data: &AuthorizationData,
+    file_id: &str,
+) -> Result<Bytes, FileHostingError> {
+    let response = reqwest::Client::new()
+        .get(&format!(
+            "{}/b2api/v2/b2_download_file_by_id",
+            authorization_data.download_url
+        ))
+        .header(
+            reqwest::header::AUTHORIZATION,
+            &authorization_data.authorization_token,
+        )
+        .query(&[("fileId", file_id)])
+        .send()
+        .await?;
+
+    if response.status().is_success() {
+        Ok(response.bytes().await?)
+    } else {
+        Err(super::process_error(response).await)
+    }
+}
+
+/// Creates a token allowing downloads of the files starting with
+/// `file_name_prefix` for `valid_duration` seconds
+pub async fn get_download_authorization(
+    authorization_data: &AuthorizationData,
+    bucket_id: &str,
+    file_name_prefix: &str,
+    valid_duration: u64,
+) -> Result<DownloadAuthorizationData, FileHostingError> {
+    let response = reqwest::Client::new()
+        .post(&format!(
+            "{}/b2api/v2/b2_get_download_authorization",
+            authorization_data.api_url
+        ))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(
+            reqwest::header::AUTHORIZATION,
+            &authorization_data.authorization_token,
+        )
+        .body(
+            serde_json::json!({
+                "bucketId": bucket_id,
+                "fileNamePrefix": file_name_prefix,
+                "validDurationInSeconds": valid_duration,
+            })
+            .to_string(),
+        )
+        .send()
+        .await?;
+
+    super::process_response(response).await
+}
@@ -0,0 +1,181 @@
+//! A small stand-in for the Backblaze B2 API, which can be told to expire
+//! tokens and to fail uploads
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use std::sync::Mutex;
+
+pub const BUCKET_ID: &str = "fake-bucket-id";
+pub const BUCKET_NAME: &str = "fake-bucket";
+
+#[derive(Default)]
+pub struct FakeState {
+    /// Only the latest account token is valid
+    pub account_token: u32,
+    pub upload_token: u32,
+    /// Upload URL tokens up to this one are expired
+    pub expired_upload_tokens: u32,
+    /// How many of the next uploads fail with a 503
+    pub unavailable_uploads: u32,
+    pub authorizations: u32,
+    pub upload_url_requests: u32,
+    pub uploaded_files: Vec<String>,
+}
+
+impl FakeState {
+    /// Expires every token handed out so far, like Backblaze does after 24
+    /// hours
+    pub fn expire_tokens(&mut self) {
+        self.account_token += 1;
+        self.expired_upload_tokens = self.upload_token;
+    }
+}
+
+pub struct FakeB2 {
+    pub url: String,
+    pub state: web::Data<Mutex<FakeState>>,
+}
+
+pub fn start() -> FakeB2 {
+    let state = web::Data::new(Mutex::new(FakeState::default()));
+
+    let app_state = state.clone();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(app_state.clone())
+            .route(
+                "/b2api/v2/b2_authorize_account",
+                web::get().to(authorize_account),
+            )
+            .route("/b2api/v2/b2_list_buckets", web::post().to(list_buckets))
+            .route(
+                "/b2api/v2/b2_get_upload_url",
+                web::post().to(get_upload_url),
+            )
+            .route("/upload/{token}", web::post().to(upload_file))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+
+    let url = format!("http://{}", server.addrs()[0]);
+    actix_rt::spawn(server.run());
+
+    FakeB2 { url, state }
+}
+
+fn error(status: u16, code: &str) -> HttpResponse {
+    HttpResponse::build(actix_web::http::StatusCode::from_u16(status).unwrap())
+        .json(serde_json::json!({
+            "status": status,
+            "code": code,
+            "message": code,
+        }))
+}
+
+fn header<'a>(req: &'a HttpRequest, name: &str) -> &'a str {
+    req.headers()
+        .get(name)
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or_default()
+}
+
+fn is_authorized(req: &HttpRequest, state: &FakeState) -> bool {
+    header(req, "Authorization")
+        == format!("account-token-{}", state.account_token)
+}
+
+async fn authorize_account(
+    req: HttpRequest,
+    state: web::Data<Mutex<FakeState>>,
+) -> HttpResponse {
+    let mut state = state.lock().unwrap();
+    state.authorizations += 1;
+    state.account_token += 1;
+
+    let url = format!("http://{}", req.connection_info().host());
+    HttpResponse::Ok().json(serde_json::json!({
+        "absoluteMinimumPartSize": 5000000,
+        "accountId": "fake-account",
+        "allowed": {
+            "bucketId": null,
+            "bucketName": null,
+            "capabilities": ["writeFiles"],
+            "namePrefix": null,
+        },
+        "apiUrl": url,
+        "authorizationToken": format!("account-token-{}", state.account_token),
+        "downloadUrl": url,
+        "recommendedPartSize": 100000000,
+    }))
+}
+
+async fn list_buckets(
+    req: HttpRequest,
+    state: web::Data<Mutex<FakeState>>,
+) -> HttpResponse {
+    if !is_authorized(&req, &state.lock().unwrap()) {
+        return error(401, "expired_auth_token");
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "buckets": [{
+            "bucketId": BUCKET_ID,
+            "bucketName": BUCKET_NAME,
+        }],
+    }))
+}
+
+async fn get_upload_url(
+    req: HttpRequest,
+    state: web::Data<Mutex<FakeState>>,
+) -> HttpResponse {
+    let mut state = state.lock().unwrap();
+    if !is_authorized(&req, &state) {
+        return error(401, "expired_auth_token");
+    }
+    state.upload_url_requests += 1;
+    state.upload_token += 1;
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "bucketId": BUCKET_ID,
+        "uploadUrl": format!(
+            "http://{}/upload/{}",
+            req.connection_info().host(),
+            state.upload_token
+        ),
+        "authorizationToken": format!("upload-token-{}", state.upload_token),
+    }))
+}
+
+async fn upload_file(
+    req: HttpRequest,
+    token: web::Path<u32>,
+    body: web::Bytes,
+    state: web::Data<Mutex<FakeState>>,
+) -> HttpResponse {
+    let mut state = state.lock().unwrap();
+    let token = token.into_inner();
+    if header(&req, "Authorization") != format!("upload-token-{}", token)
+        || token <= state.expired_upload_tokens
+    {
+        return error(401, "expired_auth_token");
+    }
+    if state.unavailable_uploads > 0 {
+        state.unavailable_uploads -= 1;
+        return error(503, "service_unavailable");
+    }
+
+    let file_name = header(&req, "X-Bz-File-Name").to_string();
+    state.uploaded_files.push(file_name.clone());
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "fileId": format!("file-{}", state.uploaded_files.len()),
+        "fileName": file_name,
+        "accountId": "fake-account",
+        "bucketId": BUCKET_ID,
+        "contentLength": body.len(),
+        "contentSha1": header(&req, "X-Bz-Content-Sha1"),
+        "contentMd5": null,
+        "contentType": header(&req, "Content-Type"),
+        "uploadTimestamp": 0,
+    }))
+}
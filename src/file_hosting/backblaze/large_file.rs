@@ -0,0 +1,165 @@
+use super::authorization::AuthorizationData;
+use super::upload::UploadFileData;
+use crate::file_hosting::FileHostingError;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LargeFileData {
+    pub file_id: String,
+    pub file_name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadPartUrlData {
+    pub file_id: String,
+    pub upload_url: String,
+    pub authorization_token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadPartData {
+    pub file_id: String,
+    pub part_number: u32,
+    pub content_length: u64,
+    pub content_sha1: String,
+}
+
+pub async fn start_large_file(
+    authorization_data: &AuthorizationData,
+    bucket_id: &str,
+    content_type: &str,
+    file_name: &str,
+) -> Result<LargeFileData, FileHostingError> {
+    let response = reqwest::Client::new()
+        .post(&format!(
+            "{}/b2api/v2/b2_start_large_file",
+            authorization_data.api_url
+        ))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(
+            reqwest::header::AUTHORIZATION,
+            &authorization_data.authorization_token,
+        )
+        .body(
+            serde_json::json!({
+                "bucketId": bucket_id,
+                "fileName": file_name,
+                "contentType": content_type,
+            })
+            .to_string(),
+        )
+        .send()
+        .await?;
+
+    super::process_response(response).await
+}
+
+pub async fn get_upload_part_url(
+    authorization_data: &AuthorizationData,
+    file_id: &str,
+) -> Result<UploadPartUrlData, FileHostingError> {
+    let response = reqwest::Client::new()
+        .post(&format!(
+            "{}/b2api/v2/b2_get_upload_part_url",
+            authorization_data.api_url
+        ))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(
+            reqwest::header::AUTHORIZATION,
+            &authorization_data.authorization_token,
+        )
+        .body(
+            serde_json::json!({
+                "fileId": file_id,
+            })
+            .to_string(),
+        )
+        .send()
+        .await?;
+
+    super::process_response(response).await
+}
+
+// Part numbers start at 1, and all parts except for the last one have to be
+// at least `absolute_minimum_part_size` bytes long
+pub async fn upload_part(
+    url_data: &UploadPartUrlData,
+    part_number: u32,
+    part_bytes: Bytes,
+) -> Result<UploadPartData, FileHostingError> {
+    let response = reqwest::Client::new()
+        .post(&url_data.upload_url)
+        .header(
+            reqwest::header::AUTHORIZATION,
+            &url_data.authorization_token,
+        )
+        .header("X-Bz-Part-Number", part_number)
+        .header(reqwest::header::CONTENT_LENGTH, part_bytes.len())
+        .header(
+            "X-Bz-Content-Sha1",
+            sha1::Sha1::from(&part_bytes).hexdigest(),
+        )
+        .body(part_bytes)
+        .send()
+        .await?;
+
+    super::process_response(response).await
+}
+
+pub async fn finish_large_file(
+    authorization_data: &AuthorizationData,
+    file_id: &str,
+    part_sha1_array: &[String],
+) -> Result<UploadFileData, FileHostingError> {
+    let response = reqwest::Client::new()
+        .post(&format!(
+            "{}/b2api/v2/b2_finish_large_file",
+            authorization_data.api_url
+        ))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(
+            reqwest::header::AUTHORIZATION,
+            &authorization_data.authorization_token,
+        )
+        .body(
+            serde_json::json!({
+                "fileId": file_id,
+                "partSha1Array": part_sha1_array,
+            })
+            .to_string(),
+        )
+        .send()
+        .await?;
+
+    super::process_response(response).await
+}
+
+pub async fn cancel_large_file(
+    authorization_data: &AuthorizationData,
+    file_id: &str,
+) -> Result<LargeFileData, FileHostingError> {
+    let response = reqwest::Client::new()
+        .post(&format!(
+            "{}/b2api/v2/b2_cancel_large_file",
+            authorization_data.api_url
+        ))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(
+            reqwest::header::AUTHORIZATION,
+            &authorization_data.authorization_token,
+        )
+        .body(
+            serde_json::json!({
+                "fileId": file_id,
+            })
+            .to_string(),
+        )
+        .send()
+        .await?;
+
+    super::process_response(response).await
+}
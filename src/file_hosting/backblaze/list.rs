@@ -0,0 +1,104 @@
+use super::authorization::AuthorizationData;
+use crate::file_hosting::FileHostingError;
+use serde::{Deserialize, Serialize};
+
+/// The most file names Backblaze returns for a single request without
+/// billing it as several requests
+const MAX_FILE_COUNT: u32 = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ListedFileData {
+    pub file_id: Option<String>,
+    pub file_name: String,
+    pub content_length: u64,
+    pub content_type: Option<String>,
+    pub content_sha1: Option<String>,
+    pub upload_timestamp: u64,
+    pub action: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ListFileNamesData {
+    files: Vec<ListedFileData>,
+    next_file_name: Option<String>,
+}
+
+async fn list_page(
+    authorization_data: &AuthorizationData,
+    bucket_id: &str,
+    prefix: &str,
+    start_file_name: Option<&str>,
+    max_file_count: u32,
+) -> Result<ListFileNamesData, FileHostingError> {
+    let response = reqwest::Client::new()
+        .post(&format!(
+            "{}/b2api/v2/b2_list_file_names",
+            authorization_data.api_url
+        ))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(
+            reqwest::header::AUTHORIZATION,
+            &authorization_data.authorization_token,
+        )
+        .body(
+            serde_json::json!({
+                "bucketId": bucket_id,
+                "prefix": prefix,
+                "startFileName": start_file_name,
+                "maxFileCount": max_file_count,
+            })
+            .to_string(),
+        )
+        .send()
+        .await?;
+
+    super::process_response(response).await
+}
+
+/// Lists the names of all uploaded files starting with `prefix`, following
+/// the pages of the listing until its end
+pub async fn list_file_names(
+    authorization_data: &AuthorizationData,
+    bucket_id: &str,
+    prefix: &str,
+) -> Result<Vec<ListedFileData>, FileHostingError> {
+    let mut files = Vec::new();
+    let mut start_file_name = None;
+
+    loop {
+        let page = list_page(
+            authorization_data,
+            bucket_id,
+            prefix,
+            start_file_name.as_deref(),
+            MAX_FILE_COUNT,
+        )
+        .await?;
+
+        // Unfinished large files are listed too, but can't be downloaded
+        files.extend(page.files.into_iter().filter(|x| x.action == "upload"));
+
+        match page.next_file_name {
+            Some(next_file_name) => start_file_name = Some(next_file_name),
+            None => return Ok(files),
+        }
+    }
+}
+
+/// Gets the uploaded file with exactly this name, if there is one
+pub async fn find_file_name(
+    authorization_data: &AuthorizationData,
+    bucket_id: &str,
+    file_name: &str,
+) -> Result<Option<ListedFileData>, FileHostingError> {
+    let page =
+        list_page(authorization_data, bucket_id, file_name, Some(file_name), 1)
+            .await?;
+
+    Ok(page
+        .files
+        .into_iter()
+        .find(|x| x.file_name == file_name && x.action == "upload"))
+}
@@ -0,0 +1,590 @@
+use super::{
+    blocking, DeleteFileData, FileHost, FileHostingError, FileMetadata,
+    FileStream, ListedFile, StreamHasher, UploadFileData,
+};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use hmac::{Hmac, Mac, NewMac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use time::OffsetDateTime;
+
+/// The folder inside of the root the metadata of stored files is kept in.
+/// File names can't start with a dot, so it never collides with a file.
+const METADATA_FOLDER: &str = ".metadata";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Stores files in a folder of the local file system, for development setups
+/// and tests
+pub struct LocalHost {
+    root: PathBuf,
+    /// The URL the files are served at
+    base_url: String,
+    /// Signs download URLs. It changes on every start, so signed URLs don't
+    /// outlive the server.
+    signing_key: [u8; 32],
+}
+
+/// What is known about a stored file besides its contents
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LocalFileMetadata {
+    pub content_type: String,
+    pub content_length: u64,
+    pub content_sha1: String,
+    pub content_sha512: String,
+    pub upload_timestamp: u64,
+}
+
+impl LocalHost {
+    pub fn new(root: impl Into<PathBuf>, base_url: &str) -> Self {
+        let mut signing_key = [0; 32];
+        rand::thread_rng().fill_bytes(&mut signing_key);
+
+        LocalHost {
+            root: root.into(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            signing_key,
+        }
+    }
+
+    fn mac(&self, file_name: &str, expires: u64) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.signing_key)
+            .expect("HMAC accepts keys of any length");
+        mac.update(format!("{}\n{}", file_name, expires).as_bytes());
+        mac
+    }
+
+    fn sign(&self, file_name: &str, expires: u64) -> String {
+        format!("{:x}", self.mac(file_name, expires).finalize().into_bytes())
+    }
+
+    /// Checks a signature of a download URL created by `signed_download_url`
+    pub fn verify_signature(
+        &self,
+        file_name: &str,
+        expires: u64,
+        signature: &str,
+    ) -> bool {
+        let signature = match decode_hex(signature) {
+            Some(signature) => signature,
+            None => return false,
+        };
+
+        // The comparison takes constant time, so the time taken doesn't
+        // reveal how much of the signature is correct
+        let matches = self.mac(file_name, expires).verify(&signature).is_ok();
+
+        matches && expires >= OffsetDateTime::now_utc().unix_timestamp() as u64
+    }
+
+    /// Resolves the path a file is stored at. Names which could point outside
+    /// of the root, such as absolute paths or paths containing `..`, are
+    /// rejected instead of being cleaned up.
+    pub fn resolve(
+        &self,
+        file_name: &str,
+    ) -> Result<PathBuf, FileHostingError> {
+        Ok(self.root.join(checked_path(file_name)?))
+    }
+
+    fn resolve_metadata(
+        &self,
+        file_name: &str,
+    ) -> Result<PathBuf, FileHostingError> {
+        Ok(metadata_path(&self.root, checked_path(file_name)?))
+    }
+
+    pub async fn read_metadata(
+        &self,
+        file_name: &str,
+    ) -> Result<LocalFileMetadata, FileHostingError> {
+        let path = self.resolve_metadata(file_name)?;
+        let contents = blocking(move || std::fs::read(path)).await?;
+
+        serde_json::from_slice(&contents)
+            .map_err(|_| FileHostingError::InvalidFilename)
+    }
+}
+
+fn metadata_path(root: &Path, file_name: &Path) -> PathBuf {
+    root.join(METADATA_FOLDER)
+        .join(format!("{}.json", file_name.display()))
+}
+
+/// Reads the metadata kept for a file. Files stored before metadata was kept
+/// don't have any.
+fn read_stored_metadata(path: &Path) -> Option<LocalFileMetadata> {
+    serde_json::from_slice(&std::fs::read(path).ok()?).ok()
+}
+
+fn write_metadata(
+    path: &Path,
+    metadata: &LocalFileMetadata,
+) -> std::io::Result<()> {
+    create_parent(path)?;
+
+    // Serializing these fields can't fail
+    std::fs::write(path, serde_json::to_vec(metadata).unwrap_or_default())
+}
+
+fn checked_path(file_name: &str) -> Result<&Path, FileHostingError> {
+    let path = Path::new(file_name);
+
+    let is_safe = !file_name.is_empty()
+        && !file_name.contains('\\')
+        && !file_name.contains('\0')
+        && path.components().all(|x| match x {
+            Component::Normal(name) => !name.to_string_lossy().starts_with('.'),
+            _ => false,
+        });
+
+    if is_safe {
+        Ok(path)
+    } else {
+        Err(FileHostingError::InvalidFilename)
+    }
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if value.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn create_parent(path: &Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(parent) => std::fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+}
+
+/// Lists the files stored under `root` whose names start with `prefix`, on a
+/// blocking thread
+fn list_folder(root: &Path, prefix: &str) -> std::io::Result<Vec<ListedFile>> {
+    let mut files = Vec::new();
+    let mut folders = vec![root.to_path_buf()];
+
+    while let Some(folder) = folders.pop() {
+        let entries = match std::fs::read_dir(&folder) {
+            Ok(entries) => entries,
+            // Nothing was stored yet
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            let file_name =
+                match path.strip_prefix(root).ok().and_then(|x| x.to_str()) {
+                    Some(name) => name.replace(std::path::MAIN_SEPARATOR, "/"),
+                    None => continue,
+                };
+
+            // Skips the metadata folder along with any other file which
+            // couldn't have been stored by this host
+            if checked_path(&file_name).is_err() {
+                continue;
+            }
+
+            let metadata = std::fs::metadata(&path)?;
+            if metadata.is_dir() {
+                folders.push(path);
+                continue;
+            }
+            if !file_name.starts_with(prefix) {
+                continue;
+            }
+
+            // Files stored before metadata was kept use their
+            // modification time instead
+            let stored = read_stored_metadata(&metadata_path(
+                root,
+                Path::new(&file_name),
+            ));
+            let upload_timestamp = match stored {
+                Some(stored) => stored.upload_timestamp,
+                None => metadata
+                    .modified()?
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|x| x.as_secs())
+                    .unwrap_or(0),
+            };
+
+            files.push(ListedFile {
+                file_id: file_name.clone(),
+                file_name,
+                content_length: metadata.len(),
+                upload_timestamp,
+            });
+        }
+    }
+
+    files.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+    Ok(files)
+}
+
+#[async_trait]
+impl FileHost for LocalHost {
+    async fn upload_file(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        file_bytes: Bytes,
+    ) -> Result<UploadFileData, FileHostingError> {
+        let path = self.resolve(file_name)?;
+        let metadata_path = self.resolve_metadata(file_name)?;
+        let content_type = content_type.to_string();
+
+        let metadata = blocking(move || {
+            let metadata = LocalFileMetadata {
+                content_type,
+                content_length: file_bytes.len() as u64,
+                content_sha1: sha1::Sha1::from(&*file_bytes).hexdigest(),
+                content_sha512: format!(
+                    "{:x}",
+                    sha2::Sha512::digest(&*file_bytes)
+                ),
+                upload_timestamp: OffsetDateTime::now_utc().unix_timestamp()
+                    as u64,
+            };
+
+            create_parent(&path)?;
+            std::fs::write(&path, &*file_bytes)?;
+            write_metadata(&metadata_path, &metadata)?;
+
+            Ok(metadata)
+        })
+        .await?;
+
+        #[allow(clippy::cast_possible_truncation)]
+        let content_length = metadata.content_length as u32;
+
+        Ok(UploadFileData {
+            file_id: file_name.to_string(),
+            file_name: file_name.to_string(),
+            content_length,
+            content_sha512: metadata.content_sha512,
+            content_sha1: metadata.content_sha1,
+            content_md5: None,
+            content_type: metadata.content_type,
+            upload_timestamp: metadata.upload_timestamp,
+        })
+    }
+
+    async fn upload_file_streaming(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        content_length: u64,
+        mut stream: FileStream,
+    ) -> Result<UploadFileData, FileHostingError> {
+        let path = self.resolve(file_name)?;
+        let metadata_path = self.resolve_metadata(file_name)?;
+
+        let created = path.clone();
+        let mut file = blocking(move || {
+            create_parent(&created)?;
+            std::fs::File::create(&created)
+        })
+        .await?;
+
+        let mut hasher = StreamHasher::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            hasher.update(&chunk);
+            file = blocking(move || {
+                file.write_all(&chunk)?;
+                Ok(file)
+            })
+            .await?;
+        }
+
+        let hashes = match hasher.finish(content_length) {
+            Ok(hashes) => hashes,
+            Err(e) => {
+                blocking(move || std::fs::remove_file(path)).await?;
+                return Err(e);
+            }
+        };
+
+        let metadata = LocalFileMetadata {
+            content_type: content_type.to_string(),
+            content_length,
+            content_sha1: hashes.content_sha1,
+            content_sha512: hashes.content_sha512,
+            upload_timestamp: OffsetDateTime::now_utc().unix_timestamp() as u64,
+        };
+        let written = metadata.clone();
+        blocking(move || write_metadata(&metadata_path, &written)).await?;
+
+        Ok(UploadFileData {
+            file_id: file_name.to_string(),
+            file_name: file_name.to_string(),
+            content_length: hashes.content_length,
+            content_sha512: metadata.content_sha512,
+            content_sha1: metadata.content_sha1,
+            content_md5: None,
+            content_type: metadata.content_type,
+            upload_timestamp: metadata.upload_timestamp,
+        })
+    }
+
+    async fn delete_file_version(
+        &self,
+        file_id: &str,
+        file_name: &str,
+    ) -> Result<DeleteFileData, FileHostingError> {
+        let path = self.resolve(file_name)?;
+        let metadata_path = self.resolve_metadata(file_name)?;
+
+        blocking(move || {
+            std::fs::remove_file(path)?;
+
+            // Files stored before metadata was kept don't have any
+            match std::fs::remove_file(metadata_path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            }
+        })
+        .await?;
+
+        Ok(DeleteFileData {
+            file_id: file_id.to_string(),
+            file_name: file_name.to_string(),
+        })
+    }
+
+    async fn get_file(
+        &self,
+        _file_id: &str,
+        file_name: &str,
+    ) -> Result<Bytes, FileHostingError> {
+        let path = self.resolve(file_name)?;
+
+        Ok(blocking(move || std::fs::read(path)).await?.into())
+    }
+
+    async fn list_files(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<ListedFile>, FileHostingError> {
+        let root = self.root.clone();
+        let prefix = prefix.to_string();
+
+        blocking(move || list_folder(&root, &prefix)).await
+    }
+
+    async fn head_file(
+        &self,
+        _file_id: &str,
+        file_name: &str,
+    ) -> Result<Option<FileMetadata>, FileHostingError> {
+        let path = self.resolve(file_name)?;
+        let length = blocking(move || match std::fs::metadata(path) {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        })
+        .await?;
+
+        let content_length = match length {
+            Some(content_length) => content_length,
+            None => return Ok(None),
+        };
+        let stored = self.read_metadata(file_name).await.ok();
+
+        Ok(Some(FileMetadata {
+            file_id: file_name.to_string(),
+            file_name: file_name.to_string(),
+            content_length,
+            content_type: stored.as_ref().map(|x| x.content_type.clone()),
+            content_sha1: stored.as_ref().map(|x| x.content_sha1.clone()),
+            etag: stored.map(|x| x.content_sha512),
+        }))
+    }
+
+    async fn signed_download_url(
+        &self,
+        _file_id: &str,
+        file_name: &str,
+        expires_in: std::time::Duration,
+    ) -> Result<String, FileHostingError> {
+        checked_path(file_name)?;
+
+        let expires = OffsetDateTime::now_utc().unix_timestamp() as u64
+            + expires_in.as_secs();
+        let encoded_name = file_name
+            .split('/')
+            .map(|x| urlencoding::encode(x).into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        Ok(format!(
+            "{}/{}?expires={}&signature={}",
+            self.base_url,
+            encoded_name,
+            expires,
+            self.sign(file_name, expires)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checked_path, decode_hex, LocalHost};
+    use crate::file_hosting::{FileHost, FileHostingError};
+    use bytes::Bytes;
+
+    #[test]
+    fn safe_paths() {
+        assert!(checked_path("data/AABBCCDD/versions/1.0.0/mod.jar").is_ok());
+        assert!(checked_path("data/AABBCCDD/mod file..jar").is_ok());
+    }
+
+    #[test]
+    fn unsafe_paths() {
+        for path in &[
+            "",
+            "../secret",
+            "data/../../secret",
+            "data/....//../secret",
+            "/etc/passwd",
+            "data\\..\\secret",
+            "./data/mod.jar",
+            ".metadata/data/mod.jar.json",
+            "data/.hidden",
+        ] {
+            assert!(
+                matches!(
+                    checked_path(path),
+                    Err(FileHostingError::InvalidFilename)
+                ),
+                "{} should be rejected",
+                path
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn stores_and_lists_files() {
+        let root = std::env::temp_dir()
+            .join(format!("labrinth-local-host-{}", std::process::id()));
+        let host = LocalHost::new(&root, "http://localhost:8000/cdn");
+
+        host.upload_file(
+            "application/java-archive",
+            "data/example/mod.jar",
+            Bytes::from_static(b"contents"),
+        )
+        .await
+        .unwrap();
+
+        let metadata =
+            host.read_metadata("data/example/mod.jar").await.unwrap();
+        assert_eq!(metadata.content_type, "application/java-archive");
+        assert_eq!(metadata.content_length, 8);
+        assert_eq!(
+            host.get_file("", "data/example/mod.jar").await.unwrap(),
+            Bytes::from_static(b"contents")
+        );
+
+        host.upload_file(
+            "image/png",
+            "user/example/icon.png",
+            Bytes::from_static(b"icon"),
+        )
+        .await
+        .unwrap();
+        let listed = host.list_files("data/").await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].file_name, "data/example/mod.jar");
+        assert_eq!(listed[0].content_length, 8);
+        assert_eq!(listed[0].upload_timestamp, metadata.upload_timestamp);
+
+        let head = host
+            .head_file("", "data/example/mod.jar")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(head.content_length, 8);
+        assert_eq!(head.etag, Some(metadata.content_sha512.clone()));
+
+        host.delete_file_version("", "data/example/mod.jar")
+            .await
+            .unwrap();
+        assert!(host.read_metadata("data/example/mod.jar").await.is_err());
+        assert!(host
+            .head_file("", "data/example/mod.jar")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(host.list_files("data/").await.unwrap().is_empty());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(decode_hex("00ff7a"), Some(vec![0x00, 0xff, 0x7a]));
+        assert_eq!(decode_hex("0ff"), None);
+        assert_eq!(decode_hex("zz"), None);
+        assert_eq!(decode_hex("é0"), None);
+    }
+
+    #[actix_rt::test]
+    async fn signed_urls() {
+        let host = LocalHost::new("/tmp/unused", "http://localhost:8000/cdn/");
+
+        let url = host
+            .signed_download_url(
+                "",
+                "data/example/my mod.jar",
+                std::time::Duration::from_secs(60),
+            )
+            .await
+            .unwrap();
+        let url = url::Url::parse(&url).unwrap();
+        assert_eq!(url.path(), "/cdn/data/example/my%20mod.jar");
+
+        let query = url.query_pairs().collect::<Vec<_>>();
+        let expires = query[0].1.parse().unwrap();
+        let signature = &query[1].1;
+
+        assert!(host.verify_signature(
+            "data/example/my mod.jar",
+            expires,
+            signature
+        ));
+        assert!(!host.verify_signature(
+            "data/example/other.jar",
+            expires,
+            signature
+        ));
+        assert!(!host.verify_signature(
+            "data/example/my mod.jar",
+            expires + 1,
+            signature
+        ));
+        assert!(!host.verify_signature(
+            "data/example/my mod.jar",
+            expires,
+            &signature[1..]
+        ));
+        assert!(!host.verify_signature(
+            "data/example/my mod.jar",
+            expires - 120,
+            &host.sign("data/example/my mod.jar", expires - 120)
+        ));
+    }
+}
@@ -1,13 +1,18 @@
 use async_trait::async_trait;
+use futures::Stream;
+use sha2::Digest;
+use std::pin::Pin;
 use thiserror::Error;
 
 mod backblaze;
-mod mock;
+mod local;
+mod replicated;
 mod s3_host;
 
 pub use backblaze::BackblazeHost;
 use bytes::Bytes;
-pub use mock::MockHost;
+pub use local::{LocalFileMetadata, LocalHost};
+pub use replicated::{Replica, ReplicatedHost};
 pub use s3_host::S3Host;
 
 #[derive(Error, Debug)]
@@ -22,8 +27,31 @@ pub enum FileHostingError {
     FileSystemError(#[from] std::io::Error),
     #[error("Invalid Filename")]
     InvalidFilename,
+    #[error("Expected a file of {expected} bytes, but received {received}")]
+    ContentLengthMismatch { expected: u64, received: u64 },
+    #[error("Database error while tracking replicas: {0}")]
+    DatabaseError(#[from] sqlx::Error),
 }
 
+/// Runs file system calls on the thread pool for blocking work, so that they
+/// don't hold up the server's threads
+pub(crate) async fn blocking<F, R>(f: F) -> Result<R, FileHostingError>
+where
+    F: FnOnce() -> std::io::Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    actix_web::web::block(f)
+        .await
+        .map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+        })?
+        .map_err(FileHostingError::from)
+}
+
+/// The contents of a file which is uploaded without being held in memory
+pub type FileStream =
+    Pin<Box<dyn Stream<Item = Result<Bytes, FileHostingError>> + Send>>;
+
 #[derive(Debug, Clone)]
 pub struct UploadFileData {
     pub file_id: String,
@@ -42,6 +70,28 @@ pub struct DeleteFileData {
     pub file_name: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct ListedFile {
+    pub file_id: String,
+    pub file_name: String,
+    pub content_length: u64,
+    /// The unix timestamp in seconds at which the file was uploaded
+    pub upload_timestamp: u64,
+}
+
+/// What a file host knows about a stored file, without its contents
+#[derive(Debug, Clone)]
+pub struct FileMetadata {
+    pub file_id: String,
+    pub file_name: String,
+    pub content_length: u64,
+    pub content_type: Option<String>,
+    /// Not every host knows the hashes of its files
+    pub content_sha1: Option<String>,
+    /// Identifies the stored contents, changing when the file is replaced
+    pub etag: Option<String>,
+}
+
 #[async_trait]
 pub trait FileHost {
     async fn upload_file(
@@ -51,9 +101,98 @@ pub trait FileHost {
         file_bytes: Bytes,
     ) -> Result<UploadFileData, FileHostingError>;
 
+    /// Uploads a file of `content_length` bytes from a stream, only keeping
+    /// a bounded part of it in memory at any time
+    async fn upload_file_streaming(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        content_length: u64,
+        stream: FileStream,
+    ) -> Result<UploadFileData, FileHostingError>;
+
     async fn delete_file_version(
         &self,
         file_id: &str,
         file_name: &str,
     ) -> Result<DeleteFileData, FileHostingError>;
+
+    async fn get_file(
+        &self,
+        file_id: &str,
+        file_name: &str,
+    ) -> Result<Bytes, FileHostingError>;
+
+    /// Lists all stored files whose names start with `prefix`
+    async fn list_files(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<ListedFile>, FileHostingError>;
+
+    /// Gets the metadata of a stored file, or `None` if it isn't stored
+    async fn head_file(
+        &self,
+        file_id: &str,
+        file_name: &str,
+    ) -> Result<Option<FileMetadata>, FileHostingError>;
+
+    /// Creates a URL the file can be downloaded from until `expires_in` has
+    /// passed, even if it isn't public
+    async fn signed_download_url(
+        &self,
+        file_id: &str,
+        file_name: &str,
+        expires_in: std::time::Duration,
+    ) -> Result<String, FileHostingError>;
+}
+
+/// Computes the length and hashes of a file while it is being streamed
+pub(crate) struct StreamHasher {
+    length: u64,
+    sha1: sha1::Sha1,
+    sha512: sha2::Sha512,
+}
+
+pub(crate) struct StreamHashes {
+    pub content_length: u32,
+    pub content_sha1: String,
+    pub content_sha512: String,
+}
+
+impl StreamHasher {
+    pub fn new() -> Self {
+        StreamHasher {
+            length: 0,
+            sha1: sha1::Sha1::new(),
+            sha512: sha2::Sha512::new(),
+        }
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.length += chunk.len() as u64;
+        self.sha1.update(chunk);
+        self.sha512.update(chunk);
+    }
+
+    /// Finishes hashing, checking that exactly `expected_length` bytes were received
+    pub fn finish(
+        self,
+        expected_length: u64,
+    ) -> Result<StreamHashes, FileHostingError> {
+        if self.length != expected_length {
+            return Err(FileHostingError::ContentLengthMismatch {
+                expected: expected_length,
+                received: self.length,
+            });
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        let content_length = self.length as u32;
+
+        Ok(StreamHashes {
+            content_length,
+            content_sha1: self.sha1.hexdigest(),
+            content_sha512: format!("{:x}", self.sha512.finalize()),
+        })
+    }
 }
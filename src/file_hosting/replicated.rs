@@ -0,0 +1,629 @@
+use super::{
+    DeleteFileData, FileHost, FileHostingError, FileMetadata, FileStream,
+    ListedFile, UploadFileData,
+};
+use crate::database::models::replica_item::{FileReplica, ReplicaStatus};
+use async_trait::async_trait;
+use bytes::Bytes;
+use log::warn;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// A file host which is part of a `ReplicatedHost`
+pub struct Replica {
+    /// The name the copies on this host are tracked by, such as `s3`
+    pub name: String,
+    pub host: Arc<dyn FileHost + Send + Sync>,
+}
+
+/// Stores every file on a primary file host and copies it to secondary
+/// hosts. Copies which can't be made or deleted right away are tracked in
+/// the database and retried with `sync_replicas`. Files are read from the
+/// primary host, falling back to the secondary hosts if that fails.
+pub struct ReplicatedHost {
+    primary: Replica,
+    secondaries: Vec<Replica>,
+    pool: PgPool,
+}
+
+impl ReplicatedHost {
+    pub fn new(
+        primary: Replica,
+        secondaries: Vec<Replica>,
+        pool: PgPool,
+    ) -> Self {
+        ReplicatedHost {
+            primary,
+            secondaries,
+            pool,
+        }
+    }
+
+    /// Copies a file to a secondary host, returning whether it succeeded
+    async fn copy_to(
+        &self,
+        replica: &Replica,
+        content_type: &str,
+        file_name: &str,
+        file_bytes: Bytes,
+    ) -> Result<bool, FileHostingError> {
+        match replica
+            .host
+            .upload_file(content_type, file_name, file_bytes)
+            .await
+        {
+            Ok(upload_data) => {
+                FileReplica::set_status(
+                    file_name,
+                    &replica.name,
+                    ReplicaStatus::Stored,
+                    Some(&upload_data.file_id),
+                    content_type,
+                    None,
+                    &self.pool,
+                )
+                .await?;
+
+                Ok(true)
+            }
+            Err(e) => {
+                warn!(
+                    "Copying {} to replica {} failed: {}",
+                    file_name, replica.name, e
+                );
+                FileReplica::set_status(
+                    file_name,
+                    &replica.name,
+                    ReplicaStatus::Pending,
+                    None,
+                    content_type,
+                    Some(&e.to_string()),
+                    &self.pool,
+                )
+                .await?;
+
+                Ok(false)
+            }
+        }
+    }
+
+    /// Queues a copy of a file for `sync_replicas`. This is only logged if it
+    /// fails, since the file is already stored on the primary host.
+    async fn queue_copy(
+        &self,
+        replica: &Replica,
+        content_type: &str,
+        file_name: &str,
+        error: Option<&str>,
+    ) {
+        if let Err(e) = FileReplica::set_status(
+            file_name,
+            &replica.name,
+            ReplicaStatus::Pending,
+            None,
+            content_type,
+            error,
+            &self.pool,
+        )
+        .await
+        {
+            warn!(
+                "Queueing the copy of {} to replica {} failed: {}",
+                file_name, replica.name, e
+            );
+        }
+    }
+
+    /// Deletes the copy of a file from a secondary host, returning whether
+    /// it succeeded
+    async fn delete_from(
+        &self,
+        replica: &Replica,
+        file_name: &str,
+    ) -> Result<bool, FileHostingError> {
+        let copy = match FileReplica::get(file_name, &replica.name, &self.pool)
+            .await?
+        {
+            Some(copy) => copy,
+            // Files stored before the replica was added have no copy
+            None => return Ok(true),
+        };
+
+        // Files which were never copied only need to be forgotten
+        let result = match &copy.file_id {
+            Some(file_id) => replica
+                .host
+                .delete_file_version(file_id, file_name)
+                .await
+                .map(|_| ()),
+            None => Ok(()),
+        };
+
+        match result {
+            Ok(()) => {
+                FileReplica::remove(file_name, &replica.name, &self.pool)
+                    .await?;
+
+                Ok(true)
+            }
+            Err(e) => {
+                warn!(
+                    "Deleting {} from replica {} failed: {}",
+                    file_name, replica.name, e
+                );
+                FileReplica::set_status(
+                    file_name,
+                    &replica.name,
+                    ReplicaStatus::Deleting,
+                    None,
+                    &copy.content_type,
+                    Some(&e.to_string()),
+                    &self.pool,
+                )
+                .await?;
+
+                Ok(false)
+            }
+        }
+    }
+
+    /// Reads a file from the primary host, or `None` if it was deleted
+    async fn read_primary(
+        &self,
+        file_name: &str,
+    ) -> Result<Option<Bytes>, FileHostingError> {
+        match self.primary.host.head_file("", file_name).await? {
+            Some(file) => Ok(Some(
+                self.primary.host.get_file(&file.file_id, file_name).await?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Retries copying and deleting the copies which are out of sync with
+    /// the primary host, up to `limit` at once. Returns how many of them
+    /// are in sync now.
+    pub async fn sync_replicas(
+        &self,
+        limit: i64,
+    ) -> Result<usize, FileHostingError> {
+        let mut synced = 0;
+
+        for copy in FileReplica::list_unsynced(limit, &self.pool).await? {
+            // Replicas which were removed from the configuration are skipped
+            let replica = match self
+                .secondaries
+                .iter()
+                .find(|x| x.name == copy.replica)
+            {
+                Some(replica) => replica,
+                None => continue,
+            };
+
+            if let Some(error) = &copy.last_error {
+                warn!(
+                    "Retrying to sync {} with replica {} after {} attempts, last error: {}",
+                    copy.file_name, copy.replica, copy.attempts, error
+                );
+            }
+
+            let in_sync = match copy.status {
+                ReplicaStatus::Pending => {
+                    match self.read_primary(&copy.file_name).await {
+                        Ok(Some(data)) => {
+                            self.copy_to(
+                                replica,
+                                &copy.content_type,
+                                &copy.file_name,
+                                data,
+                            )
+                            .await?
+                        }
+                        // The file was deleted before it could be copied
+                        Ok(None) => {
+                            FileReplica::remove(
+                                &copy.file_name,
+                                &copy.replica,
+                                &self.pool,
+                            )
+                            .await?;
+                            true
+                        }
+                        Err(e) => {
+                            FileReplica::set_status(
+                                &copy.file_name,
+                                &copy.replica,
+                                ReplicaStatus::Pending,
+                                None,
+                                &copy.content_type,
+                                Some(&e.to_string()),
+                                &self.pool,
+                            )
+                            .await?;
+                            false
+                        }
+                    }
+                }
+                ReplicaStatus::Deleting => {
+                    self.delete_from(replica, &copy.file_name).await?
+                }
+                ReplicaStatus::Stored => true,
+            };
+
+            if in_sync {
+                synced += 1;
+            }
+        }
+
+        Ok(synced)
+    }
+}
+
+#[async_trait]
+impl FileHost for ReplicatedHost {
+    async fn upload_file(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        file_bytes: Bytes,
+    ) -> Result<UploadFileData, FileHostingError> {
+        let upload_data = self
+            .primary
+            .host
+            .upload_file(content_type, file_name, file_bytes.clone())
+            .await?;
+
+        // The file is stored once the primary host has it, so copies which
+        // fail are left to `sync_replicas`
+        for replica in &self.secondaries {
+            if let Err(e) = self
+                .copy_to(replica, content_type, file_name, file_bytes.clone())
+                .await
+            {
+                warn!(
+                    "Tracking the copy of {} on replica {} failed: {}",
+                    file_name, replica.name, e
+                );
+                self.queue_copy(
+                    replica,
+                    content_type,
+                    file_name,
+                    Some(&e.to_string()),
+                )
+                .await;
+            }
+        }
+
+        Ok(upload_data)
+    }
+
+    /// Streams the file to the primary host only, since the stream can't be
+    /// read twice. It is copied to the secondary hosts by `sync_replicas`.
+    async fn upload_file_streaming(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        content_length: u64,
+        stream: FileStream,
+    ) -> Result<UploadFileData, FileHostingError> {
+        let upload_data = self
+            .primary
+            .host
+            .upload_file_streaming(
+                content_type,
+                file_name,
+                content_length,
+                stream,
+            )
+            .await?;
+
+        for replica in &self.secondaries {
+            self.queue_copy(replica, content_type, file_name, None)
+                .await;
+        }
+
+        Ok(upload_data)
+    }
+
+    async fn delete_file_version(
+        &self,
+        file_id: &str,
+        file_name: &str,
+    ) -> Result<DeleteFileData, FileHostingError> {
+        let delete_data = self
+            .primary
+            .host
+            .delete_file_version(file_id, file_name)
+            .await?;
+
+        for replica in &self.secondaries {
+            if let Err(e) = self.delete_from(replica, file_name).await {
+                warn!(
+                    "Deleting {} from replica {} failed: {}",
+                    file_name, replica.name, e
+                );
+            }
+        }
+
+        Ok(delete_data)
+    }
+
+    async fn get_file(
+        &self,
+        file_id: &str,
+        file_name: &str,
+    ) -> Result<Bytes, FileHostingError> {
+        let error = match self.primary.host.get_file(file_id, file_name).await {
+            Ok(data) => return Ok(data),
+            Err(e) => e,
+        };
+
+        for replica in &self.secondaries {
+            let copy = FileReplica::get(file_name, &replica.name, &self.pool)
+                .await?
+                .filter(|x| x.status == ReplicaStatus::Stored);
+
+            if let Some(file_id) = copy.and_then(|x| x.file_id) {
+                if let Ok(data) =
+                    replica.host.get_file(&file_id, file_name).await
+                {
+                    return Ok(data);
+                }
+            }
+        }
+
+        Err(error)
+    }
+
+    async fn list_files(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<ListedFile>, FileHostingError> {
+        self.primary.host.list_files(prefix).await
+    }
+
+    async fn head_file(
+        &self,
+        file_id: &str,
+        file_name: &str,
+    ) -> Result<Option<FileMetadata>, FileHostingError> {
+        self.primary.host.head_file(file_id, file_name).await
+    }
+
+    async fn signed_download_url(
+        &self,
+        file_id: &str,
+        file_name: &str,
+        expires_in: std::time::Duration,
+    ) -> Result<String, FileHostingError> {
+        self.primary
+            .host
+            .signed_download_url(file_id, file_name, expires_in)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Replica, ReplicatedHost};
+    use crate::database::models::replica_item::{FileReplica, ReplicaStatus};
+    use crate::file_hosting::{
+        DeleteFileData, FileHost, FileHostingError, FileMetadata, FileStream,
+        ListedFile, LocalHost, UploadFileData,
+    };
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    /// A file host which is down
+    struct FailingHost;
+
+    fn unavailable() -> FileHostingError {
+        FileHostingError::S3Error("unavailable".to_string())
+    }
+
+    #[async_trait]
+    impl FileHost for FailingHost {
+        async fn upload_file(
+            &self,
+            _content_type: &str,
+            _file_name: &str,
+            _file_bytes: Bytes,
+        ) -> Result<UploadFileData, FileHostingError> {
+            Err(unavailable())
+        }
+
+        async fn upload_file_streaming(
+            &self,
+            _content_type: &str,
+            _file_name: &str,
+            _content_length: u64,
+            _stream: FileStream,
+        ) -> Result<UploadFileData, FileHostingError> {
+            Err(unavailable())
+        }
+
+        async fn delete_file_version(
+            &self,
+            _file_id: &str,
+            _file_name: &str,
+        ) -> Result<DeleteFileData, FileHostingError> {
+            Err(unavailable())
+        }
+
+        async fn get_file(
+            &self,
+            _file_id: &str,
+            _file_name: &str,
+        ) -> Result<Bytes, FileHostingError> {
+            Err(unavailable())
+        }
+
+        async fn list_files(
+            &self,
+            _prefix: &str,
+        ) -> Result<Vec<ListedFile>, FileHostingError> {
+            Err(unavailable())
+        }
+
+        async fn head_file(
+            &self,
+            _file_id: &str,
+            _file_name: &str,
+        ) -> Result<Option<FileMetadata>, FileHostingError> {
+            Err(unavailable())
+        }
+
+        async fn signed_download_url(
+            &self,
+            _file_id: &str,
+            _file_name: &str,
+            _expires_in: std::time::Duration,
+        ) -> Result<String, FileHostingError> {
+            Err(unavailable())
+        }
+    }
+
+    fn temp_root() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("labrinth-test-{:016x}", rand::random::<u64>()))
+    }
+
+    fn file_name() -> String {
+        format!("data/{:016x}/mod.jar", rand::random::<u64>())
+    }
+
+    async fn host(
+        root: &Path,
+        secondary: Arc<dyn FileHost + Send + Sync>,
+    ) -> ReplicatedHost {
+        let pool = sqlx::PgPool::connect(&dotenv::var("DATABASE_URL").unwrap())
+            .await
+            .unwrap();
+
+        ReplicatedHost::new(
+            Replica {
+                name: "local".to_string(),
+                host: Arc::new(LocalHost::new(root, "http://localhost/cdn")),
+            },
+            vec![Replica {
+                name: "secondary".to_string(),
+                host: secondary,
+            }],
+            pool,
+        )
+    }
+
+    #[actix_rt::test]
+    #[ignore = "needs a migrated database at DATABASE_URL"]
+    async fn uploads_succeed_if_a_replica_fails() {
+        let root = temp_root();
+        let host = host(&root, Arc::new(FailingHost)).await;
+        let file_name = file_name();
+
+        host.upload_file(
+            "application/java-archive",
+            &file_name,
+            Bytes::from_static(b"contents"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            host.get_file("", &file_name).await.unwrap(),
+            Bytes::from_static(b"contents")
+        );
+        let copy = FileReplica::get(&file_name, "secondary", &host.pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(copy.status, ReplicaStatus::Pending);
+        assert_eq!(copy.file_id, None);
+        assert!(copy.last_error.is_some());
+
+        FileReplica::remove(&file_name, "secondary", &host.pool)
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(root).unwrap();
+    }
+    #[actix_rt::test]
+    #[ignore = "needs a migrated database at DATABASE_URL"]
+    async fn deletes_copies_from_replicas() {
+        let root = temp_root();
+        let secondary_root = temp_root();
+        let secondary = Arc::new(LocalHost::new(
+            &secondary_root,
+            "http://localhost/secondary",
+        ));
+        let host = host(&root, secondary.clone()).await;
+        let file_name = file_name();
+
+        host.upload_file(
+            "application/java-archive",
+            &file_name,
+            Bytes::from_static(b"contents"),
+        )
+        .await
+        .unwrap();
+
+        let copy = FileReplica::get(&file_name, "secondary", &host.pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(copy.status, ReplicaStatus::Stored);
+        assert!(secondary.head_file("", &file_name).await.unwrap().is_some());
+
+        host.delete_file_version("", &file_name).await.unwrap();
+
+        assert!(FileReplica::get(&file_name, "secondary", &host.pool)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(secondary.head_file("", &file_name).await.unwrap().is_none());
+
+        std::fs::remove_dir_all(root).unwrap();
+        std::fs::remove_dir_all(secondary_root).unwrap();
+    }
+    #[actix_rt::test]
+    #[ignore = "needs a migrated database at DATABASE_URL"]
+    async fn failed_deletes_are_retried() {
+        let root = temp_root();
+        let host = host(&root, Arc::new(FailingHost)).await;
+        let file_name = file_name();
+
+        host.upload_file(
+            "application/java-archive",
+            &file_name,
+            Bytes::from_static(b"contents"),
+        )
+        .await
+        .unwrap();
+        // Pretend the copy was made before the replica went down
+        FileReplica::set_status(
+            &file_name,
+            "secondary",
+            ReplicaStatus::Stored,
+            Some("copy"),
+            "application/java-archive",
+            None,
+            &host.pool,
+        )
+        .await
+        .unwrap();
+
+        host.delete_file_version("", &file_name).await.unwrap();
+
+        let copy = FileReplica::get(&file_name, "secondary", &host.pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(copy.status, ReplicaStatus::Deleting);
+        assert_eq!(copy.file_id.as_deref(), Some("copy"));
+
+        FileReplica::remove(&file_name, "secondary", &host.pool)
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
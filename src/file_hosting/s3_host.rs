@@ -1,13 +1,22 @@
 use crate::file_hosting::{
-    DeleteFileData, FileHost, FileHostingError, UploadFileData,
+    DeleteFileData, FileHost, FileHostingError, FileMetadata, FileStream,
+    ListedFile, StreamHasher, UploadFileData,
 };
 use async_trait::async_trait;
 use bytes::Bytes;
+use futures::TryStreamExt;
 use s3::bucket::Bucket;
 use s3::creds::Credentials;
 use s3::region::Region;
 use sha2::Digest;
-use time::OffsetDateTime;
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+/// The longest time S3 allows presigned URLs to be valid
+const MAX_PRESIGNED_URL_DURATION: u64 = 7 * 24 * 60 * 60;
+
+/// The size of the parts of multipart uploads. S3 requires all parts but the
+/// last one to be at least 5MiB.
+const MULTIPART_PART_SIZE: usize = 8 * (1 << 20);
 
 pub struct S3Host {
     bucket: Bucket,
@@ -52,6 +61,72 @@ impl S3Host {
     }
 }
 
+impl S3Host {
+    /// Uploads the parts of a multipart upload, beginning with one which was
+    /// already read, and completes it
+    async fn upload_parts(
+        &self,
+        path: &str,
+        upload_id: &str,
+        content_type: &str,
+        first_part: Vec<u8>,
+        stream: &mut FileStream,
+        hasher: &mut StreamHasher,
+    ) -> Result<(), FileHostingError> {
+        let mut parts = Vec::new();
+        let mut part = first_part;
+
+        while !part.is_empty() {
+            parts.push(
+                self.bucket
+                    .put_multipart_chunk(
+                        part,
+                        path,
+                        parts.len() as u32 + 1,
+                        upload_id,
+                        content_type,
+                    )
+                    .await
+                    .map_err(|_| {
+                        FileHostingError::S3Error(
+                            "Error while uploading file part to S3".to_string(),
+                        )
+                    })?,
+            );
+            part = read_part(stream, hasher).await?;
+        }
+
+        self.bucket
+            .complete_multipart_upload(path, upload_id, parts)
+            .await
+            .map_err(|_| {
+                FileHostingError::S3Error(
+                    "Error while completing multipart upload to S3".to_string(),
+                )
+            })?;
+
+        Ok(())
+    }
+}
+
+/// Reads from the stream until a whole part was read or the stream ended
+async fn read_part(
+    stream: &mut FileStream,
+    hasher: &mut StreamHasher,
+) -> Result<Vec<u8>, FileHostingError> {
+    let mut part = Vec::new();
+    while part.len() < MULTIPART_PART_SIZE {
+        match stream.try_next().await? {
+            Some(chunk) => {
+                hasher.update(&chunk);
+                part.extend_from_slice(&chunk);
+            }
+            None => break,
+        }
+    }
+    Ok(part)
+}
+
 #[async_trait]
 impl FileHost for S3Host {
     async fn upload_file(
@@ -92,6 +167,94 @@ impl FileHost for S3Host {
         })
     }
 
+    /// Uploads the file as a multipart upload, or with a single request if
+    /// it fits into one part
+    async fn upload_file_streaming(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        content_length: u64,
+        mut stream: FileStream,
+    ) -> Result<UploadFileData, FileHostingError> {
+        let path = format!("/{}", file_name);
+        let mut hasher = StreamHasher::new();
+
+        let first_part = read_part(&mut stream, &mut hasher).await?;
+        if first_part.len() < MULTIPART_PART_SIZE {
+            self.bucket
+                .put_object_with_content_type(&path, &first_part, content_type)
+                .await
+                .map_err(|_| {
+                    FileHostingError::S3Error(
+                        "Error while uploading file to S3".to_string(),
+                    )
+                })?;
+        } else {
+            let upload_id = self
+                .bucket
+                .initiate_multipart_upload(&path, content_type)
+                .await
+                .map_err(|_| {
+                    FileHostingError::S3Error(
+                        "Error while starting multipart upload to S3"
+                            .to_string(),
+                    )
+                })?
+                .upload_id;
+
+            let result = self
+                .upload_parts(
+                    &path,
+                    &upload_id,
+                    content_type,
+                    first_part,
+                    &mut stream,
+                    &mut hasher,
+                )
+                .await;
+
+            if let Err(e) = result {
+                if let Err(abort_error) =
+                    self.bucket.abort_upload(&path, &upload_id).await
+                {
+                    log::warn!(
+                        "Aborting multipart upload of {} failed: {}",
+                        file_name,
+                        abort_error
+                    );
+                }
+                return Err(e);
+            }
+        }
+
+        let hashes = match hasher.finish(content_length) {
+            Ok(hashes) => hashes,
+            Err(e) => {
+                if let Err(delete_error) =
+                    self.delete_file_version(file_name, file_name).await
+                {
+                    log::warn!(
+                        "Deleting incomplete upload {} failed: {}",
+                        file_name,
+                        delete_error
+                    );
+                }
+                return Err(e);
+            }
+        };
+
+        Ok(UploadFileData {
+            file_id: file_name.to_string(),
+            file_name: file_name.to_string(),
+            content_length: hashes.content_length,
+            content_sha512: hashes.content_sha512,
+            content_sha1: hashes.content_sha1,
+            content_md5: None,
+            content_type: content_type.to_string(),
+            upload_timestamp: OffsetDateTime::now_utc().unix_timestamp() as u64,
+        })
+    }
+
     async fn delete_file_version(
         &self,
         file_id: &str,
@@ -111,4 +274,132 @@ impl FileHost for S3Host {
             file_name: file_name.to_string(),
         })
     }
+
+    async fn get_file(
+        &self,
+        _file_id: &str,
+        file_name: &str,
+    ) -> Result<Bytes, FileHostingError> {
+        let (data, code) = self
+            .bucket
+            .get_object(format!("/{}", file_name))
+            .await
+            .map_err(|_| {
+                FileHostingError::S3Error(
+                    "Error while downloading file from S3".to_string(),
+                )
+            })?;
+
+        if code != 200 {
+            return Err(FileHostingError::S3Error(format!(
+                "Error while downloading file from S3: status {}",
+                code
+            )));
+        }
+
+        Ok(data.into())
+    }
+
+    async fn list_files(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<ListedFile>, FileHostingError> {
+        let pages =
+            self.bucket
+                .list(prefix.to_string(), None)
+                .await
+                .map_err(|_| {
+                    FileHostingError::S3Error(
+                        "Error while listing files in S3".to_string(),
+                    )
+                })?;
+
+        Ok(pages
+            .into_iter()
+            .flat_map(|x| x.contents)
+            .map(|x| ListedFile {
+                file_id: x.key.clone(),
+                file_name: x.key,
+                content_length: x.size,
+                upload_timestamp: parse_last_modified(&x.last_modified),
+            })
+            .collect())
+    }
+
+    async fn head_file(
+        &self,
+        _file_id: &str,
+        file_name: &str,
+    ) -> Result<Option<FileMetadata>, FileHostingError> {
+        let (head, code) = self
+            .bucket
+            .head_object(format!("/{}", file_name))
+            .await
+            .map_err(|_| {
+                FileHostingError::S3Error(
+                    "Error while getting file metadata from S3".to_string(),
+                )
+            })?;
+
+        if code == 404 {
+            return Ok(None);
+        } else if code != 200 {
+            return Err(FileHostingError::S3Error(format!(
+                "Error while getting file metadata from S3: status {}",
+                code
+            )));
+        }
+
+        // S3 doesn't keep the SHA1 hashes of files
+        Ok(Some(FileMetadata {
+            file_id: file_name.to_string(),
+            file_name: file_name.to_string(),
+            content_length: head.content_length.unwrap_or(0) as u64,
+            content_type: head.content_type,
+            content_sha1: None,
+            etag: head.e_tag,
+        }))
+    }
+
+    async fn signed_download_url(
+        &self,
+        _file_id: &str,
+        file_name: &str,
+        expires_in: std::time::Duration,
+    ) -> Result<String, FileHostingError> {
+        #[allow(clippy::cast_possible_truncation)]
+        let expires_in =
+            expires_in.as_secs().clamp(1, MAX_PRESIGNED_URL_DURATION) as u32;
+
+        self.bucket
+            .presign_get(format!("/{}", file_name), expires_in, None)
+            .map_err(|_| {
+                FileHostingError::S3Error(
+                    "Error while signing S3 download URL".to_string(),
+                )
+            })
+    }
+}
+
+/// Reads S3 modification times such as `2022-06-24T12:30:00.000Z`. Times
+/// which can't be read are treated as the current time, so that the file
+/// counts as recently uploaded.
+fn parse_last_modified(last_modified: &str) -> u64 {
+    last_modified
+        .get(..19)
+        .and_then(|x| PrimitiveDateTime::parse(x, "%Y-%m-%dT%H:%M:%S").ok())
+        .map(|x| x.assume_utc())
+        .unwrap_or_else(OffsetDateTime::now_utc)
+        .unix_timestamp() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_last_modified;
+
+    #[test]
+    fn last_modified_times() {
+        assert_eq!(parse_last_modified("2022-06-24T12:30:00.000Z"), 1656073800);
+        assert!(parse_last_modified("yesterday") > 1656073800);
+    }
 }
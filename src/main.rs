@@ -23,12 +23,18 @@ use search::indexing::index_projects;
 use search::indexing::IndexingSettings;
 use std::sync::Arc;
 
+mod analytics;
 mod database;
+mod downloads;
+mod dumps;
+mod file_hashes;
 mod file_hosting;
 mod health;
 mod models;
 mod ratelimit;
+mod reconciliation;
 mod routes;
+mod scanning;
 mod scheduler;
 mod search;
 mod util;
@@ -106,31 +112,55 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("Database connection failed");
 
+    // Load the scanner rules now, so invalid ones don't fail uploads later
+    lazy_static::initialize(&scanning::SCANNERS);
+
     let storage_backend =
         dotenv::var("STORAGE_BACKEND").unwrap_or_else(|_| "local".to_string());
 
+    // Files are copied to the secondary storage backends, if there are any
+    let secondary_backends =
+        parse_strings_from_var("SECONDARY_STORAGE_BACKENDS")
+            .unwrap_or_default();
+
+    let local_host = if storage_backend == "local"
+        || secondary_backends.iter().any(|x| x == "local")
+    {
+        Some(Arc::new(file_hosting::LocalHost::new(
+            dotenv::var("MOCK_FILE_PATH").unwrap(),
+            &dotenv::var("CDN_URL").unwrap(),
+        )))
+    } else {
+        None
+    };
+
+    let primary_host = create_file_host(&storage_backend, &local_host).await;
+
+    let replicated_host = if secondary_backends.is_empty() {
+        None
+    } else {
+        let mut secondaries = Vec::new();
+        for backend in secondary_backends {
+            secondaries.push(file_hosting::Replica {
+                host: create_file_host(&backend, &local_host).await,
+                name: backend,
+            });
+        }
+
+        Some(Arc::new(file_hosting::ReplicatedHost::new(
+            file_hosting::Replica {
+                name: storage_backend.clone(),
+                host: primary_host.clone(),
+            },
+            secondaries,
+            pool.clone(),
+        )))
+    };
+
     let file_host: Arc<dyn file_hosting::FileHost + Send + Sync> =
-        match storage_backend.as_str() {
-            "backblaze" => Arc::new(
-                file_hosting::BackblazeHost::new(
-                    &dotenv::var("BACKBLAZE_KEY_ID").unwrap(),
-                    &dotenv::var("BACKBLAZE_KEY").unwrap(),
-                    &dotenv::var("BACKBLAZE_BUCKET_ID").unwrap(),
-                )
-                .await,
-            ),
-            "s3" => Arc::new(
-                S3Host::new(
-                    &*dotenv::var("S3_BUCKET_NAME").unwrap(),
-                    &*dotenv::var("S3_REGION").unwrap(),
-                    &*dotenv::var("S3_URL").unwrap(),
-                    &*dotenv::var("S3_ACCESS_TOKEN").unwrap(),
-                    &*dotenv::var("S3_SECRET").unwrap(),
-                )
-                .unwrap(),
-            ),
-            "local" => Arc::new(file_hosting::MockHost::new()),
-            _ => panic!("Invalid storage backend specified. Aborting startup!"),
+        match &replicated_host {
+            Some(host) => host.clone(),
+            None => primary_host,
         };
 
     let mut scheduler = scheduler::Scheduler::new();
@@ -167,8 +197,10 @@ async fn main() -> std::io::Result<()> {
     });
 
     let pool_ref = pool.clone();
+    let file_host_ref = file_host.clone();
     scheduler.run(std::time::Duration::from_secs(15 * 60), move || {
         let pool_ref = pool_ref.clone();
+        let file_host_ref = file_host_ref.clone();
         // Use sqlx to delete records more than an hour old
         info!("Deleting old records from temporary tables");
 
@@ -189,21 +221,77 @@ async fn main() -> std::io::Result<()> {
                 );
             }
 
+            let sessions_result =
+                routes::upload_sessions::remove_expired_sessions(
+                    &pool_ref,
+                    &*file_host_ref,
+                )
+                .await;
+
+            if let Err(e) = sessions_result {
+                warn!("Deleting expired upload sessions failed: {:?}", e);
+            }
+
             info!("Finished deleting old records from temporary tables");
         }
     });
 
     scheduler::schedule_versions(&mut scheduler, pool.clone(), skip_initial);
 
-    let ip_salt = Pepper {
-        pepper: crate::models::ids::Base62Id(
-            crate::models::ids::random_base62(11),
-        )
-        .to_string(),
-    };
+    dumps::schedule_dumps(&mut scheduler, pool.clone(), file_host.clone());
+
+    file_hashes::schedule_hash_backfill(
+        &mut scheduler,
+        pool.clone(),
+        file_host.clone(),
+    );
+
+    if let Some(replicated_host) = replicated_host {
+        let replica_sync_interval = std::time::Duration::from_secs(
+            parse_var("REPLICA_SYNC_INTERVAL").unwrap_or(600),
+        );
+
+        scheduler.run(replica_sync_interval, move || {
+            let replicated_host = replicated_host.clone();
+            async move {
+                info!("Syncing file replicas");
+                match replicated_host.sync_replicas(100).await {
+                    Ok(synced) => {
+                        info!("Done syncing {} file replicas", synced)
+                    }
+                    Err(e) => warn!("Syncing file replicas failed: {}", e),
+                }
+            }
+        });
+    }
+
+    reconciliation::schedule_reconciliation(
+        &mut scheduler,
+        pool.clone(),
+        file_host.clone(),
+    );
+
+    let download_queue = Arc::new(downloads::DownloadQueue::from_env());
+    downloads::schedule_download_flushes(
+        &mut scheduler,
+        pool.clone(),
+        download_queue.clone(),
+    );
+
+    analytics::schedule_analytics_rollups(&mut scheduler, pool.clone());
+
+    let ip_salt = downloads::get_pepper(&pool)
+        .await
+        .expect("Unable to load the download pepper");
+
+    let served_local_host =
+        local_host.filter(|_| parse_var("SERVE_LOCAL_FILES").unwrap_or(false));
 
     let store = MemoryStore::new();
 
+    let shutdown_pool = pool.clone();
+    let shutdown_download_queue = download_queue.clone();
+
     info!("Starting Actix HTTP server!");
 
     // Init App
@@ -256,17 +344,41 @@ async fn main() -> std::io::Result<()> {
             .app_data(web::Data::new(file_host.clone()))
             .app_data(web::Data::new(search_config.clone()))
             .app_data(web::Data::new(ip_salt.clone()))
+            .app_data(web::Data::new(download_queue.clone()))
             .configure(routes::v1_config)
             .configure(routes::v2_config)
             .service(routes::index_get)
             .service(routes::health_get)
             .service(web::scope("maven").configure(routes::maven_config))
             .service(web::scope("updates").configure(routes::updates))
+            .configure(|cfg| {
+                // The local file host's files are served under `/cdn`, so
+                // `CDN_URL` should point there
+                if let Some(host) = &served_local_host {
+                    cfg.service(
+                        web::scope("cdn")
+                            .app_data(web::Data::new(host.clone()))
+                            .configure(routes::local_files_config),
+                    );
+                }
+            })
             .default_service(web::get().to(routes::not_found))
     })
     .bind(dotenv::var("BIND_ADDR").unwrap())?
     .run()
-    .await
+    .await?;
+
+    // Downloads counted since the last scheduled flush would be lost
+    // otherwise
+    match shutdown_download_queue.flush(&shutdown_pool).await {
+        Ok(0) => {}
+        Ok(count) => info!("Counted {} downloads before shutting down", count),
+        Err(e) => {
+            warn!("Counting downloads before shutting down failed: {}", e)
+        }
+    }
+
+    Ok(())
 }
 
 // This is so that env vars not used immediately don't panic at runtime
@@ -282,6 +394,34 @@ fn check_env_vars() -> bool {
         check
     }
 
+    fn check_storage_backend(backend: &str) -> bool {
+        let mut failed = false;
+
+        match backend {
+            "backblaze" => {
+                failed |= check_var::<String>("BACKBLAZE_KEY_ID");
+                failed |= check_var::<String>("BACKBLAZE_KEY");
+                failed |= check_var::<String>("BACKBLAZE_BUCKET_ID");
+            }
+            "s3" => {
+                failed |= check_var::<String>("S3_ACCESS_TOKEN");
+                failed |= check_var::<String>("S3_SECRET");
+                failed |= check_var::<String>("S3_URL");
+                failed |= check_var::<String>("S3_REGION");
+                failed |= check_var::<String>("S3_BUCKET_NAME");
+            }
+            "local" => {
+                failed |= check_var::<String>("MOCK_FILE_PATH");
+            }
+            backend => {
+                warn!("Storage backend {} is invalid. Expected \"backblaze\", \"s3\", or \"local\".", backend);
+                failed |= true;
+            }
+        }
+
+        failed
+    }
+
     let mut failed = false;
 
     if parse_strings_from_var("RATE_LIMIT_IGNORE_IPS").is_none() {
@@ -306,36 +446,60 @@ fn check_env_vars() -> bool {
 
     let storage_backend = dotenv::var("STORAGE_BACKEND").ok();
     match storage_backend.as_deref() {
-        Some("backblaze") => {
-            failed |= check_var::<String>("BACKBLAZE_KEY_ID");
-            failed |= check_var::<String>("BACKBLAZE_KEY");
-            failed |= check_var::<String>("BACKBLAZE_BUCKET_ID");
-        }
-        Some("s3") => {
-            failed |= check_var::<String>("S3_ACCESS_TOKEN");
-            failed |= check_var::<String>("S3_SECRET");
-            failed |= check_var::<String>("S3_URL");
-            failed |= check_var::<String>("S3_REGION");
-            failed |= check_var::<String>("S3_BUCKET_NAME");
-        }
-        Some("local") => {
-            failed |= check_var::<String>("MOCK_FILE_PATH");
-        }
-        Some(backend) => {
-            warn!("Variable `STORAGE_BACKEND` contains an invalid value: {}. Expected \"backblaze\", \"s3\", or \"local\".", backend);
-            failed |= true;
-        }
+        Some(backend) => failed |= check_storage_backend(backend),
         _ => {
             warn!("Variable `STORAGE_BACKEND` is not set!");
             failed |= true;
         }
     }
+
+    for backend in
+        parse_strings_from_var("SECONDARY_STORAGE_BACKENDS").unwrap_or_default()
+    {
+        failed |= check_storage_backend(&backend);
+    }
     failed |= check_var::<usize>("LOCAL_INDEX_INTERVAL");
 
     failed |= check_var::<usize>("VERSION_INDEX_INTERVAL");
 
+    failed |= check_var::<usize>("DUMP_INTERVAL");
+    failed |= check_var::<usize>("DUMP_RETENTION");
+
+    failed |= check_var::<usize>("HASH_BACKFILL_INTERVAL");
+
     failed |= check_var::<String>("GITHUB_CLIENT_ID");
     failed |= check_var::<String>("GITHUB_CLIENT_SECRET");
 
     failed
 }
+
+/// Connects to a storage backend. Each kind of backend is configured by its
+/// own variables, so it can only be used once.
+async fn create_file_host(
+    backend: &str,
+    local_host: &Option<Arc<file_hosting::LocalHost>>,
+) -> Arc<dyn file_hosting::FileHost + Send + Sync> {
+    match backend {
+        "backblaze" => Arc::new(
+            file_hosting::BackblazeHost::new(
+                &dotenv::var("BACKBLAZE_KEY_ID").unwrap(),
+                &dotenv::var("BACKBLAZE_KEY").unwrap(),
+                &dotenv::var("BACKBLAZE_BUCKET_ID").unwrap(),
+            )
+            .await
+            .unwrap(),
+        ),
+        "s3" => Arc::new(
+            S3Host::new(
+                &*dotenv::var("S3_BUCKET_NAME").unwrap(),
+                &*dotenv::var("S3_REGION").unwrap(),
+                &*dotenv::var("S3_URL").unwrap(),
+                &*dotenv::var("S3_ACCESS_TOKEN").unwrap(),
+                &*dotenv::var("S3_SECRET").unwrap(),
+            )
+            .unwrap(),
+        ),
+        "local" => local_host.clone().unwrap(),
+        _ => panic!("Invalid storage backend specified. Aborting startup!"),
+    }
+}
@@ -0,0 +1,62 @@
+use super::projects::{ProjectId, VersionId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use time::{Date, OffsetDateTime};
+
+/// The downloads on one day
+#[derive(Serialize, Deserialize)]
+pub struct DailyDownloads {
+    #[serde(with = "crate::util::time_ser")]
+    /// The start of the day in UTC
+    pub day: OffsetDateTime,
+    pub downloads: i64,
+}
+
+impl DailyDownloads {
+    pub fn new(day: Date, downloads: i64) -> Self {
+        Self {
+            day: day.midnight().assume_utc(),
+            downloads,
+        }
+    }
+}
+
+/// The followers a project gained and lost on one day
+#[derive(Serialize, Deserialize)]
+pub struct DailyFollowers {
+    #[serde(with = "crate::util::time_ser")]
+    /// The start of the day in UTC
+    pub day: OffsetDateTime,
+    pub gained: i32,
+    pub lost: i32,
+}
+
+impl From<crate::database::models::analytics_item::DailyFollowers>
+    for DailyFollowers
+{
+    fn from(
+        data: crate::database::models::analytics_item::DailyFollowers,
+    ) -> Self {
+        Self {
+            day: data.day.midnight().assume_utc(),
+            gained: data.gained,
+            lost: data.lost,
+        }
+    }
+}
+
+/// The analytics of a project, only counting days with downloads or follower
+/// changes. Today's numbers only include what was rolled up so far.
+#[derive(Serialize, Deserialize)]
+pub struct ProjectAnalytics {
+    pub project_id: ProjectId,
+    /// The daily downloads of each version of the project
+    pub version_downloads: HashMap<VersionId, Vec<DailyDownloads>>,
+    /// The daily downloads of the versions for each game version. Versions
+    /// for several game versions count for each of them.
+    pub game_version_downloads: HashMap<String, Vec<DailyDownloads>>,
+    /// The daily downloads of the versions for each loader. Versions for
+    /// several loaders count for each of them.
+    pub loader_downloads: HashMap<String, Vec<DailyDownloads>>,
+    pub followers: Vec<DailyFollowers>,
+}
@@ -0,0 +1,86 @@
+use super::ids::Base62Id;
+use super::projects::{ProjectId, VersionId};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// An opaque position in the changes feed, encoded as base62 for usage in the API
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Base62Id")]
+#[serde(into = "Base62Id")]
+pub struct ChangeCursor(pub u64);
+
+/// A single entry of the changes feed
+#[derive(Serialize, Deserialize)]
+pub struct Change {
+    /// The cursor to resume the feed from to get the changes after this one
+    pub cursor: ChangeCursor,
+    /// Whether the project or version was created, updated, hidden or deleted
+    pub change_type: ChangeType,
+    /// The project which was changed, or whose version was changed
+    pub project_id: ProjectId,
+    /// The version which was changed, if this is a version change
+    pub version_id: Option<VersionId>,
+    #[serde(with = "crate::util::time_ser")]
+    /// The date at which the change happened
+    pub created: OffsetDateTime,
+}
+
+impl From<crate::database::models::change_item::Change> for Change {
+    fn from(data: crate::database::models::change_item::Change) -> Self {
+        Self {
+            cursor: ChangeCursor(data.position as u64),
+            change_type: ChangeType::from_str(&data.change_type),
+            project_id: data.project_id.into(),
+            version_id: data.version_id.map(Into::into),
+            created: data.created,
+        }
+    }
+}
+
+/// A page of the changes feed
+#[derive(Serialize, Deserialize)]
+pub struct ChangesFeed {
+    /// The changes after the requested cursor, oldest first
+    pub changes: Vec<Change>,
+    /// The cursor to request the next page with. This is the requested cursor
+    /// if there were no new changes.
+    pub cursor: ChangeCursor,
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeType {
+    Created,
+    Updated,
+    /// The project or version can no longer be viewed by everyone, but still
+    /// exists
+    Hidden,
+    Deleted,
+}
+
+impl std::fmt::Display for ChangeType {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str(self.as_str())
+    }
+}
+
+impl ChangeType {
+    // These are constant, so this can remove unnecessary allocations (`to_string`)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeType::Created => "created",
+            ChangeType::Updated => "updated",
+            ChangeType::Hidden => "hidden",
+            ChangeType::Deleted => "deleted",
+        }
+    }
+
+    pub fn from_str(string: &str) -> ChangeType {
+        match string {
+            "created" => ChangeType::Created,
+            "hidden" => ChangeType::Hidden,
+            "deleted" => ChangeType::Deleted,
+            _ => ChangeType::Updated,
+        }
+    }
+}
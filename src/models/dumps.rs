@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+/// A compressed JSON lines export of all public projects and their versions
+#[derive(Serialize, Deserialize)]
+pub struct Dump {
+    /// A direct link to the dump for downloading it.
+    pub url: String,
+    /// The filename of the dump.
+    pub filename: String,
+    /// The size in bytes of the (compressed) dump
+    pub size: u64,
+    /// A map of hashes of the dump. The key is the hashing algorithm
+    /// and the value is the string version of the hash.
+    pub hashes: HashMap<String, String>,
+    /// The number of projects contained in the dump
+    pub projects: u32,
+    /// The number of versions contained in the dump
+    pub versions: u32,
+    #[serde(with = "crate::util::time_ser")]
+    /// The date at which the dump was created
+    pub created: OffsetDateTime,
+}
+
+impl From<crate::database::models::dump_item::Dump> for Dump {
+    fn from(data: crate::database::models::dump_item::Dump) -> Self {
+        let mut hashes = HashMap::new();
+        hashes.insert("sha1".to_string(), data.sha1);
+        hashes.insert("sha512".to_string(), data.sha512);
+
+        Self {
+            url: data.url,
+            filename: data
+                .file_name
+                .rsplit('/')
+                .next()
+                .unwrap_or(&data.file_name)
+                .to_string(),
+            size: data.size as u64,
+            hashes,
+            projects: data.projects as u32,
+            versions: data.versions as u32,
+            created: data.created,
+        }
+    }
+}
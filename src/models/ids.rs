@@ -1,9 +1,11 @@
 use thiserror::Error;
 
+pub use super::changes::ChangeCursor;
 pub use super::notifications::NotificationId;
 pub use super::projects::{ProjectId, VersionId};
 pub use super::reports::ReportId;
 pub use super::teams::TeamId;
+pub use super::upload_sessions::UploadSessionId;
 pub use super::users::UserId;
 
 /// Generates a random 64 bit integer that is exactly `n` characters
@@ -109,6 +111,8 @@ base62_id_impl!(VersionId, VersionId);
 base62_id_impl!(TeamId, TeamId);
 base62_id_impl!(ReportId, ReportId);
 base62_id_impl!(NotificationId, NotificationId);
+base62_id_impl!(ChangeCursor, ChangeCursor);
+base62_id_impl!(UploadSessionId, UploadSessionId);
 
 pub mod base62_impl {
     use serde::de::{self, Deserializer, Visitor};
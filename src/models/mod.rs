@@ -1,3 +1,6 @@
+pub mod analytics;
+pub mod changes;
+pub mod dumps;
 pub mod error;
 pub mod ids;
 pub mod notifications;
@@ -5,4 +8,5 @@ pub mod pack;
 pub mod projects;
 pub mod reports;
 pub mod teams;
+pub mod upload_sessions;
 pub mod users;
@@ -19,6 +19,41 @@ pub struct PackFormat {
     pub dependencies: std::collections::HashMap<PackDependency, String>,
 }
 
+/// An overview of what a modpack file installs, kept for moderators
+/// reviewing it
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct PackSummary {
+    /// The number of files downloaded when installing the pack
+    pub file_count: u32,
+    /// The combined size of the downloaded files, as declared by the pack
+    pub total_file_size: u64,
+    pub override_count: u32,
+    pub client_override_count: u32,
+    pub server_override_count: u32,
+    /// The number of jars included in any of the overrides instead of being
+    /// downloaded from an allowed source
+    pub override_jar_count: u32,
+    pub minecraft_version: String,
+    pub loader: Option<String>,
+    pub loader_version: Option<String>,
+}
+
+impl From<crate::database::models::version_item::PackSummary> for PackSummary {
+    fn from(data: crate::database::models::version_item::PackSummary) -> Self {
+        PackSummary {
+            file_count: data.file_count as u32,
+            total_file_size: data.total_file_size as u64,
+            override_count: data.override_count as u32,
+            client_override_count: data.client_override_count as u32,
+            server_override_count: data.server_override_count as u32,
+            override_jar_count: data.override_jar_count as u32,
+            minecraft_version: data.minecraft_version,
+            loader: data.loader,
+            loader_version: data.loader_version,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Validate, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PackFile {
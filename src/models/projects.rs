@@ -3,6 +3,7 @@ use super::teams::TeamId;
 use super::users::UserId;
 use crate::database::models::project_item::QueryProject;
 use crate::database::models::version_item::QueryVersion;
+use crate::models::pack::PackSummary;
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use validator::Validate;
@@ -354,11 +355,20 @@ impl From<QueryVersion> for Version {
                         hashes: f
                             .hashes
                             .into_iter()
+                            .filter(|(k, _)| {
+                                PUBLIC_HASH_ALGORITHMS.contains(&k.as_str())
+                            })
                             .map(|(k, v)| Some((k, String::from_utf8(v).ok()?)))
                             .collect::<Option<_>>()
                             .unwrap_or_default(),
                         primary: f.primary,
                         size: f.size,
+                        findings: f
+                            .findings
+                            .into_iter()
+                            .map(ValidationFinding::from)
+                            .collect(),
+                        pack_summary: f.pack_summary.map(PackSummary::from),
                     }
                 })
                 .collect(),
@@ -384,13 +394,41 @@ impl From<QueryVersion> for Version {
     }
 }
 
+/// The hashing algorithms the files of versions can be looked up by. Files
+/// also have MD5 and SHA-256 hashes, but those are only served to Maven
+/// clients.
+pub const PUBLIC_HASH_ALGORITHMS: [&str; 2] = ["sha1", "sha512"];
+
+/// Deserializes the name of a hashing algorithm, which has to be one of the
+/// `PUBLIC_HASH_ALGORITHMS`
+pub fn deserialize_hash_algorithm<'de, D>(
+    deserializer: D,
+) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let algorithm = String::deserialize(deserializer)?;
+
+    if PUBLIC_HASH_ALGORITHMS.contains(&algorithm.as_str()) {
+        Ok(algorithm)
+    } else {
+        Err(serde::de::Error::custom(format!(
+            "Unsupported hashing algorithm: {}",
+            algorithm
+        )))
+    }
+}
+
 /// A single project file, with a url for the file and the file's hash
 #[derive(Serialize, Deserialize)]
 pub struct VersionFile {
     /// A map of hashes of the file.  The key is the hashing algorithm
-    /// and the value is the string version of the hash.
+    /// and the value is the string version of the hash. Only the
+    /// `PUBLIC_HASH_ALGORITHMS` are included.
     pub hashes: std::collections::HashMap<String, String>,
-    /// A direct link to the file for downloading it.
+    /// A direct link to the file for downloading it. With content addressed
+    /// storage, files with the same contents share their link, which ends
+    /// with the name the contents were first uploaded as.
     pub url: String,
     /// The filename of the file.
     pub filename: String,
@@ -398,6 +436,116 @@ pub struct VersionFile {
     pub primary: bool,
     /// The size in bytes of the file
     pub size: u32,
+    /// Everything noteworthy found while validating the file
+    pub findings: Vec<ValidationFinding>,
+    /// What the file installs, if it is a modpack
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pack_summary: Option<PackSummary>,
+}
+
+/// A single finding from validating a version file, such as a missing
+/// manifest or a mod file which only contains source code
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ValidationFinding {
+    pub severity: FindingSeverity,
+    /// A stable identifier for the kind of finding, such as `source_file`
+    pub code: String,
+    /// A human readable description of the finding
+    pub message: String,
+    /// The path of the file inside the archive the finding is about
+    pub path: Option<String>,
+}
+
+impl From<crate::database::models::version_item::ValidationFinding>
+    for ValidationFinding
+{
+    fn from(
+        data: crate::database::models::version_item::ValidationFinding,
+    ) -> Self {
+        ValidationFinding {
+            severity: FindingSeverity::from_str(&data.severity),
+            code: data.code,
+            message: data.message,
+            path: data.path,
+        }
+    }
+}
+
+impl From<ValidationFinding>
+    for crate::database::models::version_item::ValidationFinding
+{
+    fn from(data: ValidationFinding) -> Self {
+        crate::database::models::version_item::ValidationFinding {
+            severity: data.severity.as_str().to_string(),
+            code: data.code,
+            message: data.message,
+            path: data.path,
+        }
+    }
+}
+
+impl ValidationFinding {
+    pub fn new(
+        severity: FindingSeverity,
+        code: &str,
+        message: impl Into<String>,
+    ) -> Self {
+        ValidationFinding {
+            severity,
+            code: code.to_string(),
+            message: message.into(),
+            path: None,
+        }
+    }
+
+    pub fn info(code: &str, message: impl Into<String>) -> Self {
+        Self::new(FindingSeverity::Info, code, message)
+    }
+
+    pub fn warning(code: &str, message: impl Into<String>) -> Self {
+        Self::new(FindingSeverity::Warning, code, message)
+    }
+
+    pub fn at(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+}
+
+/// Files with findings of `Warning` or above are not marked as primary
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum FindingSeverity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for FindingSeverity {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str(self.as_str())
+    }
+}
+
+impl FindingSeverity {
+    // These are constant, so this can remove unneccessary allocations (`to_string`)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FindingSeverity::Info => "info",
+            FindingSeverity::Warning => "warning",
+            FindingSeverity::Error => "error",
+        }
+    }
+
+    pub fn from_str(string: &str) -> FindingSeverity {
+        match string {
+            "info" => FindingSeverity::Info,
+            "error" => FindingSeverity::Error,
+            _ => FindingSeverity::Warning,
+        }
+    }
 }
 
 /// A dendency which describes what versions are required, break support, or are optional to the
@@ -439,7 +587,7 @@ impl VersionType {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum DependencyType {
     Required,
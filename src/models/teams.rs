@@ -35,7 +35,8 @@ bitflags::bitflags! {
         const REMOVE_MEMBER = 1 << 5;
         const EDIT_MEMBER = 1 << 6;
         const DELETE_PROJECT = 1 << 7;
-        const ALL = 0b11111111;
+        const VIEW_ANALYTICS = 1 << 8;
+        const ALL = 0b111111111;
     }
 }
 
@@ -0,0 +1,46 @@
+use super::ids::Base62Id;
+use crate::models::ids::VersionId;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Base62Id")]
+#[serde(into = "Base62Id")]
+pub struct UploadSessionId(pub u64);
+
+/// An in-progress upload of a single version file, sent in chunks
+#[derive(Serialize, Deserialize)]
+pub struct UploadSession {
+    pub id: UploadSessionId,
+    /// The version the file is attached to once the upload is finalized
+    pub version_id: VersionId,
+    pub filename: String,
+    /// The total size of the file in bytes
+    pub size: u64,
+    /// The number of bytes received so far. This is the offset the next
+    /// chunk has to be sent at, which allows resuming interrupted uploads.
+    pub received: u64,
+    #[serde(with = "crate::util::time_ser")]
+    pub created: OffsetDateTime,
+    #[serde(with = "crate::util::time_ser")]
+    /// The date after which the session and its chunks are deleted
+    pub expires: OffsetDateTime,
+}
+
+impl From<crate::database::models::upload_session_item::UploadSession>
+    for UploadSession
+{
+    fn from(
+        data: crate::database::models::upload_session_item::UploadSession,
+    ) -> Self {
+        Self {
+            id: data.id.into(),
+            version_id: data.version_id.into(),
+            filename: data.filename,
+            size: data.size as u64,
+            received: data.received as u64,
+            created: data.created,
+            expires: data.expires,
+        }
+    }
+}
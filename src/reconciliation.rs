@@ -0,0 +1,377 @@
+//! Periodic comparison of the files stored by the file host with the files
+//! the database refers to. Files left behind by failed uploads or deletes
+//! are removed, and files which went missing from the host are reported.
+use crate::database::models::blob_item::StoredBlob;
+use crate::file_hosting::{FileHost, FileHostingError, ListedFile};
+use crate::scheduler::Scheduler;
+use crate::util::env::parse_var;
+use log::{info, warn};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::sync::Arc;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+/// The folders which only contain files the database refers to. Upload
+/// session chunks and dumps are removed when they expire instead.
+const MANAGED_PREFIXES: [&str; 3] = ["data/", "user/", "blobs/"];
+
+/// If more than this share of the stored files seems to be orphaned, the CDN
+/// URL most likely changed, so nothing is deleted
+const MAX_ORPHAN_SHARE: f64 = 0.5;
+
+/// How often (in seconds) to check whether a reconciliation is due. This
+/// bounds how long a restart can delay a reconciliation.
+const RECONCILE_CHECK_INTERVAL: u64 = 3600;
+
+#[derive(Error, Debug)]
+pub enum ReconciliationError {
+    #[error("Database Error: {0}")]
+    Sqlx(#[from] sqlx::error::Error),
+    #[error("Error while accessing stored files: {0}")]
+    FileHosting(#[from] FileHostingError),
+    #[error("Environment Error")]
+    Env(#[from] dotenv::Error),
+}
+
+#[derive(Debug, Default)]
+pub struct ReconciliationReport {
+    pub listed_files: usize,
+    /// Stored files which nothing refers to
+    pub orphans: Vec<ListedFile>,
+    /// Files which are referred to, but aren't stored
+    pub missing: Vec<String>,
+    /// The hashes of blobs which no file references anymore
+    pub unreferenced_blobs: Vec<String>,
+    pub deleted_files: usize,
+}
+
+pub fn schedule_reconciliation(
+    scheduler: &mut Scheduler,
+    pool: PgPool,
+    file_host: Arc<dyn FileHost + Send + Sync>,
+) {
+    let reconcile_interval = std::time::Duration::from_secs(
+        parse_var("RECONCILE_INTERVAL").unwrap_or(86400),
+    );
+    let check_interval = reconcile_interval
+        .min(std::time::Duration::from_secs(RECONCILE_CHECK_INTERVAL));
+    let dry_run = parse_var("RECONCILE_DRY_RUN").unwrap_or(true);
+
+    scheduler.run(check_interval, move || {
+        let pool_ref = pool.clone();
+        let file_host_ref = file_host.clone();
+        async move {
+            match is_reconciliation_due(&pool_ref, reconcile_interval).await {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(e) => {
+                    warn!("Checking for a due reconciliation failed: {}", e);
+                    return;
+                }
+            }
+
+            info!("Reconciling stored files");
+            let started = OffsetDateTime::now_utc();
+            let report =
+                match reconcile(&pool_ref, &*file_host_ref, dry_run).await {
+                    Ok(report) => report,
+                    Err(e) => {
+                        warn!("Reconciling stored files failed: {}", e);
+                        return;
+                    }
+                };
+            info!(
+                "Done reconciling {} stored files: {} orphaned, {} missing, {} unreferenced blobs, {} files deleted",
+                report.listed_files,
+                report.orphans.len(),
+                report.missing.len(),
+                report.unreferenced_blobs.len(),
+                report.deleted_files,
+            );
+
+            if let Err(e) =
+                record_reconciliation(&report, started, dry_run, &pool_ref)
+                    .await
+            {
+                warn!("Recording the reconciliation failed: {}", e);
+            }
+        }
+    });
+}
+
+/// Whether the last reconciliation started at least `reconcile_interval`
+/// ago. Listing every stored file is expensive, so this is read from the
+/// database to keep restarts from repeating or postponing reconciliations.
+async fn is_reconciliation_due(
+    pool: &PgPool,
+    reconcile_interval: std::time::Duration,
+) -> Result<bool, sqlx::Error> {
+    let latest = sqlx::query!(
+        "
+        SELECT MAX(started) started
+        FROM reconciliation_runs
+        "
+    )
+    .fetch_one(pool)
+    .await?
+    .started;
+
+    Ok(latest.map_or(true, |x| {
+        OffsetDateTime::now_utc() - x >= reconcile_interval
+    }))
+}
+
+async fn record_reconciliation(
+    report: &ReconciliationReport,
+    started: OffsetDateTime,
+    dry_run: bool,
+    pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        INSERT INTO reconciliation_runs (started, dry_run, listed_files, orphaned_files, missing_files, deleted_files)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ",
+        started,
+        dry_run,
+        report.listed_files as i32,
+        report.orphans.len() as i32,
+        report.missing.len() as i32,
+        report.deleted_files as i32,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Compares the stored files with the database. Files and blobs which are
+/// younger than `RECONCILE_GRACE_PERIOD` seconds (1 day by default) are never
+/// considered orphaned or unreferenced, since their upload may still be in
+/// progress. In a dry run, problems are only reported.
+pub async fn reconcile(
+    pool: &PgPool,
+    file_host: &(dyn FileHost + Send + Sync),
+    dry_run: bool,
+) -> Result<ReconciliationReport, ReconciliationError> {
+    let cdn_url = dotenv::var("CDN_URL")?;
+    let grace_period: u64 =
+        parse_var("RECONCILE_GRACE_PERIOD").unwrap_or(86400);
+    let cutoff = (OffsetDateTime::now_utc().unix_timestamp() as u64)
+        .saturating_sub(grace_period);
+
+    let known = known_files(pool, &cdn_url).await?;
+
+    let mut listed = Vec::new();
+    for prefix in MANAGED_PREFIXES.iter() {
+        listed.extend(file_host.list_files(prefix).await?);
+    }
+
+    let mut report = compare_files(listed, &known, cutoff);
+    report.unreferenced_blobs = StoredBlob::list_unreferenced(
+        OffsetDateTime::from_unix_timestamp(cutoff as i64),
+        pool,
+    )
+    .await?;
+
+    for file in &report.missing {
+        warn!("Stored file {} is missing", file);
+    }
+    for file in &report.orphans {
+        info!("Stored file {} is orphaned", file.file_name);
+    }
+
+    if dry_run {
+        return Ok(report);
+    }
+
+    if report.orphans.len() as f64
+        > report.listed_files as f64 * MAX_ORPHAN_SHARE
+    {
+        warn!(
+            "{} of {} stored files seem to be orphaned, not deleting any. Did the CDN URL change?",
+            report.orphans.len(),
+            report.listed_files
+        );
+        return Ok(report);
+    }
+
+    for file in &report.orphans {
+        match file_host
+            .delete_file_version(&file.file_id, &file.file_name)
+            .await
+        {
+            Ok(_) => report.deleted_files += 1,
+            Err(e) => {
+                warn!("Deleting orphaned file {} failed: {}", file.file_name, e)
+            }
+        }
+    }
+
+    for sha512 in &report.unreferenced_blobs {
+        match delete_blob(sha512, pool, file_host).await {
+            Ok(true) => report.deleted_files += 1,
+            Ok(false) => {}
+            Err(e) => warn!("Deleting blob {} failed: {}", sha512, e),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Deletes a blob if it still isn't referenced. The record is only removed
+/// once the stored file was deleted.
+async fn delete_blob(
+    sha512: &str,
+    pool: &PgPool,
+    file_host: &(dyn FileHost + Send + Sync),
+) -> Result<bool, ReconciliationError> {
+    let mut transaction = pool.begin().await?;
+
+    let blobs =
+        StoredBlob::take_unreferenced(&[sha512.to_string()], &mut transaction)
+            .await?;
+    for blob in &blobs {
+        file_host
+            .delete_file_version(&blob.file_id, &blob.file_name)
+            .await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(!blobs.is_empty())
+}
+
+/// Gets the names of all stored files the database refers to
+async fn known_files(
+    pool: &PgPool,
+    cdn_url: &str,
+) -> Result<HashSet<String>, ReconciliationError> {
+    let urls = sqlx::query!(
+        r#"
+        SELECT url "url?" FROM files WHERE blob_sha512 IS NULL
+        UNION ALL
+        SELECT icon_url FROM mods WHERE icon_url IS NOT NULL
+        UNION ALL
+        SELECT image_url FROM mods_gallery
+        UNION ALL
+        SELECT avatar_url FROM users WHERE avatar_url IS NOT NULL
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    // Anything not on the CDN, such as GitHub avatars, isn't stored by us
+    let cdn_prefix = format!("{}/", cdn_url);
+    let mut known = urls
+        .into_iter()
+        .filter_map(|x| {
+            let path = x.url?.strip_prefix(&cdn_prefix)?.to_string();
+            // The file names of version files are encoded in their URLs
+            Some(
+                urlencoding::decode(&path)
+                    .map(|x| x.into_owned())
+                    .unwrap_or(path),
+            )
+        })
+        .collect::<HashSet<_>>();
+
+    let blobs = sqlx::query!(
+        "
+        SELECT file_name FROM stored_blobs
+        "
+    )
+    .fetch_all(pool)
+    .await?;
+    known.extend(blobs.into_iter().map(|x| x.file_name));
+
+    Ok(known)
+}
+
+/// Finds the stored files which aren't known and are older than `cutoff`,
+/// and the known files in the managed folders which aren't stored
+fn compare_files(
+    listed: Vec<ListedFile>,
+    known: &HashSet<String>,
+    cutoff: u64,
+) -> ReconciliationReport {
+    let stored = listed
+        .iter()
+        .map(|x| x.file_name.as_str())
+        .collect::<HashSet<_>>();
+
+    let mut missing = known
+        .iter()
+        .filter(|x| MANAGED_PREFIXES.iter().any(|prefix| x.starts_with(prefix)))
+        .filter(|x| !stored.contains(x.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+    missing.sort();
+
+    let listed_files = listed.len();
+    let orphans = listed
+        .into_iter()
+        .filter(|x| {
+            !known.contains(&x.file_name) && x.upload_timestamp < cutoff
+        })
+        .collect();
+
+    ReconciliationReport {
+        listed_files,
+        orphans,
+        missing,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compare_files;
+    use crate::file_hosting::ListedFile;
+    use std::collections::HashSet;
+
+    fn listed(file_name: &str, upload_timestamp: u64) -> ListedFile {
+        ListedFile {
+            file_id: file_name.to_string(),
+            file_name: file_name.to_string(),
+            content_length: 1,
+            upload_timestamp,
+        }
+    }
+
+    #[test]
+    fn finds_orphans_and_missing_files() {
+        let known = vec![
+            "data/AABBCCDD/versions/1.0.0/mod.jar",
+            "data/AABBCCDD/icon.png",
+            "blobs/ab/abcdef/mod.jar",
+            "elsewhere/file.txt",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect::<HashSet<_>>();
+
+        let report = compare_files(
+            vec![
+                listed("data/AABBCCDD/versions/1.0.0/mod.jar", 10),
+                listed("data/AABBCCDD/versions/0.9.0/mod.jar", 10),
+                listed("data/EEFFGGHH/versions/1.0.0/new.jar", 200),
+                listed("blobs/ab/abcdef/mod.jar", 10),
+            ],
+            &known,
+            100,
+        );
+
+        assert_eq!(report.listed_files, 4);
+        assert_eq!(
+            report
+                .orphans
+                .iter()
+                .map(|x| x.file_name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["data/AABBCCDD/versions/0.9.0/mod.jar"]
+        );
+        assert_eq!(report.missing, vec!["data/AABBCCDD/icon.png"]);
+    }
+}
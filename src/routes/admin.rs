@@ -1,21 +1,35 @@
+use crate::database::models;
+use crate::downloads::DownloadQueue;
 use crate::models::ids::ProjectId;
 use crate::routes::ApiError;
 use crate::util::guards::admin_key_guard;
+use crate::Pepper;
 use actix_web::{patch, web, HttpResponse};
 use serde::Deserialize;
 use sqlx::PgPool;
+use std::sync::Arc;
 
 #[derive(Deserialize)]
 pub struct DownloadBody {
     pub url: String,
     pub hash: ProjectId,
     pub version_name: String,
+    /// The address of the downloader. Without it, repeated downloads can't
+    /// be told apart and are all counted.
+    #[serde(default)]
+    pub ip: Option<String>,
+    /// The user agent of the downloader. Without it, downloads by bots can't
+    /// be told apart and are all counted.
+    #[serde(default)]
+    pub user_agent: Option<String>,
 }
 
 // This is an internal route, cannot be used without key
 #[patch("/_count-download", guard = "admin_key_guard")]
 pub async fn count_download(
     pool: web::Data<PgPool>,
+    pepper: web::Data<Pepper>,
+    download_queue: web::Data<Arc<DownloadQueue>>,
     download_body: web::Json<DownloadBody>,
 ) -> Result<HttpResponse, ApiError> {
     let project_id: crate::database::models::ids::ProjectId =
@@ -49,27 +63,23 @@ pub async fn count_download(
         ));
     };
 
-    let mut transaction = pool.begin().await?;
+    // The request itself comes from the CDN, so its address and user agent
+    // say nothing about the downloader
+    if let Some(user_agent) = &download_body.user_agent {
+        if download_queue.is_bot(user_agent) {
+            return Ok(HttpResponse::Ok().body(""));
+        }
+    }
 
-    sqlx::query!(
-        "UPDATE versions
-         SET downloads = downloads + 1
-         WHERE (id = $1)",
-        version_id
-    )
-    .execute(&mut *transaction)
-    .await?;
-
-    sqlx::query!(
-        "UPDATE mods
-         SET downloads = downloads + 1
-         WHERE (id = $1)",
-        project_id
-    )
-    .execute(&mut *transaction)
-    .await?;
-
-    transaction.commit().await?;
+    // Downloads are written to the database by a scheduled task
+    download_queue.add(
+        models::VersionId(version_id),
+        models::ProjectId(project_id),
+        download_body
+            .ip
+            .as_ref()
+            .map(|ip| DownloadQueue::identifier(ip, &pepper)),
+    );
 
     Ok(HttpResponse::Ok().body(""))
 }
@@ -0,0 +1,110 @@
+use crate::database;
+use crate::database::models::analytics_item::{self, GroupedDownloads};
+use crate::models::analytics::{DailyDownloads, ProjectAnalytics};
+use crate::models::projects::VersionId;
+use crate::models::teams::Permissions;
+use crate::routes::ApiError;
+use crate::util::auth::get_user_from_headers;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+#[derive(Deserialize)]
+pub struct AnalyticsQuery {
+    /// How many days to include, counting today. Defaults to 30.
+    pub days: Option<u32>,
+}
+
+fn group_downloads(
+    downloads: Vec<GroupedDownloads>,
+) -> HashMap<String, Vec<DailyDownloads>> {
+    let mut grouped: HashMap<String, Vec<DailyDownloads>> = HashMap::new();
+    for x in downloads {
+        grouped
+            .entry(x.name)
+            .or_default()
+            .push(DailyDownloads::new(x.day, x.downloads));
+    }
+    grouped
+}
+
+#[get("{id}/analytics")]
+pub async fn project_analytics_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    web::Query(query): web::Query<AnalyticsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let string = info.into_inner().0;
+
+    let project =
+        database::models::Project::get_from_slug_or_project_id(string, &**pool)
+            .await?
+            .ok_or_else(|| {
+                ApiError::InvalidInput(
+                    "The specified project does not exist!".to_string(),
+                )
+            })?;
+
+    let team_member = database::models::TeamMember::get_from_user_id(
+        project.team_id,
+        user.id.into(),
+        &**pool,
+    )
+    .await?;
+
+    let permissions = if let Some(member) = team_member {
+        member.permissions
+    } else if user.role.is_mod() {
+        Permissions::ALL
+    } else {
+        Permissions::empty()
+    };
+
+    if !permissions.contains(Permissions::VIEW_ANALYTICS) {
+        return Err(ApiError::CustomAuthentication(
+            "You do not have the permissions to view the analytics of this project!"
+                .to_string(),
+        ));
+    }
+
+    let days = query.days.unwrap_or(30).clamp(1, 365);
+    let since = OffsetDateTime::now_utc().date()
+        - time::Duration::days(i64::from(days - 1));
+
+    let mut version_downloads: HashMap<VersionId, Vec<DailyDownloads>> =
+        HashMap::new();
+    for x in analytics_item::get_version_downloads(project.id, since, &**pool)
+        .await?
+    {
+        version_downloads
+            .entry(x.version_id.into())
+            .or_default()
+            .push(DailyDownloads::new(x.day, x.downloads));
+    }
+
+    let game_version_downloads = group_downloads(
+        analytics_item::get_game_version_downloads(project.id, since, &**pool)
+            .await?,
+    );
+    let loader_downloads = group_downloads(
+        analytics_item::get_loader_downloads(project.id, since, &**pool)
+            .await?,
+    );
+    let followers = analytics_item::get_followers(project.id, since, &**pool)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(ProjectAnalytics {
+        project_id: project.id.into(),
+        version_downloads,
+        game_version_downloads,
+        loader_downloads,
+        followers,
+    }))
+}
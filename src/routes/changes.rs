@@ -0,0 +1,37 @@
+use crate::database;
+use crate::models::changes::{Change, ChangeCursor, ChangesFeed};
+use crate::routes::ApiError;
+use actix_web::{get, web, HttpResponse};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+#[derive(Deserialize)]
+pub struct ChangesQuery {
+    pub since: Option<ChangeCursor>,
+    pub limit: Option<u32>,
+}
+
+#[get("changes")]
+pub async fn changes_get(
+    web::Query(query): web::Query<ChangesQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let since = query.since.unwrap_or(ChangeCursor(0));
+    let limit = query.limit.unwrap_or(100).min(1000);
+
+    database::models::change_item::Change::assign_positions(&pool).await?;
+
+    let changes = database::models::change_item::Change::get_since(
+        since.0 as i64,
+        i64::from(limit),
+        &**pool,
+    )
+    .await?
+    .into_iter()
+    .map(Change::from)
+    .collect::<Vec<_>>();
+
+    let cursor = changes.last().map_or(since, |x| x.cursor);
+
+    Ok(HttpResponse::Ok().json(ChangesFeed { changes, cursor }))
+}
@@ -0,0 +1,18 @@
+use crate::database;
+use crate::models::dumps::Dump;
+use crate::routes::ApiError;
+use actix_web::{get, web, HttpResponse};
+use sqlx::PgPool;
+
+#[get("dumps")]
+pub async fn dumps_list(
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let dumps = database::models::dump_item::Dump::list(&**pool)
+        .await?
+        .into_iter()
+        .map(Dump::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(dumps))
+}
@@ -0,0 +1,160 @@
+use crate::file_hosting::LocalHost;
+use crate::models::projects::ProjectStatus;
+use crate::routes::ApiError;
+use crate::util::cdn::stored_file_url;
+use crate::util::range::{requested_range, RequestedRange};
+use actix_web::body::SizedStream;
+use actix_web::http::header::{ACCEPT_RANGES, CONTENT_RANGE};
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use bytes::Bytes;
+use futures::Stream;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+/// How much of a file is read at once while sending it
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// The parameters of a signed download URL
+#[derive(Deserialize)]
+pub struct Signature {
+    expires: Option<u64>,
+    signature: Option<String>,
+}
+
+/// Serves the files stored by the local file host, for setups without a CDN.
+/// Files of versions which can't be viewed by everyone, such as those of
+/// drafts or quarantined versions, can only be downloaded with a signed URL.
+/// Every other file can be downloaded like on a public CDN.
+#[get("{file_name:.*}")]
+pub async fn local_file_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    signature: web::Query<Signature>,
+    host: web::Data<Arc<LocalHost>>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let file_name = info.into_inner().0;
+
+    match &*signature {
+        Signature {
+            expires: None,
+            signature: None,
+        } => {
+            if !is_public(&file_name, &pool).await? {
+                return Ok(HttpResponse::Forbidden().body(""));
+            }
+        }
+        Signature {
+            expires: Some(expires),
+            signature: Some(signature),
+        } if host.verify_signature(&file_name, *expires, signature) => {}
+        _ => return Ok(HttpResponse::Forbidden().body("")),
+    }
+
+    let path = match host.resolve(&file_name) {
+        Ok(path) => path,
+        Err(_) => return Ok(HttpResponse::NotFound().body("")),
+    };
+    let opened = web::block(move || {
+        let file = File::open(path)?;
+        let length = file.metadata()?.len();
+
+        Ok::<_, std::io::Error>((file, length))
+    })
+    .await?;
+    let (file, length) = match opened {
+        Ok(opened) => opened,
+        Err(_) => return Ok(HttpResponse::NotFound().body("")),
+    };
+
+    // Files stored before their metadata was kept are served as plain bytes
+    let content_type = host
+        .read_metadata(&file_name)
+        .await
+        .map(|x| x.content_type)
+        .unwrap_or_else(|_| "application/octet-stream".to_string());
+
+    let (mut response, start, end) = match requested_range(&req, length) {
+        RequestedRange::Full => (HttpResponse::Ok(), 0, length),
+        RequestedRange::Partial(start, end) => {
+            let mut response = HttpResponse::PartialContent();
+            response.insert_header((
+                CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end, length),
+            ));
+            (response, start, end + 1)
+        }
+        RequestedRange::Unsatisfiable => {
+            return Ok(HttpResponse::RangeNotSatisfiable()
+                .insert_header((CONTENT_RANGE, format!("bytes */{}", length)))
+                .body(""))
+        }
+    };
+
+    Ok(response
+        .content_type(content_type)
+        .insert_header((ACCEPT_RANGES, "bytes"))
+        .body(SizedStream::new(end - start, read_file(file, start, end))))
+}
+
+/// Whether a stored file can be downloaded without a signed URL. Files which
+/// don't belong to any version, such as icons, are always public. Version
+/// files are public if at least one of the versions sharing them is.
+async fn is_public(file_name: &str, pool: &PgPool) -> Result<bool, ApiError> {
+    let cdn_url = dotenv::var("CDN_URL")?;
+
+    let versions = sqlx::query!(
+        "
+        SELECT s.status, v.quarantined FROM files f
+        INNER JOIN versions v ON v.id = f.version_id
+        INNER JOIN mods m ON m.id = v.mod_id
+        INNER JOIN statuses s ON s.id = m.status
+        WHERE f.url = $1
+        ",
+        stored_file_url(&cdn_url, file_name),
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(versions.is_empty()
+        || versions.iter().any(|x| {
+            !x.quarantined && !ProjectStatus::from_str(&x.status).is_hidden()
+        }))
+}
+
+/// Reads the bytes of a file from `start` up to `end` in chunks, without
+/// blocking the server's threads
+fn read_file(
+    file: File,
+    start: u64,
+    end: u64,
+) -> impl Stream<Item = Result<Bytes, std::io::Error>> {
+    futures::stream::try_unfold(
+        (file, start, false),
+        move |(mut file, position, seeked)| async move {
+            if position >= end {
+                return Ok(None);
+            }
+
+            let (file, chunk) = web::block(move || {
+                if !seeked {
+                    file.seek(SeekFrom::Start(position))?;
+                }
+
+                let mut chunk =
+                    vec![0; CHUNK_SIZE.min(end - position) as usize];
+                file.read_exact(&mut chunk)?;
+
+                Ok::<_, std::io::Error>((file, chunk))
+            })
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))??;
+
+            let next = position + chunk.len() as u64;
+            Ok(Some((Bytes::from(chunk), (file, next, true))))
+        },
+    )
+}
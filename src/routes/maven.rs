@@ -1,13 +1,27 @@
+use crate::database::models::categories::{GameVersion, Loader};
 use crate::database::models::project_item::QueryProject;
 use crate::database::models::version_item::{QueryFile, QueryVersion};
-use crate::models::projects::ProjectId;
+use crate::models::projects::{DependencyType, ProjectId};
 use crate::routes::ApiError;
 use crate::util::auth::get_user_from_headers;
+use crate::util::cache::CacheValidators;
+use crate::util::cdn::{file_mirrors, mirrored_redirect};
 use crate::{database, util::auth::is_authorized};
 use actix_web::{get, route, web, HttpRequest, HttpResponse};
+use sha2::Digest;
 use sqlx::PgPool;
+use std::collections::HashSet;
 use yaserde_derive::YaSerialize;
 
+const MAVEN_GROUP_ID: &str = "maven.modrinth";
+
+/// The version range of dependencies on a project rather than a version
+const ANY_VERSION: &str = "[0,)";
+
+/// Tells Gradle to look for the `.module` file next to the POM
+const GRADLE_METADATA_MARKER: &str =
+    "<!-- do_not_remove: published-with-gradle-metadata -->";
+
 #[derive(Default, Debug, Clone, YaSerialize)]
 #[yaserde(root = "metadata", rename = "metadata")]
 pub struct Metadata {
@@ -48,15 +62,118 @@ pub struct MavenPom {
     version: String,
     name: String,
     description: String,
+    dependencies: PomDependencies,
+}
+#[derive(Default, Debug, Clone, YaSerialize)]
+#[yaserde(rename = "dependencies")]
+pub struct PomDependencies {
+    #[yaserde(rename = "dependency")]
+    dependencies: Vec<PomDependency>,
+}
+#[derive(Default, Debug, Clone, YaSerialize)]
+#[yaserde(rename = "dependency")]
+pub struct PomDependency {
+    #[yaserde(rename = "groupId")]
+    group_id: String,
+    #[yaserde(rename = "artifactId")]
+    artifact_id: String,
+    version: String,
+    optional: Option<String>,
 }
 
-#[get("maven/modrinth/{id}/maven-metadata.xml")]
+/// The versions a set of coordinates is limited to. Besides the
+/// `maven.modrinth:{id}` coordinates of every version of a project, each
+/// loader has its own group, such as `maven.modrinth.fabric:{id}`, which can
+/// be limited further to a game version with its dots written as underscores,
+/// such as `maven.modrinth.fabric.1_19_2:{id}`. Routes match these as up to
+/// two path segments before the project.
+struct Scope {
+    group_id: String,
+    loader: Option<String>,
+    game_version: Option<String>,
+}
+
+impl Scope {
+    /// The scope of versions for a loader and game version. Dots aren't
+    /// allowed in the parts of group IDs, so the game version is written
+    /// with underscores instead.
+    fn new(loader: Option<&str>, game_version: Option<&str>) -> Scope {
+        let mut group_id = MAVEN_GROUP_ID.to_string();
+
+        if let Some(loader) = loader {
+            group_id = format!("{}.{}", group_id, loader);
+        }
+        if let Some(game_version) = game_version {
+            group_id =
+                format!("{}.{}", group_id, game_version.replace('.', "_"));
+        }
+
+        Scope {
+            group_id,
+            loader: loader.map(String::from),
+            game_version: game_version.map(String::from),
+        }
+    }
+
+    /// Parses the scope segments of a path, returning `None` if they don't
+    /// name a loader and game version
+    async fn parse(
+        path: &str,
+        pool: &PgPool,
+    ) -> Result<Option<Scope>, ApiError> {
+        let mut segments = path.split('/').filter(|x| !x.is_empty());
+        let loader = segments.next();
+
+        if let Some(loader) = loader {
+            if Loader::get_id(loader, pool).await?.is_none() {
+                return Ok(None);
+            }
+        }
+
+        let game_version = match segments.next() {
+            Some(segment) => {
+                match GameVersion::get_name_from_underscored(segment, pool)
+                    .await?
+                {
+                    Some(game_version) => Some(game_version),
+                    None => return Ok(None),
+                }
+            }
+            None => None,
+        };
+
+        Ok(Some(Scope::new(loader, game_version.as_deref())))
+    }
+
+    fn is_scoped(&self) -> bool {
+        self.loader.is_some() || self.game_version.is_some()
+    }
+
+    fn contains(&self, version: &QueryVersion) -> bool {
+        self.loader
+            .as_ref()
+            .map_or(true, |x| version.loaders.contains(x))
+            && self
+                .game_version
+                .as_ref()
+                .map_or(true, |x| version.game_versions.contains(x))
+    }
+}
+
+#[get("maven/modrinth/{scope:(?:[^/]+/)?(?:[^/]+/)?}{id}/maven-metadata.xml")]
 pub async fn maven_metadata(
     req: HttpRequest,
-    params: web::Path<(String,)>,
+    params: web::Path<(String, String)>,
     pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, ApiError> {
-    let project_id = params.into_inner().0;
+    let (scope, project_id) = params.into_inner();
+
+    let scope = if let Some(scope) = Scope::parse(&scope, &pool).await? {
+        scope
+    } else {
+        return Ok(HttpResponse::NotFound().body(""));
+    };
+
     let project_data =
         database::models::Project::get_full_from_slug_or_project_id(
             &*project_id,
@@ -76,274 +193,804 @@ pub async fn maven_metadata(
         return Ok(HttpResponse::NotFound().body(""));
     }
 
-    let version_names = sqlx::query!(
-        "
+    let version_names = if scope.is_scoped() {
+        let version_ids = database::models::Version::get_project_versions(
+            data.inner.id,
+            scope.game_version.clone().map(|x| vec![x]),
+            scope.loader.clone().map(|x| vec![x]),
+            &**pool,
+        )
+        .await?;
+
+        // Other loaders may still have versions for the project
+        if version_ids.is_empty() {
+            return Ok(HttpResponse::NotFound().body(""));
+        }
+
+        database::models::Version::get_many(version_ids, &**pool)
+            .await?
+            .into_iter()
+            .map(|x| (x.version_number, x.version_type))
+            .collect::<Vec<_>>()
+    } else {
+        sqlx::query!(
+            "
             SELECT version_number, version_type
             FROM versions
-            WHERE mod_id = $1
+            WHERE mod_id = $1 AND NOT quarantined
+            ORDER BY date_published ASC
             ",
-        data.inner.id as database::models::ids::ProjectId
-    )
-    .fetch_all(&**pool)
-    .await?;
+            data.inner.id as database::models::ids::ProjectId
+        )
+        .fetch_all(&**pool)
+        .await?
+        .into_iter()
+        .map(|x| (x.version_number, x.version_type))
+        .collect::<Vec<_>>()
+    };
 
     let project_id: ProjectId = data.inner.id.into();
+    let public = !data.status.is_hidden();
 
     let respdata = Metadata {
-        group_id: "maven.modrinth".to_string(),
+        group_id: scope.group_id,
         artifact_id: format!("{}", project_id),
         versioning: Versioning {
             latest: version_names
                 .last()
-                .map_or("release", |x| &x.version_number)
+                .map_or("release", |(version_number, _)| version_number)
                 .to_string(),
             release: version_names
                 .iter()
-                .rfind(|x| x.version_type == "release")
-                .map_or("", |x| &x.version_number)
+                .rfind(|(_, version_type)| version_type == "release")
+                .map_or("", |(version_number, _)| version_number)
                 .to_string(),
             versions: Versions {
                 versions: version_names
                     .iter()
-                    .map(|x| x.version_number.clone())
+                    .map(|(version_number, _)| version_number.clone())
                     .collect::<Vec<_>>(),
             },
             last_updated: data.inner.updated.format("%Y%m%d%H%M%S"),
         },
     };
 
-    Ok(HttpResponse::Ok()
-        .content_type("text/xml")
-        .body(yaserde::ser::to_string(&respdata).map_err(ApiError::Xml)?))
+    let body = yaserde::ser::to_string(&respdata).map_err(ApiError::Xml)?;
+
+    // Deleting or editing versions changes the metadata without touching
+    // `updated`, so only the ETag is used
+    Ok(CacheValidators::new(body.as_bytes(), None, public).respond(
+        &req,
+        "text/xml",
+        body.into_bytes(),
+    ))
 }
 
-fn find_file<'a>(
-    project_id: &str,
+/// A dependency of a version on another project on this repository
+struct MavenDependency {
+    artifact_id: String,
+    version: String,
+    optional: bool,
+}
+
+/// A file of a version, named the way Maven and Gradle request it
+struct ArtifactFile<'a> {
+    name: String,
+    classifier: Option<String>,
+    file: &'a QueryFile,
+}
+
+/// Something which can be requested from the directory of a version
+enum Artifact<'a> {
+    Pom,
+    Module,
+    File(&'a QueryFile),
+}
+
+fn split_extension(file_name: &str) -> Option<(&str, &str)> {
+    file_name
+        .rsplit_once('.')
+        .filter(|(stem, _)| !stem.is_empty())
+}
+
+/// Derives the classifier of a secondary file from its name: what follows
+/// the primary file's name, such as `sources` for `mod-1.0-sources.jar`
+/// next to `mod-1.0.jar`, or else the last part of its name
+fn classifier_of(primary_stem: Option<&str>, stem: &str) -> String {
+    let suffix = primary_stem
+        .and_then(|primary| stem.strip_prefix(primary))
+        .and_then(|x| x.strip_prefix('-'))
+        .unwrap_or_else(|| stem.rsplit('-').next().unwrap_or(stem));
+
+    suffix
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string()
+}
+
+/// Names the files of a version after the artifact. The primary file has no
+/// classifier, and secondary files with the same classifier as an earlier
+/// file are left out.
+fn artifact_files<'a>(
+    artifact_id: &str,
     project: &QueryProject,
     version: &'a QueryVersion,
-    file: &str,
-) -> Option<&'a QueryFile> {
-    if let Some(selected_file) =
-        version.files.iter().find(|x| x.filename == file)
-    {
-        return Some(selected_file);
-    }
-
+) -> Vec<ArtifactFile<'a>> {
     let fileext = match project.project_type.as_str() {
         "mod" => "jar",
         "modpack" => "mrpack",
-        _ => return None,
+        "resourcepack" | "datapack" | "shader" => "zip",
+        _ => return Vec::new(),
     };
 
-    if file
-        == format!("{}-{}.{}", &project_id, &version.version_number, fileext)
+    let primary = match version
+        .files
+        .iter()
+        .find(|x| x.primary)
+        .or_else(|| version.files.iter().last())
     {
-        version
-            .files
-            .iter()
-            .find(|x| x.primary)
-            .or_else(|| version.files.iter().last())
-    } else {
-        None
+        Some(primary) => primary,
+        None => return Vec::new(),
+    };
+
+    let mut artifacts = vec![ArtifactFile {
+        name: format!("{}-{}.{}", artifact_id, version.version_number, fileext),
+        classifier: None,
+        file: primary,
+    }];
+
+    let primary_stem = split_extension(&primary.filename).map(|x| x.0);
+    let mut classifiers = HashSet::new();
+    for file in version.files.iter().filter(|x| x.id != primary.id) {
+        let (stem, extension) = match split_extension(&file.filename) {
+            Some(parts) => parts,
+            None => continue,
+        };
+
+        let classifier = classifier_of(primary_stem, stem);
+        if classifier.is_empty() || !classifiers.insert(classifier.clone()) {
+            continue;
+        }
+
+        artifacts.push(ArtifactFile {
+            name: format!(
+                "{}-{}-{}.{}",
+                artifact_id, version.version_number, classifier, extension
+            ),
+            classifier: Some(classifier),
+            file,
+        });
     }
+
+    artifacts
 }
 
-#[route(
-    "maven/modrinth/{id}/{versionnum}/{file}",
-    method = "GET",
-    method = "HEAD"
-)]
-pub async fn version_file(
-    req: HttpRequest,
-    params: web::Path<(String, String, String)>,
-    pool: web::Data<PgPool>,
-) -> Result<HttpResponse, ApiError> {
-    let (project_id, vnum, file) = params.into_inner();
+fn find_artifact<'a>(
+    artifact_id: &str,
+    project: &QueryProject,
+    version: &'a QueryVersion,
+    file: &str,
+) -> Option<Artifact<'a>> {
+    if let Some(selected_file) =
+        version.files.iter().find(|x| x.filename == file)
+    {
+        return Some(Artifact::File(selected_file));
+    }
+
+    let base_name = format!("{}-{}", artifact_id, version.version_number);
+    if file == format!("{}.pom", base_name) {
+        return Some(Artifact::Pom);
+    }
+    if file == format!("{}.module", base_name) {
+        return Some(Artifact::Module);
+    }
+
+    artifact_files(artifact_id, project, version)
+        .into_iter()
+        .find(|x| x.name == file)
+        .map(|x| Artifact::File(x.file))
+}
+
+/// Gets a project and one of its versions in a scope, if the user may see
+/// them. Versions of different loaders can have the same version number, in
+/// which case the latest one is used.
+async fn get_project_version(
+    req: &HttpRequest,
+    scope: &Scope,
+    project_id: &str,
+    version_number: &str,
+    pool: &web::Data<PgPool>,
+) -> Result<Option<(QueryProject, QueryVersion)>, ApiError> {
     let project_data =
         database::models::Project::get_full_from_slug_or_project_id(
-            &project_id,
-            &**pool,
+            project_id, &***pool,
         )
         .await?;
 
     let project = if let Some(data) = project_data {
         data
     } else {
-        return Ok(HttpResponse::NotFound().body(""));
+        return Ok(None);
     };
 
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
+    let user_option = get_user_from_headers(req.headers(), &***pool).await.ok();
 
-    if !is_authorized(&project, &user_option, &pool).await? {
-        return Ok(HttpResponse::NotFound().body(""));
+    if !is_authorized(&project, &user_option, pool).await? {
+        return Ok(None);
     }
 
-    let vid = if let Some(vid) = sqlx::query!(
-        "SELECT id FROM versions WHERE mod_id = $1 AND version_number = $2",
+    let version_ids = sqlx::query!(
+        "
+        SELECT id FROM versions
+        WHERE mod_id = $1 AND version_number = $2 AND NOT quarantined
+        ORDER BY date_published DESC
+        ",
         project.inner.id as database::models::ids::ProjectId,
-        vnum
+        version_number
     )
-    .fetch_optional(&**pool)
+    .fetch_all(&***pool)
     .await?
-    {
-        vid
-    } else {
-        return Ok(HttpResponse::NotFound().body(""));
-    };
+    .into_iter()
+    .map(|x| database::models::ids::VersionId(x.id))
+    .collect();
+
+    let version =
+        database::models::Version::get_many_full(version_ids, &***pool)
+            .await?
+            .into_iter()
+            .find(|x| scope.contains(x));
 
-    let version = if let Some(version) = database::models::Version::get_full(
-        database::models::ids::VersionId(vid.id),
-        &**pool,
+    Ok(version.map(|version| (project, version)))
+}
+
+/// Resolves the required and optional dependencies of a version on other
+/// projects. Dependencies on a project rather than a version accept any of
+/// its versions.
+async fn get_dependencies(
+    version: &QueryVersion,
+    pool: &PgPool,
+) -> Result<Vec<MavenDependency>, ApiError> {
+    let version_ids = version
+        .dependencies
+        .iter()
+        .filter_map(|x| x.version_id.map(|x| x.0))
+        .collect::<Vec<_>>();
+
+    let versions = sqlx::query!(
+        "
+        SELECT id, mod_id, version_number FROM versions
+        WHERE id = ANY($1) AND NOT quarantined
+        ",
+        &version_ids,
     )
-    .await?
-    {
-        version
-    } else {
-        return Ok(HttpResponse::NotFound().body(""));
-    };
+    .fetch_all(pool)
+    .await?;
 
-    if file == format!("{}-{}.pom", &project_id, &version.version_number) {
-        let respdata = MavenPom {
-            schema_location:
-                "http://maven.apache.org/POM/4.0.0 http://maven.apache.org/xsd/maven-4.0.0.xsd"
-                    .to_string(),
-            xsi: "http://www.w3.org/2001/XMLSchema-instance".to_string(),
-            model_version: "4.0.0".to_string(),
-            group_id: "maven.modrinth".to_string(),
-            artifact_id: project_id,
-            version: version.version_number,
-            name: project.inner.title,
-            description: project.inner.description,
-        };
-        return Ok(HttpResponse::Ok()
-            .content_type("text/xml")
-            .body(yaserde::ser::to_string(&respdata).map_err(ApiError::Xml)?));
-    } else if let Some(selected_file) =
-        find_file(&project_id, &project, &version, &file)
-    {
-        return Ok(HttpResponse::TemporaryRedirect()
-            .append_header(("location", &*selected_file.url))
-            .body(""));
+    let mut dependencies = Vec::new();
+    for dependency in &version.dependencies {
+        let optional =
+            match DependencyType::from_str(&dependency.dependency_type) {
+                DependencyType::Required => false,
+                DependencyType::Optional => true,
+                DependencyType::Incompatible => continue,
+            };
+
+        let (project_id, version_number) =
+            if let Some(version_id) = dependency.version_id {
+                match versions.iter().find(|x| x.id == version_id.0) {
+                    Some(x) => (
+                        database::models::ids::ProjectId(x.mod_id),
+                        x.version_number.clone(),
+                    ),
+                    None => continue,
+                }
+            } else if let Some(project_id) = dependency.project_id {
+                (project_id, ANY_VERSION.to_string())
+            } else {
+                continue;
+            };
+
+        dependencies.push(MavenDependency {
+            artifact_id: ProjectId::from(project_id).to_string(),
+            version: version_number,
+            optional,
+        });
     }
 
-    Ok(HttpResponse::NotFound().body(""))
+    Ok(dependencies)
 }
 
-#[get("maven/modrinth/{id}/{versionnum}/{file}.sha1")]
-pub async fn version_file_sha1(
-    req: HttpRequest,
-    params: web::Path<(String, String, String)>,
-    pool: web::Data<PgPool>,
-) -> Result<HttpResponse, ApiError> {
-    let (project_id, vnum, file) = params.into_inner();
-    let project_data =
-        database::models::Project::get_full_from_slug_or_project_id(
-            &project_id,
-            &**pool,
-        )
-        .await?;
+async fn pom(
+    group_id: &str,
+    artifact_id: &str,
+    project: &QueryProject,
+    version: &QueryVersion,
+    pool: &PgPool,
+) -> Result<String, ApiError> {
+    let dependencies = get_dependencies(version, pool).await?;
+
+    let respdata = MavenPom {
+        schema_location:
+            "http://maven.apache.org/POM/4.0.0 http://maven.apache.org/xsd/maven-4.0.0.xsd"
+                .to_string(),
+        xsi: "http://www.w3.org/2001/XMLSchema-instance".to_string(),
+        model_version: "4.0.0".to_string(),
+        group_id: group_id.to_string(),
+        artifact_id: artifact_id.to_string(),
+        version: version.version_number.clone(),
+        name: project.inner.title.clone(),
+        description: project.inner.description.clone(),
+        dependencies: PomDependencies {
+            dependencies: dependencies
+                .into_iter()
+                .map(|x| PomDependency {
+                    group_id: group_id.to_string(),
+                    artifact_id: x.artifact_id,
+                    version: x.version,
+                    optional: if x.optional {
+                        Some("true".to_string())
+                    } else {
+                        None
+                    },
+                })
+                .collect(),
+        },
+    };
 
-    let project = if let Some(data) = project_data {
-        data
+    let body = yaserde::ser::to_string(&respdata).map_err(ApiError::Xml)?;
+
+    // yaserde can't write comments, so the marker is added afterwards
+    Ok(body.replacen(
+        "<modelVersion>",
+        &format!("{}<modelVersion>", GRADLE_METADATA_MARKER),
+        1,
+    ))
+}
+
+fn module_file(artifact: &ArtifactFile) -> serde_json::Value {
+    let hash = |algorithm: &str| {
+        artifact
+            .file
+            .hashes
+            .get(algorithm)
+            .and_then(|x| std::str::from_utf8(x).ok())
+    };
+
+    serde_json::json!({
+        "name": artifact.name,
+        "url": artifact.name,
+        "size": artifact.file.size,
+        "sha1": hash("sha1"),
+        "sha512": hash("sha512"),
+    })
+}
+
+/// Writes the Gradle module metadata of a version. The primary file is
+/// published as a library, and files with the `sources` and `javadoc`
+/// classifiers as its documentation. Other classifiers can only be requested
+/// explicitly, since their variants couldn't be told apart.
+async fn gradle_module(
+    group_id: &str,
+    artifact_id: &str,
+    project: &QueryProject,
+    version: &QueryVersion,
+    pool: &PgPool,
+) -> Result<String, ApiError> {
+    let artifacts = artifact_files(artifact_id, project, version);
+
+    // Gradle has no optional dependencies
+    let dependencies = get_dependencies(version, pool)
+        .await?
+        .into_iter()
+        .filter(|x| !x.optional)
+        .map(|x| {
+            serde_json::json!({
+                "group": group_id,
+                "module": x.artifact_id,
+                "version": { "requires": x.version },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut variants = Vec::new();
+    for artifact in &artifacts {
+        match artifact.classifier.as_deref() {
+            None => {
+                for (name, usage) in &[
+                    ("apiElements", "java-api"),
+                    ("runtimeElements", "java-runtime"),
+                ] {
+                    variants.push(serde_json::json!({
+                        "name": name,
+                        "attributes": {
+                            "org.gradle.category": "library",
+                            "org.gradle.dependency.bundling": "external",
+                            "org.gradle.libraryelements": "jar",
+                            "org.gradle.usage": usage,
+                        },
+                        "dependencies": dependencies,
+                        "files": [module_file(artifact)],
+                    }));
+                }
+            }
+            Some(classifier @ "sources") | Some(classifier @ "javadoc") => {
+                variants.push(serde_json::json!({
+                    "name": format!("{}Elements", classifier),
+                    "attributes": {
+                        "org.gradle.category": "documentation",
+                        "org.gradle.dependency.bundling": "external",
+                        "org.gradle.docstype": classifier,
+                        "org.gradle.usage": "java-runtime",
+                    },
+                    "files": [module_file(artifact)],
+                }));
+            }
+            Some(_) => {}
+        }
+    }
+
+    let status = if version.version_type == "release" {
+        "release"
     } else {
-        return Ok(HttpResponse::NotFound().body(""));
+        "integration"
     };
 
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
+    Ok(serde_json::json!({
+        "formatVersion": "1.1",
+        "component": {
+            "group": group_id,
+            "module": artifact_id,
+            "version": version.version_number,
+            "attributes": {
+                "org.gradle.status": status,
+            },
+        },
+        "variants": variants,
+    })
+    .to_string())
+}
 
-    if !is_authorized(&project, &user_option, &pool).await? {
-        return Ok(HttpResponse::NotFound().body(""));
+fn hash_bytes(algorithm: &str, data: &[u8]) -> Option<String> {
+    match algorithm {
+        "md5" => Some(format!("{:x}", md5::Md5::digest(data))),
+        "sha1" => Some(sha1::Sha1::from(data).hexdigest()),
+        "sha256" => Some(format!("{:x}", sha2::Sha256::digest(data))),
+        "sha512" => Some(format!("{:x}", sha2::Sha512::digest(data))),
+        _ => None,
     }
+}
 
-    let vid = if let Some(vid) = sqlx::query!(
-        "SELECT id FROM versions WHERE mod_id = $1 AND version_number = $2",
-        project.inner.id as database::models::ids::ProjectId,
-        vnum
-    )
-    .fetch_optional(&**pool)
-    .await?
-    {
-        vid
+/// Gets a hash of a version file. Hashes which weren't computed on upload
+/// are filled in by `schedule_hash_backfill`, so until then there is none.
+fn file_hash(file: &QueryFile, algorithm: &str) -> Option<String> {
+    file.hashes
+        .get(algorithm)
+        .and_then(|hash| std::str::from_utf8(hash).ok())
+        .map(String::from)
+}
+
+#[route(
+    "maven/modrinth/{scope:(?:[^/]+/)?(?:[^/]+/)?}{id}/{versionnum}/{file}",
+    method = "GET",
+    method = "HEAD"
+)]
+pub async fn version_file(
+    req: HttpRequest,
+    params: web::Path<(String, String, String, String)>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let (scope, project_id, vnum, file) = params.into_inner();
+
+    let scope = if let Some(scope) = Scope::parse(&scope, &pool).await? {
+        scope
     } else {
         return Ok(HttpResponse::NotFound().body(""));
     };
 
-    let version = if let Some(version) = database::models::Version::get_full(
-        database::models::ids::VersionId(vid.id),
-        &**pool,
-    )
-    .await?
+    let (project, version) = if let Some(data) =
+        get_project_version(&req, &scope, &project_id, &vnum, &pool).await?
     {
-        version
+        data
     } else {
         return Ok(HttpResponse::NotFound().body(""));
     };
 
-    Ok(find_file(&project_id, &project, &version, &file)
-        .and_then(|file| file.hashes.get("sha1"))
-        .and_then(|hash_bytes| std::str::from_utf8(hash_bytes).ok())
-        .map_or_else(
-            || HttpResponse::NotFound().body(""),
-            |hash_str| HttpResponse::Ok().body(hash_str.to_string()),
-        ))
+    match find_artifact(&project_id, &project, &version, &file) {
+        Some(Artifact::Pom) => {
+            Ok(HttpResponse::Ok().content_type("text/xml").body(
+                pom(&scope.group_id, &project_id, &project, &version, &pool)
+                    .await?,
+            ))
+        }
+        Some(Artifact::Module) => {
+            Ok(HttpResponse::Ok().content_type("application/json").body(
+                gradle_module(
+                    &scope.group_id,
+                    &project_id,
+                    &project,
+                    &version,
+                    &pool,
+                )
+                .await?,
+            ))
+        }
+        Some(Artifact::File(selected_file)) => {
+            let mirrors = file_mirrors(&selected_file.url, &**pool).await?;
+
+            Ok(mirrored_redirect(&mirrors).body(""))
+        }
+        None => Ok(HttpResponse::NotFound().body("")),
+    }
 }
 
-#[get("maven/modrinth/{id}/{versionnum}/{file}.sha512")]
-pub async fn version_file_sha512(
+#[get("maven/modrinth/{scope:(?:[^/]+/)?(?:[^/]+/)?}{id}/{versionnum}/{file}.{algorithm:md5|sha1|sha256|sha512}")]
+pub async fn version_file_checksum(
     req: HttpRequest,
-    params: web::Path<(String, String, String)>,
+    params: web::Path<(String, String, String, String, String)>,
     pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, ApiError> {
-    let (project_id, vnum, file) = params.into_inner();
-    let project_data =
-        database::models::Project::get_full_from_slug_or_project_id(
-            &project_id,
-            &**pool,
-        )
-        .await?;
+    let (scope, project_id, vnum, file, algorithm) = params.into_inner();
 
-    let project = if let Some(data) = project_data {
-        data
+    let scope = if let Some(scope) = Scope::parse(&scope, &pool).await? {
+        scope
     } else {
         return Ok(HttpResponse::NotFound().body(""));
     };
 
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
-
-    if !is_authorized(&project, &user_option, &pool).await? {
-        return Ok(HttpResponse::NotFound().body(""));
-    }
-
-    let vid = if let Some(vid) = sqlx::query!(
-        "SELECT id FROM versions WHERE mod_id = $1 AND version_number = $2",
-        project.inner.id as database::models::ids::ProjectId,
-        vnum
-    )
-    .fetch_optional(&**pool)
-    .await?
+    let (project, version) = if let Some(data) =
+        get_project_version(&req, &scope, &project_id, &vnum, &pool).await?
     {
-        vid
+        data
     } else {
         return Ok(HttpResponse::NotFound().body(""));
     };
 
-    let version = if let Some(version) = database::models::Version::get_full(
-        database::models::ids::VersionId(vid.id),
-        &**pool,
-    )
-    .await?
-    {
-        version
-    } else {
-        return Ok(HttpResponse::NotFound().body(""));
+    let hash = match find_artifact(&project_id, &project, &version, &file) {
+        Some(Artifact::Pom) => hash_bytes(
+            &algorithm,
+            pom(&scope.group_id, &project_id, &project, &version, &pool)
+                .await?
+                .as_bytes(),
+        ),
+        Some(Artifact::Module) => hash_bytes(
+            &algorithm,
+            gradle_module(
+                &scope.group_id,
+                &project_id,
+                &project,
+                &version,
+                &pool,
+            )
+            .await?
+            .as_bytes(),
+        ),
+        Some(Artifact::File(selected_file)) => {
+            file_hash(selected_file, &algorithm)
+        }
+        None => None,
     };
 
-    let resp = find_file(&project_id, &project, &version, &file)
-        .and_then(|file| file.hashes.get("sha512"))
-        .and_then(|hash_bytes| std::str::from_utf8(hash_bytes).ok())
-        .map_or_else(
-            || HttpResponse::NotFound().body(""),
-            |hash_str| HttpResponse::Ok().body(hash_str.to_string()),
+    Ok(hash.map_or_else(
+        || HttpResponse::NotFound().body(""),
+        |hash| HttpResponse::Ok().body(hash),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classifier_of, split_extension, Scope};
+    use crate::database::models::ids::{ProjectId, UserId, VersionId};
+    use crate::database::models::version_item::QueryVersion;
+    use actix_web::dev::{Path, ResourceDef};
+    use time::OffsetDateTime;
+
+    /// The patterns of the Maven routes, in the order `maven_config`
+    /// registers them
+    const ROUTES: [(&str, &str); 3] = [
+        (
+            "maven_metadata",
+            "/maven/modrinth/{scope:(?:[^/]+/)?(?:[^/]+/)?}{id}/maven-metadata.xml",
+        ),
+        (
+            "version_file_checksum",
+            "/maven/modrinth/{scope:(?:[^/]+/)?(?:[^/]+/)?}{id}/{versionnum}/{file}.{algorithm:md5|sha1|sha256|sha512}",
+        ),
+        (
+            "version_file",
+            "/maven/modrinth/{scope:(?:[^/]+/)?(?:[^/]+/)?}{id}/{versionnum}/{file}",
+        ),
+    ];
+
+    /// Gets the route a path is handled by, along with its parameters
+    fn route(path: &str) -> Option<(&'static str, Vec<(String, String)>)> {
+        ROUTES.iter().find_map(|(name, pattern)| {
+            let mut path = Path::new(path);
+            if ResourceDef::new(*pattern).capture_match_info(&mut path) {
+                Some((
+                    *name,
+                    path.iter()
+                        .map(|(key, value)| {
+                            (key.to_string(), value.to_string())
+                        })
+                        .collect(),
+                ))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn params(params: &[(&str, &str)]) -> Vec<(String, String)> {
+        params
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn version(loaders: &[&str], game_versions: &[&str]) -> QueryVersion {
+        QueryVersion {
+            id: VersionId(1),
+            project_id: ProjectId(1),
+            author_id: UserId(1),
+            name: "Example".to_string(),
+            version_number: "1.0.0".to_string(),
+            changelog: String::new(),
+            changelog_url: None,
+            date_published: OffsetDateTime::now_utc(),
+            downloads: 0,
+            version_type: "release".to_string(),
+            files: Vec::new(),
+            game_versions: game_versions
+                .iter()
+                .map(|x| x.to_string())
+                .collect(),
+            loaders: loaders.iter().map(|x| x.to_string()).collect(),
+            featured: false,
+            dependencies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn classifiers() {
+        assert_eq!(
+            classifier_of(Some("mod-1.0"), "mod-1.0-sources"),
+            "sources"
+        );
+        assert_eq!(
+            classifier_of(Some("mod-1.0"), "mod-1.0-dev-shaded"),
+            "dev-shaded"
         );
-    Ok(resp)
+        assert_eq!(classifier_of(Some("mod-1.0"), "other-api"), "api");
+        assert_eq!(classifier_of(None, "Mod Dev"), "mod-dev");
+    }
+
+    #[test]
+    fn extensions() {
+        assert_eq!(split_extension("mod-1.0.jar"), Some(("mod-1.0", "jar")));
+        assert_eq!(split_extension(".jar"), None);
+        assert_eq!(split_extension("mod"), None);
+    }
+
+    #[test]
+    fn scopes() {
+        let scope = Scope::new(None, None);
+        assert_eq!(scope.group_id, "maven.modrinth");
+        assert!(!scope.is_scoped());
+
+        let scope = Scope::new(Some("fabric"), None);
+        assert_eq!(scope.group_id, "maven.modrinth.fabric");
+        assert!(scope.is_scoped());
+
+        let scope = Scope::new(Some("fabric"), Some("1.18.2"));
+        assert_eq!(scope.group_id, "maven.modrinth.fabric.1_18_2");
+        assert_eq!(scope.game_version.as_deref(), Some("1.18.2"));
+    }
+
+    #[test]
+    fn scopes_contain_matching_versions() {
+        let fabric = version(&["fabric", "quilt"], &["1.18.1", "1.18.2"]);
+        let forge = version(&["forge"], &["1.18.2"]);
+
+        assert!(Scope::new(None, None).contains(&fabric));
+        assert!(Scope::new(Some("fabric"), None).contains(&fabric));
+        assert!(!Scope::new(Some("fabric"), None).contains(&forge));
+        assert!(Scope::new(Some("quilt"), Some("1.18.1")).contains(&fabric));
+        assert!(!Scope::new(Some("forge"), Some("1.18.1")).contains(&forge));
+        assert!(Scope::new(Some("forge"), Some("1.18.2")).contains(&forge));
+    }
+
+    #[actix_rt::test]
+    #[ignore = "needs a migrated database at DATABASE_URL"]
+    async fn parses_scopes() {
+        let pool = sqlx::PgPool::connect(&dotenv::var("DATABASE_URL").unwrap())
+            .await
+            .unwrap();
+
+        let scope = Scope::parse("", &pool).await.unwrap().unwrap();
+        assert_eq!(scope.group_id, "maven.modrinth");
+        assert!(Scope::parse("not-a-loader/", &pool)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn metadata_routes_take_precedence() {
+        // Scoped metadata paths are valid file paths too, so they only work
+        // because the metadata route is registered first
+        assert_eq!(
+            route("/maven/modrinth/fabric/AABBCCDD/maven-metadata.xml"),
+            Some((
+                "maven_metadata",
+                params(&[("scope", "fabric/"), ("id", "AABBCCDD")])
+            ))
+        );
+        assert!(ResourceDef::new(ROUTES[2].1)
+            .is_match("/maven/modrinth/fabric/AABBCCDD/maven-metadata.xml"));
+
+        assert_eq!(
+            route("/maven/modrinth/AABBCCDD/maven-metadata.xml"),
+            Some((
+                "maven_metadata",
+                params(&[("scope", ""), ("id", "AABBCCDD")])
+            ))
+        );
+        assert_eq!(
+            route("/maven/modrinth/fabric/1_18_2/AABBCCDD/maven-metadata.xml"),
+            Some((
+                "maven_metadata",
+                params(&[("scope", "fabric/1_18_2/"), ("id", "AABBCCDD")])
+            ))
+        );
+    }
+
+    #[test]
+    fn file_routes() {
+        assert_eq!(
+            route("/maven/modrinth/AABBCCDD/1.0.0/mod-1.0.0.jar"),
+            Some((
+                "version_file",
+                params(&[
+                    ("scope", ""),
+                    ("id", "AABBCCDD"),
+                    ("versionnum", "1.0.0"),
+                    ("file", "mod-1.0.0.jar"),
+                ])
+            ))
+        );
+        assert_eq!(
+            route("/maven/modrinth/fabric/1_18_2/AABBCCDD/1.0.0/mod-1.0.0.jar"),
+            Some((
+                "version_file",
+                params(&[
+                    ("scope", "fabric/1_18_2/"),
+                    ("id", "AABBCCDD"),
+                    ("versionnum", "1.0.0"),
+                    ("file", "mod-1.0.0.jar"),
+                ])
+            ))
+        );
+        assert_eq!(
+            route("/maven/modrinth/AABBCCDD/1.0.0/mod-1.0.0.jar.sha1"),
+            Some((
+                "version_file_checksum",
+                params(&[
+                    ("scope", ""),
+                    ("id", "AABBCCDD"),
+                    ("versionnum", "1.0.0"),
+                    ("file", "mod-1.0.0.jar"),
+                    ("algorithm", "sha1"),
+                ])
+            ))
+        );
+        assert_eq!(route("/maven/modrinth/a/b/c/AABBCCDD/1.0.0/mod.jar"), None);
+    }
 }
@@ -2,19 +2,25 @@ mod v1;
 pub use v1::v1_config;
 
 mod admin;
+mod analytics;
 mod auth;
+mod changes;
+mod dumps;
 mod health;
 mod index;
+mod local_files;
 mod maven;
 mod moderation;
 mod not_found;
 mod notifications;
+mod packs;
 pub(crate) mod project_creation;
 mod projects;
 mod reports;
 mod tags;
 mod teams;
 mod updates;
+pub(crate) mod upload_sessions;
 mod users;
 mod version_creation;
 mod version_file;
@@ -41,7 +47,10 @@ pub fn v2_config(cfg: &mut web::ServiceConfig) {
             .configure(moderation_config)
             .configure(reports_config)
             .configure(notifications_config)
-            .configure(admin_config),
+            .configure(admin_config)
+            .configure(changes_config)
+            .configure(dumps_config)
+            .configure(packs_config),
     );
 }
 
@@ -62,6 +71,7 @@ pub fn projects_config(cfg: &mut web::ServiceConfig) {
             .service(projects::delete_gallery_item)
             .service(projects::project_follow)
             .service(projects::project_unfollow)
+            .service(analytics::project_analytics_get)
             .service(teams::team_members_get_project)
             .service(
                 web::scope("{project_id}")
@@ -73,11 +83,14 @@ pub fn projects_config(cfg: &mut web::ServiceConfig) {
 
 pub fn maven_config(cfg: &mut web::ServiceConfig) {
     cfg.service(maven::maven_metadata);
-    cfg.service(maven::version_file_sha512);
-    cfg.service(maven::version_file_sha1);
+    cfg.service(maven::version_file_checksum);
     cfg.service(maven::version_file);
 }
 
+pub fn local_files_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(local_files::local_file_get);
+}
+
 pub fn updates(cfg: &mut web::ServiceConfig) {
     cfg.service(updates::forge_updates);
 }
@@ -90,13 +103,22 @@ pub fn versions_config(cfg: &mut web::ServiceConfig) {
             .service(versions::version_get)
             .service(versions::version_delete)
             .service(version_creation::upload_file_to_version)
+            .service(upload_sessions::upload_session_create)
             .service(versions::version_edit),
     );
+    cfg.service(
+        web::scope("upload_session")
+            .service(upload_sessions::upload_session_get)
+            .service(upload_sessions::upload_session_chunk)
+            .service(upload_sessions::upload_session_finalize)
+            .service(upload_sessions::upload_session_delete),
+    );
     cfg.service(
         web::scope("version_file")
             .service(version_file::delete_file)
             .service(version_file::get_version_from_hash)
             .service(version_file::download_version)
+            .service(version_file::get_bundling_files)
             .service(version_file::get_update_from_hash),
     );
 
@@ -148,7 +170,13 @@ pub fn notifications_config(cfg: &mut web::ServiceConfig) {
 }
 
 pub fn moderation_config(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::scope("moderation").service(moderation::get_projects));
+    cfg.service(
+        web::scope("moderation")
+            .service(moderation::get_projects)
+            .service(moderation::get_flagged_files)
+            .service(moderation::get_quarantined_versions)
+            .service(moderation::release_quarantined_version),
+    );
 }
 
 pub fn reports_config(cfg: &mut web::ServiceConfig) {
@@ -157,6 +185,22 @@ pub fn reports_config(cfg: &mut web::ServiceConfig) {
     cfg.service(reports::delete_report);
 }
 
+pub fn changes_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(changes::changes_get);
+}
+
+pub fn dumps_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(dumps::dumps_list);
+}
+
+pub fn packs_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("pack")
+            .service(packs::pack_index)
+            .service(packs::pack_export),
+    );
+}
+
 pub fn admin_config(cfg: &mut web::ServiceConfig) {
     cfg.service(web::scope("admin").service(admin::count_download));
 }
@@ -187,12 +231,15 @@ pub enum ApiError {
     Search(#[from] meilisearch_sdk::errors::Error),
     #[error("Indexing Error: {0}")]
     Indexing(#[from] crate::search::indexing::IndexingError),
+    #[error("Error while creating archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
 }
 
 impl actix_web::ResponseError for ApiError {
     fn status_code(&self) -> actix_web::http::StatusCode {
         match self {
             ApiError::Xml(..)
+            | ApiError::Zip(..)
             | ApiError::Search(..)
             | ApiError::Indexing(..)
             | ApiError::FileHosting(..)
@@ -224,6 +271,7 @@ impl actix_web::ResponseError for ApiError {
                     ApiError::Authentication(..)
                     | ApiError::CustomAuthentication(..) => "unauthorized",
                     ApiError::Xml(..) => "xml_error",
+                    ApiError::Zip(..) => "zip_error",
                     ApiError::Json(..) => "json_error",
                     ApiError::Search(..) => "search_error",
                     ApiError::Indexing(..) => "indexing_error",
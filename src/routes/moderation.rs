@@ -1,10 +1,13 @@
 use super::ApiError;
 use crate::database;
-use crate::models::projects::ProjectStatus;
+use crate::models::ids::{ProjectId, VersionId};
+use crate::models::projects::{
+    FindingSeverity, ProjectStatus, ValidationFinding, Version,
+};
 use crate::util::auth::check_is_moderator_from_headers;
-use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_web::{delete, get, web, HttpRequest, HttpResponse};
 use futures::TryStreamExt;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 
 #[derive(Deserialize)]
@@ -53,3 +56,195 @@ pub async fn get_projects(
 
     Ok(HttpResponse::Ok().json(projects))
 }
+
+/// A file with findings which kept it from being marked as primary
+#[derive(Serialize)]
+pub struct FlaggedFile {
+    pub project_id: ProjectId,
+    pub version_id: VersionId,
+    pub filename: String,
+    pub findings: Vec<ValidationFinding>,
+}
+
+#[get("files")]
+pub async fn get_flagged_files(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    count: web::Query<ResultCount>,
+) -> Result<HttpResponse, ApiError> {
+    check_is_moderator_from_headers(req.headers(), &**pool).await?;
+
+    let severities = vec![FindingSeverity::Warning, FindingSeverity::Error]
+        .into_iter()
+        .map(|x| x.as_str().to_string())
+        .collect::<Vec<_>>();
+
+    // The most recently flagged files come first
+    let rows = sqlx::query!(
+        "
+        SELECT f.id file_id, f.filename filename, v.id version_id, v.mod_id project_id,
+        fvf.severity severity, fvf.code code, fvf.message message, fvf.path path
+        FROM (
+            SELECT file_id, MAX(id) latest FROM file_validation_findings
+            WHERE severity = ANY($1)
+            GROUP BY file_id
+            ORDER BY latest DESC
+            LIMIT $2
+        ) flagged
+        INNER JOIN files f ON f.id = flagged.file_id
+        INNER JOIN versions v ON v.id = f.version_id
+        INNER JOIN file_validation_findings fvf ON fvf.file_id = f.id
+        ORDER BY flagged.latest DESC, fvf.id ASC
+        ",
+        &severities,
+        i64::from(count.count)
+    )
+    .fetch_all(&**pool)
+    .await?;
+
+    let mut files: Vec<(i64, FlaggedFile)> = Vec::new();
+
+    for row in rows {
+        let finding = ValidationFinding {
+            severity: FindingSeverity::from_str(&row.severity),
+            code: row.code,
+            message: row.message,
+            path: row.path,
+        };
+
+        match files.last_mut() {
+            Some((file_id, file)) if *file_id == row.file_id => {
+                file.findings.push(finding)
+            }
+            _ => files.push((
+                row.file_id,
+                FlaggedFile {
+                    project_id: database::models::ProjectId(row.project_id)
+                        .into(),
+                    version_id: database::models::VersionId(row.version_id)
+                        .into(),
+                    filename: row.filename,
+                    findings: vec![finding],
+                },
+            )),
+        }
+    }
+
+    Ok(HttpResponse::Ok()
+        .json(files.into_iter().map(|(_, x)| x).collect::<Vec<_>>()))
+}
+
+/// A quarantined version along with what the upload scanner found in it
+#[derive(Serialize)]
+pub struct QuarantinedVersion {
+    #[serde(flatten)]
+    pub version: Version,
+    pub scan_findings: Vec<ScanFinding>,
+}
+
+#[derive(Serialize)]
+pub struct ScanFinding {
+    pub filename: String,
+    #[serde(flatten)]
+    pub finding: ValidationFinding,
+}
+
+#[get("quarantine")]
+pub async fn get_quarantined_versions(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    count: web::Query<ResultCount>,
+) -> Result<HttpResponse, ApiError> {
+    check_is_moderator_from_headers(req.headers(), &**pool).await?;
+
+    let version_ids = sqlx::query!(
+        "
+        SELECT id FROM versions
+        WHERE quarantined
+        ORDER BY date_published ASC
+        LIMIT $1;
+        ",
+        i64::from(count.count)
+    )
+    .fetch_many(&**pool)
+    .try_filter_map(|e| async {
+        Ok(e.right().map(|v| database::models::VersionId(v.id)))
+    })
+    .try_collect::<Vec<database::models::VersionId>>()
+    .await?;
+
+    let version_ids_parsed =
+        version_ids.iter().map(|x| x.0).collect::<Vec<_>>();
+    let scan_findings = sqlx::query!(
+        "
+        SELECT f.version_id version_id, f.filename filename,
+        fsf.severity severity, fsf.code code, fsf.message message, fsf.path path
+        FROM file_scan_findings fsf
+        INNER JOIN files f ON f.id = fsf.file_id
+        WHERE f.version_id = ANY($1)
+        ORDER BY fsf.id ASC
+        ",
+        &version_ids_parsed,
+    )
+    .fetch_all(&**pool)
+    .await?;
+
+    let versions: Vec<_> =
+        database::Version::get_many_full(version_ids, &**pool)
+            .await?
+            .into_iter()
+            .map(|version| {
+                let id = version.id.0;
+
+                QuarantinedVersion {
+                    version: Version::from(version),
+                    scan_findings: scan_findings
+                        .iter()
+                        .filter(|x| x.version_id == id)
+                        .map(|x| ScanFinding {
+                            filename: x.filename.clone(),
+                            finding: ValidationFinding {
+                                severity: FindingSeverity::from_str(
+                                    &x.severity,
+                                ),
+                                code: x.code.clone(),
+                                message: x.message.clone(),
+                                path: x.path.clone(),
+                            },
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+    Ok(HttpResponse::Ok().json(versions))
+}
+
+/// Releases a quarantined version after a moderator reviewed its files
+#[delete("quarantine/{id}")]
+pub async fn release_quarantined_version(
+    req: HttpRequest,
+    info: web::Path<(VersionId,)>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    check_is_moderator_from_headers(req.headers(), &**pool).await?;
+
+    let id: database::models::VersionId = info.into_inner().0.into();
+
+    let result = sqlx::query!(
+        "
+        UPDATE versions
+        SET quarantined = FALSE
+        WHERE (id = $1 AND quarantined)
+        ",
+        id as database::models::VersionId,
+    )
+    .execute(&**pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        Ok(HttpResponse::NotFound().body(""))
+    } else {
+        Ok(HttpResponse::NoContent().body(""))
+    }
+}
@@ -0,0 +1,598 @@
+use super::ApiError;
+use crate::database;
+use crate::database::models::version_item::QueryVersion;
+use crate::models::ids::VersionId;
+use crate::models::pack::{
+    EnvType, PackDependency, PackFile, PackFileHash, PackFormat,
+};
+use crate::models::projects::{ProjectStatus, SideType};
+use crate::util::validate::validation_errors_to_string;
+use actix_web::http::header::{
+    ContentDisposition, DispositionParam, DispositionType,
+};
+use actix_web::{post, web, HttpResponse};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Write};
+use validator::Validate;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// The versions to build a pack from, along with the version of Minecraft
+/// and the mod loader the pack is made for
+#[derive(Deserialize, Validate)]
+pub struct PackExport {
+    #[validate(length(min = 3, max = 512))]
+    pub name: String,
+    /// The version of the pack itself
+    #[validate(length(min = 3, max = 512))]
+    pub version_number: String,
+    #[validate(length(max = 2048))]
+    pub summary: Option<String>,
+    #[validate(length(min = 1, max = 1024))]
+    pub versions: Vec<VersionId>,
+    pub dependencies: HashMap<PackDependency, String>,
+}
+
+#[post("index")]
+pub async fn pack_index(
+    pool: web::Data<PgPool>,
+    export: web::Json<PackExport>,
+) -> Result<HttpResponse, ApiError> {
+    let pack = build_pack(export.into_inner(), &pool).await?;
+
+    Ok(HttpResponse::Ok().json(pack))
+}
+
+#[post("export")]
+pub async fn pack_export(
+    pool: web::Data<PgPool>,
+    export: web::Json<PackExport>,
+) -> Result<HttpResponse, ApiError> {
+    let pack = build_pack(export.into_inner(), &pool).await?;
+    let file_name = format!("{}-{}.mrpack", pack.name, pack.version_id);
+
+    let data = write_pack(&pack)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-modrinth-modpack+zip")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file_name)],
+        })
+        .body(data))
+}
+
+/// The files of the pack are downloaded when installing it, so the pack
+/// itself only contains its index
+fn write_pack(pack: &PackFormat) -> Result<Vec<u8>, ApiError> {
+    let index = serde_json::to_vec_pretty(pack)?;
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("modrinth.index.json", FileOptions::default())?;
+    zip.write_all(&index).map_err(zip::result::ZipError::from)?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+async fn build_pack(
+    export: PackExport,
+    pool: &PgPool,
+) -> Result<PackFormat, ApiError> {
+    export.validate().map_err(|err| {
+        ApiError::Validation(validation_errors_to_string(err, None))
+    })?;
+
+    let game_version = export
+        .dependencies
+        .get(&PackDependency::Minecraft)
+        .ok_or_else(|| {
+            ApiError::InvalidInput(
+                "Packs must depend on a version of Minecraft!".to_string(),
+            )
+        })?;
+
+    if !database::models::categories::GameVersion::list(pool)
+        .await?
+        .iter()
+        .any(|x| x.version == *game_version)
+    {
+        return Err(ApiError::InvalidInput(format!(
+            "Minecraft {} does not exist!",
+            game_version
+        )));
+    }
+
+    let loaders = pack_loaders(&export.dependencies)?;
+
+    let version_ids = export
+        .versions
+        .iter()
+        .map(|x| database::models::VersionId::from(*x).0)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let statuses = vec![
+        ProjectStatus::Approved,
+        ProjectStatus::Archived,
+        ProjectStatus::Unlisted,
+    ]
+    .into_iter()
+    .map(|x| x.as_str().to_string())
+    .collect::<Vec<_>>();
+
+    let projects = sqlx::query!(
+        "
+        SELECT v.id version_id, pt.name project_type, cs.name client_side, ss.name server_side
+        FROM versions v
+        INNER JOIN mods m ON m.id = v.mod_id
+        INNER JOIN project_types pt ON pt.id = m.project_type
+        INNER JOIN statuses s ON s.id = m.status
+        INNER JOIN side_types cs ON m.client_side = cs.id
+        INNER JOIN side_types ss ON m.server_side = ss.id
+        WHERE v.id = ANY($1) AND s.status = ANY($2) AND NOT v.quarantined
+        ",
+        &version_ids,
+        &statuses,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|x| {
+        (
+            x.version_id,
+            PackProject {
+                project_type: x.project_type,
+                client_side: x.client_side,
+                server_side: x.server_side,
+            },
+        )
+    })
+    .collect::<HashMap<_, _>>();
+
+    if let Some(missing) =
+        version_ids.iter().find(|x| !projects.contains_key(x))
+    {
+        return Err(ApiError::InvalidInput(format!(
+            "Version {} does not exist!",
+            VersionId::from(database::models::VersionId(*missing))
+        )));
+    }
+
+    let versions = database::models::Version::get_many_full(
+        version_ids
+            .into_iter()
+            .map(database::models::VersionId)
+            .collect(),
+        pool,
+    )
+    .await?;
+
+    let mut files = Vec::new();
+    let mut paths = HashSet::new();
+
+    for version in versions {
+        let file = pack_file(
+            &version,
+            &projects[&version.id.0],
+            game_version,
+            loaders,
+        )?;
+
+        if !paths.insert(file.path.clone()) {
+            return Err(ApiError::InvalidInput(format!(
+                "Several versions have a file at {}!",
+                file.path
+            )));
+        }
+
+        files.push(file);
+    }
+
+    // Launchers try the other downloads of a file if the first one fails
+    let urls = files
+        .iter()
+        .flat_map(|x| x.downloads.iter().cloned())
+        .collect::<Vec<_>>();
+    let mut mirrors = crate::util::cdn::mirrored_urls(&urls, pool).await?;
+    for file in &mut files {
+        if let Some(downloads) =
+            file.downloads.first().and_then(|x| mirrors.remove(x))
+        {
+            file.downloads = downloads;
+        }
+    }
+
+    Ok(PackFormat {
+        game: "minecraft".to_string(),
+        format_version: 1,
+        version_id: export.version_number,
+        name: export.name,
+        summary: export.summary,
+        files,
+        dependencies: export.dependencies,
+    })
+}
+
+/// The project a version in a pack belongs to
+struct PackProject {
+    project_type: String,
+    client_side: String,
+    server_side: String,
+}
+
+/// The loaders of the mods in the pack, if it depends on a mod loader
+fn pack_loaders(
+    dependencies: &HashMap<PackDependency, String>,
+) -> Result<Option<&'static [&'static str]>, ApiError> {
+    let loaders = dependencies
+        .keys()
+        .filter_map(compatible_loaders)
+        .collect::<Vec<_>>();
+
+    if loaders.len() > 1 {
+        return Err(ApiError::InvalidInput(
+            "Packs can only depend on one mod loader!".to_string(),
+        ));
+    }
+
+    Ok(loaders.first().copied())
+}
+
+/// Picks the file of a version to include in a pack, checking that the
+/// version can be used with the pack
+fn pack_file(
+    version: &QueryVersion,
+    project: &PackProject,
+    game_version: &str,
+    loaders: Option<&[&str]>,
+) -> Result<PackFile, ApiError> {
+    let id = VersionId::from(version.id);
+
+    let folder = match &*project.project_type {
+        "mod" => "mods",
+        "resourcepack" => "resourcepacks",
+        "shader" => "shaderpacks",
+        project_type => {
+            return Err(ApiError::InvalidInput(format!(
+                "Version {} is of a {} project, which can't be part of a pack!",
+                id, project_type
+            )))
+        }
+    };
+
+    if !version.game_versions.iter().any(|x| x == game_version) {
+        return Err(ApiError::InvalidInput(format!(
+            "Version {} does not support Minecraft {}!",
+            id, game_version
+        )));
+    }
+
+    // Resource packs and shaders don't depend on the mod loader
+    if let (Some(loaders), "mods") = (loaders, folder) {
+        if !version.loaders.iter().any(|x| loaders.contains(&&**x)) {
+            return Err(ApiError::InvalidInput(format!(
+                "Version {} does not support the pack's mod loader!",
+                id
+            )));
+        }
+    }
+
+    let file = version
+        .files
+        .iter()
+        .find(|x| x.primary)
+        .or_else(|| version.files.first())
+        .ok_or_else(|| {
+            ApiError::InvalidInput(format!("Version {} has no files!", id))
+        })?;
+
+    // Hashes are stored as the bytes of their hex strings
+    let hashes = vec![
+        ("sha1", PackFileHash::Sha1),
+        ("sha512", PackFileHash::Sha512),
+    ]
+    .into_iter()
+    .filter_map(|(algorithm, hash)| {
+        let value = file.hashes.get(algorithm)?;
+        Some((hash, String::from_utf8(value.clone()).ok()?))
+    })
+    .collect();
+
+    Ok(PackFile {
+        path: format!("{}/{}", folder, file.filename),
+        hashes,
+        env: pack_env(&project.client_side, &project.server_side),
+        downloads: vec![file.url.clone()],
+        file_size: file.size,
+    })
+}
+
+/// The loaders of versions which can be used with a pack depending on this
+fn compatible_loaders(dependency: &PackDependency) -> Option<&'static [&str]> {
+    match dependency {
+        PackDependency::Forge => Some(&["forge"]),
+        PackDependency::FabricLoader => Some(&["fabric"]),
+        // Quilt loads most Fabric mods too
+        PackDependency::QuiltLoader => Some(&["quilt", "fabric"]),
+        PackDependency::Minecraft => None,
+    }
+}
+
+/// Files of projects without a known environment are installed everywhere
+fn pack_env(
+    client_side: &str,
+    server_side: &str,
+) -> Option<HashMap<EnvType, SideType>> {
+    let client_side = SideType::from_str(client_side);
+    let server_side = SideType::from_str(server_side);
+
+    if client_side == SideType::Unknown || server_side == SideType::Unknown {
+        return None;
+    }
+
+    let mut env = HashMap::new();
+    env.insert(EnvType::Client, client_side);
+    env.insert(EnvType::Server, server_side);
+
+    Some(env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::ids::{FileId, ProjectId, UserId};
+    use crate::database::models::version_item::QueryFile;
+    use std::io::Read;
+    use time::OffsetDateTime;
+
+    fn query_file(filename: &str, primary: bool) -> QueryFile {
+        let mut hashes = HashMap::new();
+        hashes.insert("sha1".to_string(), b"abc".to_vec());
+        hashes.insert("sha512".to_string(), b"def".to_vec());
+        hashes.insert("murmur2".to_string(), b"123".to_vec());
+
+        QueryFile {
+            id: FileId(1),
+            url: format!("https://cdn.modrinth.com/data/AABBCCDD/{}", filename),
+            filename: filename.to_string(),
+            hashes,
+            primary,
+            size: 1024,
+            findings: Vec::new(),
+            pack_summary: None,
+        }
+    }
+
+    fn version(loaders: &[&str], files: Vec<QueryFile>) -> QueryVersion {
+        QueryVersion {
+            id: database::models::ids::VersionId(1),
+            project_id: ProjectId(1),
+            author_id: UserId(1),
+            name: "Example".to_string(),
+            version_number: "1.0.0".to_string(),
+            changelog: String::new(),
+            changelog_url: None,
+            date_published: OffsetDateTime::now_utc(),
+            downloads: 0,
+            version_type: "release".to_string(),
+            files,
+            game_versions: vec!["1.18.2".to_string()],
+            loaders: loaders.iter().map(|x| x.to_string()).collect(),
+            featured: false,
+            dependencies: Vec::new(),
+        }
+    }
+
+    fn project(project_type: &str) -> PackProject {
+        PackProject {
+            project_type: project_type.to_string(),
+            client_side: "required".to_string(),
+            server_side: "optional".to_string(),
+        }
+    }
+
+    fn dependencies(
+        dependencies: &[PackDependency],
+    ) -> HashMap<PackDependency, String> {
+        dependencies
+            .iter()
+            .map(|x| (x.clone(), "1.0.0".to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn known_sides_are_mapped_to_the_environment() {
+        let env = pack_env("required", "unsupported").unwrap();
+
+        assert_eq!(env.len(), 2);
+        assert_eq!(env[&EnvType::Client], SideType::Required);
+        assert_eq!(env[&EnvType::Server], SideType::Unsupported);
+    }
+    #[test]
+    fn unknown_sides_are_installed_everywhere() {
+        assert_eq!(pack_env("unknown", "required"), None);
+        assert_eq!(pack_env("required", "something"), None);
+    }
+    #[test]
+    fn quilt_packs_can_use_fabric_mods() {
+        assert_eq!(
+            compatible_loaders(&PackDependency::QuiltLoader),
+            Some(&["quilt", "fabric"][..])
+        );
+        assert_eq!(
+            compatible_loaders(&PackDependency::FabricLoader),
+            Some(&["fabric"][..])
+        );
+        assert_eq!(compatible_loaders(&PackDependency::Minecraft), None);
+    }
+    #[test]
+    fn packs_depend_on_at_most_one_loader() {
+        let loaders = pack_loaders(&dependencies(&[
+            PackDependency::Minecraft,
+            PackDependency::Forge,
+        ]))
+        .unwrap();
+        assert_eq!(loaders, Some(&["forge"][..]));
+
+        let loaders =
+            pack_loaders(&dependencies(&[PackDependency::Minecraft])).unwrap();
+        assert_eq!(loaders, None);
+
+        assert!(matches!(
+            pack_loaders(&dependencies(&[
+                PackDependency::Forge,
+                PackDependency::FabricLoader,
+            ])),
+            Err(ApiError::InvalidInput(_))
+        ));
+    }
+    #[test]
+    fn mods_are_placed_in_the_mods_folder() {
+        let file = pack_file(
+            &version(&["fabric"], vec![query_file("example.jar", true)]),
+            &project("mod"),
+            "1.18.2",
+            Some(&["quilt", "fabric"]),
+        )
+        .unwrap();
+
+        assert_eq!(file.path, "mods/example.jar");
+        assert_eq!(
+            file.downloads,
+            vec!["https://cdn.modrinth.com/data/AABBCCDD/example.jar"]
+        );
+        assert_eq!(file.file_size, 1024);
+        assert_eq!(file.hashes.len(), 2);
+        assert_eq!(file.hashes[&PackFileHash::Sha1], "abc");
+        assert_eq!(file.hashes[&PackFileHash::Sha512], "def");
+        assert_eq!(file.env, pack_env("required", "optional"));
+    }
+    #[test]
+    fn the_primary_file_is_picked() {
+        let file = pack_file(
+            &version(
+                &["fabric"],
+                vec![
+                    query_file("sources.jar", false),
+                    query_file("example.jar", true),
+                ],
+            ),
+            &project("mod"),
+            "1.18.2",
+            None,
+        )
+        .unwrap();
+        assert_eq!(file.path, "mods/example.jar");
+
+        let file = pack_file(
+            &version(
+                &["fabric"],
+                vec![
+                    query_file("example.jar", false),
+                    query_file("sources.jar", false),
+                ],
+            ),
+            &project("mod"),
+            "1.18.2",
+            None,
+        )
+        .unwrap();
+        assert_eq!(file.path, "mods/example.jar");
+    }
+    #[test]
+    fn mods_for_other_loaders_are_rejected() {
+        assert!(matches!(
+            pack_file(
+                &version(&["forge"], vec![query_file("example.jar", true)]),
+                &project("mod"),
+                "1.18.2",
+                Some(&["fabric"]),
+            ),
+            Err(ApiError::InvalidInput(_))
+        ));
+    }
+    #[test]
+    fn resource_packs_and_shaders_ignore_the_loader() {
+        let file = pack_file(
+            &version(&["minecraft"], vec![query_file("example.zip", true)]),
+            &project("resourcepack"),
+            "1.18.2",
+            Some(&["fabric"]),
+        )
+        .unwrap();
+        assert_eq!(file.path, "resourcepacks/example.zip");
+
+        let file = pack_file(
+            &version(&["iris"], vec![query_file("example.zip", true)]),
+            &project("shader"),
+            "1.18.2",
+            Some(&["fabric"]),
+        )
+        .unwrap();
+        assert_eq!(file.path, "shaderpacks/example.zip");
+    }
+    #[test]
+    fn unsupported_versions_are_rejected() {
+        assert!(matches!(
+            pack_file(
+                &version(&["fabric"], vec![query_file("example.jar", true)]),
+                &project("mod"),
+                "1.19",
+                None,
+            ),
+            Err(ApiError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            pack_file(
+                &version(&["fabric"], vec![query_file("example.mrpack", true)]),
+                &project("modpack"),
+                "1.18.2",
+                None,
+            ),
+            Err(ApiError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            pack_file(
+                &version(&["fabric"], Vec::new()),
+                &project("mod"),
+                "1.18.2",
+                None,
+            ),
+            Err(ApiError::InvalidInput(_))
+        ));
+    }
+    #[test]
+    fn packs_only_contain_their_index() {
+        let pack = PackFormat {
+            game: "minecraft".to_string(),
+            format_version: 1,
+            version_id: "1.0.0".to_string(),
+            name: "Example".to_string(),
+            summary: None,
+            files: vec![pack_file(
+                &version(&["fabric"], vec![query_file("example.jar", true)]),
+                &project("mod"),
+                "1.18.2",
+                None,
+            )
+            .unwrap()],
+            dependencies: dependencies(&[
+                PackDependency::Minecraft,
+                PackDependency::FabricLoader,
+            ]),
+        };
+
+        let data = write_pack(&pack).unwrap();
+        let mut zip = zip::ZipArchive::new(Cursor::new(data)).unwrap();
+        assert_eq!(zip.len(), 1);
+
+        let mut index = String::new();
+        zip.by_name("modrinth.index.json")
+            .unwrap()
+            .read_to_string(&mut index)
+            .unwrap();
+        assert_eq!(serde_json::from_str::<PackFormat>(&index).unwrap(), pack);
+    }
+}
@@ -242,6 +242,7 @@ pub async fn project_create(
     let result = project_create_inner(
         req,
         payload,
+        &**client,
         &mut transaction,
         &***file_host,
         &mut uploaded_files,
@@ -297,6 +298,7 @@ Get logged in user
 pub async fn project_create_inner(
     req: HttpRequest,
     mut payload: Multipart,
+    pool: &PgPool,
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     file_host: &dyn FileHost,
     uploaded_files: &mut Vec<UploadedFile>,
@@ -549,6 +551,9 @@ pub async fn project_create_inner(
             uploaded_files,
             &mut created_version.files,
             &mut created_version.dependencies,
+            // The response of project creation is the project itself, so
+            // there's nowhere to report manifest mismatches to
+            &mut Vec::new(),
             &cdn_url,
             &content_disposition,
             project_id,
@@ -559,6 +564,7 @@ pub async fn project_create_inner(
             all_game_versions.clone(),
             version_data.primary_file.is_some(),
             version_data.primary_file.as_deref() == Some(name),
+            pool,
             transaction,
         )
         .await?;
@@ -892,3 +898,82 @@ async fn process_icon_upload(
         Err(CreateError::InvalidIconFormat(file_extension.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::undo_uploads;
+    use crate::database::models;
+    use crate::file_hosting::LocalHost;
+    use crate::models::projects::{GameVersion, Loader};
+    use crate::routes::version_creation::upload_file_data;
+    use crate::util::spool::SpoolWriter;
+    use crate::validate::test_util::make_dummy_zip_bytes;
+    use time::OffsetDateTime;
+
+    /// Project creation uploads the files of the initial versions before the
+    /// project is inserted, so reading their manifests mustn't depend on it
+    #[actix_rt::test]
+    #[ignore = "needs a migrated database at DATABASE_URL"]
+    async fn uploads_mod_files_of_new_projects() {
+        let pool = sqlx::PgPool::connect(&dotenv::var("DATABASE_URL").unwrap())
+            .await
+            .unwrap();
+        let mut transaction = pool.begin().await.unwrap();
+        let root = std::env::temp_dir()
+            .join(format!("labrinth-test-{:016x}", rand::random::<u64>()));
+        let file_host = LocalHost::new(root.clone(), "http://localhost/cdn");
+
+        let jar = make_dummy_zip_bytes(&[
+            (
+                "fabric.mod.json",
+                br#"{"schemaVersion": 1, "id": "examplemod", "version": "1.0.0"}"#,
+            ),
+            ("com/example/ExampleMod.class", b""),
+        ])
+        .unwrap();
+        let mut spool = SpoolWriter::new().await.unwrap();
+        spool.write(jar.into()).await.unwrap();
+
+        let project_id =
+            models::generate_project_id(&mut transaction).await.unwrap();
+        let mut uploaded_files = Vec::new();
+        let mut version_files = Vec::new();
+
+        let result = upload_file_data(
+            spool.finish().unwrap(),
+            "examplemod-1.0.0.jar",
+            "jar",
+            &file_host,
+            &mut uploaded_files,
+            &mut version_files,
+            &mut Vec::new(),
+            &mut Vec::new(),
+            "http://localhost/cdn",
+            project_id.into(),
+            "1.0.0",
+            "mod",
+            vec![Loader("fabric".to_string())],
+            vec![GameVersion("1.19".to_string())],
+            vec![models::categories::GameVersion {
+                id: models::GameVersionId(0),
+                version: "1.19".to_string(),
+                version_type: "release".to_string(),
+                date: OffsetDateTime::now_utc(),
+                major: true,
+            }],
+            false,
+            false,
+            &pool,
+            &mut transaction,
+        )
+        .await;
+
+        undo_uploads(&file_host, &uploaded_files).await.unwrap();
+        transaction.rollback().await.unwrap();
+        let _ = std::fs::remove_dir_all(&root);
+
+        result.unwrap();
+        assert_eq!(version_files.len(), 1);
+        assert_eq!(version_files[0].mod_id.as_deref(), Some("examplemod"));
+    }
+}
@@ -1,6 +1,7 @@
 use crate::database;
 use crate::file_hosting::FileHost;
 use crate::models;
+use crate::models::changes::ChangeType;
 use crate::models::projects::{
     DonationLink, Project, ProjectId, ProjectStatus, SearchRequest, SideType,
 };
@@ -8,6 +9,7 @@ use crate::models::teams::Permissions;
 use crate::routes::ApiError;
 use crate::search::{search_for_project, SearchConfig, SearchError};
 use crate::util::auth::{get_user_from_headers, is_authorized};
+use crate::util::cache::CacheValidators;
 use crate::util::routes::read_from_payload;
 use crate::util::validate::validation_errors_to_string;
 use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
@@ -81,7 +83,16 @@ pub async fn project_get(
 
     if let Some(data) = project_data {
         if is_authorized(&data, &user_option, &pool).await? {
-            return Ok(HttpResponse::Ok().json(Project::from(data)));
+            let public = !data.status.is_hidden();
+            let body = serde_json::to_vec(&Project::from(data))?;
+
+            // `updated` only changes when versions are created, so only the
+            // ETag can tell whether the project was edited
+            return Ok(CacheValidators::new(&body, None, public).respond(
+                &req,
+                "application/json",
+                body,
+            ));
         }
     }
     Ok(HttpResponse::NotFound().body(""))
@@ -420,6 +431,16 @@ pub async fn project_edit(
                     )
                 })?;
 
+                // Changes are only recorded while the project is public
+                if !project_item.status.is_hidden() && status.is_hidden() {
+                    database::models::change_item::ChangeBuilder::project(
+                        id,
+                        ChangeType::Hidden.as_str(),
+                    )
+                    .insert(&mut transaction)
+                    .await?;
+                }
+
                 sqlx::query!(
                     "
                     UPDATE mods
@@ -833,6 +854,13 @@ pub async fn project_edit(
                 .await?;
             }
 
+            database::models::change_item::ChangeBuilder::project(
+                id,
+                ChangeType::Updated.as_str(),
+            )
+            .insert(&mut transaction)
+            .await?;
+
             transaction.commit().await?;
             Ok(HttpResponse::NoContent().body(""))
         } else {
@@ -1377,6 +1405,7 @@ pub async fn project_delete(
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
     config: web::Data<SearchConfig>,
+    file_host: web::Data<Arc<dyn FileHost + Send + Sync>>,
 ) -> Result<HttpResponse, ApiError> {
     let user = get_user_from_headers(req.headers(), &**pool).await?;
     let string = info.into_inner().0;
@@ -1419,12 +1448,20 @@ pub async fn project_delete(
 
     let mut transaction = pool.begin().await?;
 
+    let blobs = database::models::blob_item::StoredBlob::hashes_of_project(
+        project.id,
+        &mut *transaction,
+    )
+    .await?;
     let result =
         database::models::Project::remove_full(project.id, &mut transaction)
             .await?;
 
     transaction.commit().await?;
 
+    crate::util::blobs::delete_unreferenced_blobs(&blobs, &***file_host, &pool)
+        .await?;
+
     delete_from_index(project.id.into(), config).await?;
 
     if result.is_some() {
@@ -1497,6 +1534,13 @@ pub async fn project_follow(
     .execute(&mut *transaction)
     .await?;
 
+    database::models::analytics_item::record_follow(
+        project_id,
+        true,
+        &mut transaction,
+    )
+    .await?;
+
     transaction.commit().await?;
 
     Ok(HttpResponse::NoContent().body(""))
@@ -1560,6 +1604,13 @@ pub async fn project_unfollow(
         .execute(&mut *transaction)
         .await?;
 
+        database::models::analytics_item::record_follow(
+            project_id,
+            false,
+            &mut transaction,
+        )
+        .await?;
+
         transaction.commit().await?;
 
         Ok(HttpResponse::NoContent().body(""))
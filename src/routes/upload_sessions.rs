@@ -0,0 +1,540 @@
+use crate::database::models;
+use crate::database::models::upload_session_item::{
+    UploadSession, UploadSessionBuilder, UploadSessionChunk,
+};
+use crate::database::models::version_item::DependencyBuilder;
+use crate::file_hosting::FileHost;
+use crate::models::changes::ChangeType;
+use crate::models::ids::UploadSessionId;
+use crate::models::projects::{GameVersion, Loader, ProjectId, VersionId};
+use crate::models::teams::Permissions;
+use crate::routes::project_creation::{CreateError, UploadedFile};
+use crate::routes::version_creation::{
+    upload_file_data, FileUploadResponse, MAX_FILE_SIZE,
+};
+use crate::util::auth::get_user_from_headers;
+use crate::util::spool::SpoolWriter;
+use actix_web::web::{Data, Payload};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use bytes::BytesMut;
+use futures::StreamExt;
+use log::warn;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// The maximum size of a single chunk, in bytes
+const MAX_CHUNK_SIZE: usize = 16 * (1 << 20);
+/// How many upload sessions a user may have at once
+const MAX_SESSIONS_PER_USER: i64 = 10;
+/// The maximum total size of the files of a user's upload sessions, in bytes
+const MAX_RESERVED_SIZE: i64 = 2 * (1 << 30);
+
+#[derive(Deserialize)]
+pub struct UploadSessionCreate {
+    pub filename: String,
+    pub size: u64,
+}
+
+// under /api/v1/version/{version_id}
+#[post("{version_id}/upload_session")]
+pub async fn upload_session_create(
+    req: HttpRequest,
+    info: web::Path<(VersionId,)>,
+    pool: Data<PgPool>,
+    new_session: web::Json<UploadSessionCreate>,
+) -> Result<HttpResponse, CreateError> {
+    let mut transaction = pool.begin().await?;
+
+    let user = get_user_from_headers(req.headers(), &mut *transaction).await?;
+    let version_id: models::VersionId = info.into_inner().0.into();
+
+    let file_extension = get_extension(&new_session.filename)?;
+    if crate::util::ext::project_file_type(file_extension).is_none() {
+        return Err(CreateError::InvalidFileType(file_extension.to_string()));
+    }
+
+    if new_session.filename.len() > 2048 {
+        return Err(CreateError::InvalidInput(
+            "File names must be at most 2048 characters long".to_string(),
+        ));
+    }
+
+    if new_session.size == 0 || new_session.size > MAX_FILE_SIZE as u64 {
+        return Err(CreateError::InvalidInput(
+            "Project file exceeds the maximum of 500MiB. Contact a moderator or admin to request permission to upload larger files.".to_string(),
+        ));
+    }
+
+    check_upload_permission(version_id, &user, &mut transaction).await?;
+
+    let (sessions, reserved) =
+        UploadSession::get_reserved(user.id.into(), &mut transaction).await?;
+    if sessions >= MAX_SESSIONS_PER_USER {
+        return Err(CreateError::InvalidInput(format!(
+            "You may have at most {} upload sessions at once",
+            MAX_SESSIONS_PER_USER
+        )));
+    }
+    if reserved + new_session.size as i64 > MAX_RESERVED_SIZE {
+        return Err(CreateError::InvalidInput(
+            "The files of your upload sessions may be at most 2GiB in total"
+                .to_string(),
+        ));
+    }
+
+    let id = models::generate_upload_session_id(&mut transaction).await?;
+    UploadSessionBuilder {
+        id,
+        version_id,
+        user_id: user.id.into(),
+        filename: new_session.filename.clone(),
+        size: new_session.size as i64,
+    }
+    .insert(&mut transaction)
+    .await?;
+
+    let session = UploadSession::get(id, &mut *transaction)
+        .await?
+        .ok_or_else(|| {
+            CreateError::InvalidInput(
+                "Upload session could not be created".to_string(),
+            )
+        })?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok()
+        .json(crate::models::upload_sessions::UploadSession::from(session)))
+}
+
+// under /api/v1/upload_session/{id}
+#[get("{id}")]
+pub async fn upload_session_get(
+    req: HttpRequest,
+    info: web::Path<(UploadSessionId,)>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, CreateError> {
+    let user = get_user_from_headers(req.headers(), &**pool).await?;
+
+    let session = get_own_session(info.into_inner().0, &user, &**pool).await?;
+
+    Ok(HttpResponse::Ok()
+        .json(crate::models::upload_sessions::UploadSession::from(session)))
+}
+
+#[derive(Deserialize)]
+pub struct ChunkOffset {
+    pub offset: u64,
+}
+
+// under /api/v1/upload_session/{id}?offset={offset}
+#[put("{id}")]
+pub async fn upload_session_chunk(
+    req: HttpRequest,
+    info: web::Path<(UploadSessionId,)>,
+    chunk_offset: web::Query<ChunkOffset>,
+    pool: Data<PgPool>,
+    file_host: Data<Arc<dyn FileHost + Send + Sync>>,
+    mut payload: Payload,
+) -> Result<HttpResponse, CreateError> {
+    let user = get_user_from_headers(req.headers(), &**pool).await?;
+
+    let session = get_own_session(info.into_inner().0, &user, &**pool).await?;
+    let offset = chunk_offset.offset as i64;
+
+    if offset != session.received {
+        return Err(CreateError::InvalidInput(format!(
+            "Expected a chunk at offset {}",
+            session.received
+        )));
+    }
+
+    let mut data = BytesMut::new();
+    while let Some(item) = payload.next().await {
+        data.extend_from_slice(&item.map_err(|_| {
+            CreateError::InvalidInput(
+                "Unable to parse bytes in payload sent!".to_string(),
+            )
+        })?);
+
+        if data.len() > MAX_CHUNK_SIZE {
+            return Err(CreateError::InvalidInput(
+                "Chunks may be at most 16MiB in size".to_string(),
+            ));
+        }
+    }
+
+    if data.is_empty() {
+        return Err(CreateError::InvalidInput(
+            "Chunks must not be empty".to_string(),
+        ));
+    }
+    if offset + data.len() as i64 > session.size {
+        return Err(CreateError::InvalidInput(
+            "The chunk exceeds the size of the file".to_string(),
+        ));
+    }
+
+    // Reserving the chunk locks the session, so that concurrent requests
+    // for the same offset can't overwrite each other's chunks
+    let mut transaction = pool.begin().await?;
+    if !UploadSession::reserve_chunk(
+        session.id,
+        offset,
+        data.len() as i64,
+        &mut transaction,
+    )
+    .await?
+    {
+        return Err(CreateError::InvalidInput(
+            "Another chunk was uploaded at this offset".to_string(),
+        ));
+    }
+
+    let file_name =
+        format!("uploads/{}/{}", UploadSessionId::from(session.id), offset);
+    let upload_data = file_host
+        .upload_file("application/octet-stream", &file_name, data.freeze())
+        .await?;
+
+    let chunk = UploadSessionChunk {
+        offset,
+        size: upload_data.content_length as i32,
+        sha1: upload_data.content_sha1,
+        file_id: upload_data.file_id,
+        file_name: upload_data.file_name,
+    };
+
+    let result = async {
+        UploadSession::insert_chunk(session.id, &chunk, &mut transaction)
+            .await?;
+        transaction.commit().await
+    }
+    .await;
+
+    if let Err(e) = result {
+        remove_chunks(&***file_host, &[chunk]).await;
+        return Err(e.into());
+    }
+
+    let mut session =
+        crate::models::upload_sessions::UploadSession::from(session);
+    session.received += chunk.size as u64;
+
+    Ok(HttpResponse::Ok().json(session))
+}
+
+// under /api/v1/upload_session/{id}/finalize
+#[post("{id}/finalize")]
+pub async fn upload_session_finalize(
+    req: HttpRequest,
+    info: web::Path<(UploadSessionId,)>,
+    pool: Data<PgPool>,
+    file_host: Data<Arc<dyn FileHost + Send + Sync>>,
+) -> Result<HttpResponse, CreateError> {
+    let mut transaction = pool.begin().await?;
+    let mut uploaded_files = Vec::new();
+
+    let result = upload_session_finalize_inner(
+        req,
+        info.into_inner().0,
+        &pool,
+        &mut transaction,
+        &***file_host,
+        &mut uploaded_files,
+    )
+    .await;
+
+    match result {
+        Ok((chunks, warnings)) => {
+            transaction.commit().await?;
+            remove_chunks(&***file_host, &chunks).await;
+
+            Ok(HttpResponse::Ok().json(FileUploadResponse { warnings }))
+        }
+        Err(e) => {
+            // Failing to clean up mustn't hide why the upload failed
+            if let Err(undo_error) = super::project_creation::undo_uploads(
+                &***file_host,
+                &uploaded_files,
+            )
+            .await
+            {
+                warn!(
+                    "Deleting the files of a failed upload session failed: {}",
+                    undo_error
+                );
+            }
+            if let Err(rollback_error) = transaction.rollback().await {
+                warn!(
+                    "Rolling back a failed upload session failed: {}",
+                    rollback_error
+                );
+            }
+
+            Err(e)
+        }
+    }
+}
+
+/// Attaches the uploaded file to its version and removes the session,
+/// returning the chunks which can be deleted from the file host afterwards
+/// and the mismatches found in the file's manifest
+async fn upload_session_finalize_inner(
+    req: HttpRequest,
+    session_id: UploadSessionId,
+    pool: &PgPool,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    file_host: &dyn FileHost,
+    uploaded_files: &mut Vec<UploadedFile>,
+) -> Result<(Vec<UploadSessionChunk>, Vec<String>), CreateError> {
+    let cdn_url = dotenv::var("CDN_URL")?;
+
+    let user = get_user_from_headers(req.headers(), &mut *transaction).await?;
+    let session = own_session(
+        UploadSession::get_for_update(session_id.into(), transaction).await?,
+        &user,
+    )?;
+
+    if session.received != session.size {
+        return Err(CreateError::InvalidInput(format!(
+            "Only {} of {} bytes have been uploaded",
+            session.received, session.size
+        )));
+    }
+
+    check_upload_permission(session.version_id, &user, &mut *transaction)
+        .await?;
+
+    let version = models::Version::get_full(session.version_id, pool)
+        .await?
+        .ok_or_else(|| {
+            CreateError::InvalidInput(
+                "The version of this upload session has been deleted"
+                    .to_string(),
+            )
+        })?;
+
+    // Reassemble the file on disk one chunk at a time, verifying every chunk
+    let chunks =
+        UploadSession::get_chunks(session.id, &mut *transaction).await?;
+    let mut spool = SpoolWriter::new().await?;
+
+    for chunk in &chunks {
+        let chunk_data =
+            file_host.get_file(&chunk.file_id, &chunk.file_name).await?;
+
+        if chunk.offset != spool.size() as i64
+            || sha1::Sha1::from(&chunk_data).hexdigest() != chunk.sha1
+        {
+            return Err(CreateError::InvalidInput(
+                "A chunk of this upload is corrupted. Please restart the upload."
+                    .to_string(),
+            ));
+        }
+
+        spool.write(chunk_data).await?;
+    }
+
+    if spool.size() as i64 != session.size {
+        return Err(CreateError::InvalidInput(
+            "The uploaded chunks don't match the size of the file".to_string(),
+        ));
+    }
+
+    let project_type = sqlx::query!(
+        "
+        SELECT name FROM project_types pt
+        INNER JOIN mods ON mods.project_type = pt.id
+        WHERE mods.id = $1
+        ",
+        version.project_id as models::ProjectId,
+    )
+    .fetch_one(&mut *transaction)
+    .await?
+    .name;
+
+    let all_game_versions =
+        models::categories::GameVersion::list(&mut *transaction).await?;
+
+    let existing_dependencies = version.dependencies.len();
+    let mut file_builders = Vec::new();
+    let mut dependencies = version
+        .dependencies
+        .into_iter()
+        .map(|x| DependencyBuilder {
+            project_id: x.project_id,
+            version_id: x.version_id,
+            file_name: x.file_name,
+            dependency_type: x.dependency_type,
+        })
+        .collect();
+    let mut warnings = Vec::new();
+
+    upload_file_data(
+        spool.finish()?,
+        &session.filename,
+        get_extension(&session.filename)?,
+        file_host,
+        uploaded_files,
+        &mut file_builders,
+        &mut dependencies,
+        &mut warnings,
+        &cdn_url,
+        ProjectId(version.project_id.0 as u64),
+        &version.version_number,
+        &project_type,
+        version.loaders.into_iter().map(Loader).collect(),
+        version.game_versions.into_iter().map(GameVersion).collect(),
+        all_game_versions,
+        true,
+        false,
+        pool,
+        transaction,
+    )
+    .await?;
+
+    for file_builder in file_builders {
+        file_builder
+            .insert(session.version_id, &mut *transaction)
+            .await?;
+    }
+
+    // Dependencies found in the manifest of the uploaded file
+    for dependency in dependencies.split_off(existing_dependencies) {
+        dependency
+            .insert(session.version_id, &mut *transaction)
+            .await?;
+    }
+
+    models::change_item::ChangeBuilder::version(
+        version.project_id,
+        session.version_id,
+        ChangeType::Updated.as_str(),
+    )
+    .insert(&mut *transaction)
+    .await?;
+
+    UploadSession::remove(session.id, &mut *transaction).await?;
+
+    Ok((chunks, warnings))
+}
+
+// under /api/v1/upload_session/{id}
+#[delete("{id}")]
+pub async fn upload_session_delete(
+    req: HttpRequest,
+    info: web::Path<(UploadSessionId,)>,
+    pool: Data<PgPool>,
+    file_host: Data<Arc<dyn FileHost + Send + Sync>>,
+) -> Result<HttpResponse, CreateError> {
+    let mut transaction = pool.begin().await?;
+
+    let user = get_user_from_headers(req.headers(), &mut *transaction).await?;
+    let session =
+        get_own_session(info.into_inner().0, &user, &mut *transaction).await?;
+
+    let chunks =
+        UploadSession::get_chunks(session.id, &mut *transaction).await?;
+    UploadSession::remove(session.id, &mut *transaction).await?;
+
+    transaction.commit().await?;
+
+    remove_chunks(&***file_host, &chunks).await;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+/// Deletes the chunks of expired upload sessions from the file host and the database
+pub async fn remove_expired_sessions(
+    pool: &PgPool,
+    file_host: &(dyn FileHost + Send + Sync),
+) -> Result<(), sqlx::Error> {
+    for id in UploadSession::get_expired(pool).await? {
+        let chunks = UploadSession::get_chunks(id, pool).await?;
+        remove_chunks(file_host, &chunks).await;
+
+        UploadSession::remove(id, pool).await?;
+    }
+
+    Ok(())
+}
+
+async fn remove_chunks(
+    file_host: &(dyn FileHost + Send + Sync),
+    chunks: &[UploadSessionChunk],
+) {
+    for chunk in chunks {
+        if let Err(e) = file_host
+            .delete_file_version(&chunk.file_id, &chunk.file_name)
+            .await
+        {
+            warn!(
+                "Deleting upload session chunk {} failed: {}",
+                chunk.file_name, e
+            );
+        }
+    }
+}
+
+async fn get_own_session<'a, E>(
+    id: UploadSessionId,
+    user: &crate::models::users::User,
+    exec: E,
+) -> Result<UploadSession, CreateError>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    own_session(UploadSession::get(id.into(), exec).await?, user)
+}
+
+/// Hides sessions of other users, as if they didn't exist
+fn own_session(
+    session: Option<UploadSession>,
+    user: &crate::models::users::User,
+) -> Result<UploadSession, CreateError> {
+    session
+        .filter(|session| session.user_id == user.id.into())
+        .ok_or_else(|| {
+            CreateError::InvalidInput(
+                "The specified upload session does not exist!".to_string(),
+            )
+        })
+}
+
+async fn check_upload_permission(
+    version_id: models::VersionId,
+    user: &crate::models::users::User,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<(), CreateError> {
+    let team_member = models::TeamMember::get_from_user_id_version(
+        version_id,
+        user.id.into(),
+        &mut *transaction,
+    )
+    .await?;
+
+    if !team_member
+        .map(|x| x.permissions.contains(Permissions::UPLOAD_VERSION))
+        .unwrap_or(false)
+    {
+        return Err(CreateError::CustomAuthenticationError(
+            "You don't have permission to upload files to this version!"
+                .to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn get_extension(file_name: &str) -> Result<&str, CreateError> {
+    file_name
+        .rfind('.')
+        .and_then(|last_period| file_name.get((last_period + 1)..))
+        .ok_or_else(|| {
+            CreateError::MissingValueError(
+                "Missing content file extension".to_string(),
+            )
+        })
+}
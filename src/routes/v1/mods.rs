@@ -126,6 +126,7 @@ pub async fn mod_create(
     let result = project_create_inner(
         req,
         payload,
+        &**client,
         &mut transaction,
         &***file_host,
         &mut uploaded_files,
@@ -1,7 +1,8 @@
 use crate::file_hosting::FileHost;
 use crate::models::ids::{ProjectId, UserId, VersionId};
 use crate::models::projects::{
-    Dependency, GameVersion, Loader, Version, VersionFile, VersionType,
+    deserialize_hash_algorithm, Dependency, GameVersion, Loader, Version,
+    VersionFile, VersionType,
 };
 use crate::models::teams::Permissions;
 use crate::routes::versions::{VersionIds, VersionListFilters};
@@ -196,7 +197,10 @@ pub async fn version_get(
 
 #[derive(Deserialize)]
 pub struct Algorithm {
-    #[serde(default = "default_algorithm")]
+    #[serde(
+        default = "default_algorithm",
+        deserialize_with = "deserialize_hash_algorithm"
+    )]
     algorithm: String,
 }
 
@@ -217,7 +221,8 @@ pub async fn get_version_from_hash(
         "
         SELECT f.version_id version_id FROM hashes h
         INNER JOIN files f ON h.file_id = f.id
-        WHERE h.algorithm = $2 AND h.hash = $1
+        INNER JOIN versions v ON v.id = f.version_id
+        WHERE h.algorithm = $2 AND h.hash = $1 AND NOT v.quarantined
         ",
         hash.as_bytes(),
         algorithm.algorithm
@@ -263,7 +268,7 @@ pub async fn download_version(
         SELECT f.url url, f.id id, f.version_id version_id, v.mod_id mod_id FROM hashes h
         INNER JOIN files f ON h.file_id = f.id
         INNER JOIN versions v ON v.id = f.version_id
-        WHERE h.algorithm = $2 AND h.hash = $1
+        WHERE h.algorithm = $2 AND h.hash = $1 AND NOT v.quarantined
         ",
         hash.as_bytes(),
         algorithm.algorithm
@@ -296,7 +301,7 @@ pub async fn delete_file(
 
     let result = sqlx::query!(
         "
-        SELECT f.id id, f.version_id version_id, f.filename filename, v.version_number version_number, v.mod_id project_id FROM hashes h
+        SELECT f.id id, f.version_id version_id, f.filename filename, f.blob_sha512 blob_sha512, v.version_number version_number, v.mod_id project_id FROM hashes h
         INNER JOIN files f ON h.file_id = f.id
         INNER JOIN versions v ON v.id = f.version_id
         WHERE h.algorithm = $2 AND h.hash = $1
@@ -338,6 +343,46 @@ pub async fn delete_file(
 
         let mut transaction = pool.begin().await?;
 
+        sqlx::query!(
+            "
+            DELETE FROM file_pack_summaries
+            WHERE file_id = $1
+            ",
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM nested_files
+            WHERE file_id = $1
+            ",
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM file_validation_findings
+            WHERE file_id = $1
+            ",
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM file_scan_findings
+            WHERE file_id = $1
+            ",
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
         sqlx::query!(
             "
             DELETE FROM hashes
@@ -358,20 +403,38 @@ pub async fn delete_file(
         .execute(&mut *transaction)
         .await?;
 
-        let project_id: models::projects::ProjectId =
-            database::models::ids::ProjectId(row.project_id).into();
-        file_host
-            .delete_file_version(
-                "",
-                &format!(
-                    "data/{}/versions/{}/{}",
-                    project_id, row.version_number, row.filename
-                ),
+        if let Some(blob_sha512) = &row.blob_sha512 {
+            database::models::blob_item::StoredBlob::remove_reference(
+                blob_sha512,
+                &mut transaction,
             )
             .await?;
+        } else {
+            let project_id: models::projects::ProjectId =
+                database::models::ids::ProjectId(row.project_id).into();
+            file_host
+                .delete_file_version(
+                    "",
+                    &format!(
+                        "data/{}/versions/{}/{}",
+                        project_id, row.version_number, row.filename
+                    ),
+                )
+                .await?;
+        }
 
         transaction.commit().await?;
 
+        // Other files may still be stored in the same blob
+        if let Some(blob_sha512) = row.blob_sha512 {
+            crate::util::blobs::delete_unreferenced_blobs(
+                &[blob_sha512],
+                &***file_host,
+                &pool,
+            )
+            .await?;
+        }
+
         Ok(HttpResponse::NoContent().body(""))
     } else {
         Ok(HttpResponse::NotFound().body(""))
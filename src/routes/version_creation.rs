@@ -4,26 +4,35 @@ use crate::database::models::version_item::{
     DependencyBuilder, VersionBuilder, VersionFileBuilder,
 };
 use crate::file_hosting::FileHost;
-use crate::models::pack::PackFileHash;
+use crate::models::changes::ChangeType;
+use crate::models::pack::{PackFileHash, PackSummary};
 use crate::models::projects::{
-    Dependency, DependencyType, GameVersion, Loader, ProjectId, Version,
-    VersionFile, VersionId, VersionType,
+    Dependency, DependencyType, FindingSeverity, GameVersion, Loader,
+    ProjectId, ProjectStatus, ValidationFinding, Version, VersionFile,
+    VersionId, VersionType, PUBLIC_HASH_ALGORITHMS,
 };
 use crate::models::teams::Permissions;
 use crate::routes::project_creation::{CreateError, UploadedFile};
 use crate::util::auth::get_user_from_headers;
-use crate::util::routes::read_from_field;
+use crate::util::routes::spool_field;
+use crate::util::spool::SpooledFile;
 use crate::util::validate::validation_errors_to_string;
-use crate::validate::{validate_file, ValidationResult};
+use crate::validate::game_version_range::versions_outside_range;
+use crate::validate::metadata::ModMetadata;
+use crate::validate::{validate_file, ValidationError, ValidationResult};
 use actix_multipart::{Field, Multipart};
 use actix_web::web::Data;
 use actix_web::{post, HttpRequest, HttpResponse};
 use futures::stream::{StreamExt, TryStreamExt};
+use log::warn;
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPool;
 use time::OffsetDateTime;
 use validator::Validate;
 
+/// The maximum size of a single version file, in bytes
+pub const MAX_FILE_SIZE: usize = 500 * (1 << 20);
+
 #[derive(Serialize, Deserialize, Validate, Clone)]
 pub struct InitialVersionData {
     #[serde(alias = "mod_id")]
@@ -56,6 +65,21 @@ pub struct InitialVersionData {
     pub primary_file: Option<String>,
 }
 
+/// The created version, along with mismatches between its data and the
+/// manifests of its files
+#[derive(Serialize)]
+struct VersionCreateResponse {
+    #[serde(flatten)]
+    version: Version,
+    warnings: Vec<String>,
+}
+
+/// Mismatches between the data of a version and the manifest of an uploaded file
+#[derive(Serialize)]
+pub struct FileUploadResponse {
+    pub warnings: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 struct InitialFileData {
     // TODO: hashes?
@@ -75,6 +99,7 @@ pub async fn version_create(
     let result = version_create_inner(
         req,
         payload,
+        &**client,
         &mut transaction,
         &***file_host,
         &mut uploaded_files,
@@ -105,6 +130,7 @@ pub async fn version_create(
 async fn version_create_inner(
     req: HttpRequest,
     mut payload: Multipart,
+    pool: &PgPool,
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     file_host: &dyn FileHost,
     uploaded_files: &mut Vec<UploadedFile>,
@@ -113,6 +139,7 @@ async fn version_create_inner(
 
     let mut initial_version_data = None;
     let mut version_builder = None;
+    let mut warnings = Vec::new();
 
     let all_game_versions =
         models::categories::GameVersion::list(&mut *transaction).await?;
@@ -316,6 +343,7 @@ async fn version_create_inner(
             uploaded_files,
             &mut version.files,
             &mut version.dependencies,
+            &mut warnings,
             &cdn_url,
             &content_disposition,
             version.project_id.into(),
@@ -326,6 +354,7 @@ async fn version_create_inner(
             all_game_versions.clone(),
             version_data.primary_file.is_some(),
             version_data.primary_file.as_deref() == Some(name),
+            pool,
             transaction,
         )
         .await?;
@@ -410,6 +439,10 @@ async fn version_create_inner(
                 hashes: file
                     .hashes
                     .iter()
+                    .filter(|hash| {
+                        PUBLIC_HASH_ALGORITHMS
+                            .contains(&hash.algorithm.as_str())
+                    })
                     .map(|hash| {
                         (
                             hash.algorithm.clone(),
@@ -425,16 +458,36 @@ async fn version_create_inner(
                 filename: file.filename.clone(),
                 primary: file.primary,
                 size: file.size,
+                findings: file
+                    .findings
+                    .iter()
+                    .cloned()
+                    .map(ValidationFinding::from)
+                    .collect(),
+                pack_summary: file.pack_summary.clone().map(PackSummary::from),
             })
             .collect::<Vec<_>>(),
-        dependencies: version_data.dependencies,
+        // Includes the dependencies found in the uploaded files
+        dependencies: builder
+            .dependencies
+            .iter()
+            .map(|d| Dependency {
+                version_id: d.version_id.map(Into::into),
+                project_id: d.project_id.map(Into::into),
+                file_name: d.file_name.clone(),
+                dependency_type: DependencyType::from_str(&d.dependency_type),
+            })
+            .collect(),
         game_versions: version_data.game_versions,
         loaders: version_data.loaders,
     };
 
     builder.insert(transaction).await?;
 
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().json(VersionCreateResponse {
+        version: response,
+        warnings,
+    }))
 }
 
 // TODO: file deletion, listing, etc
@@ -498,6 +551,7 @@ async fn upload_file_to_version_inner(
 
     let mut initial_file_data: Option<InitialFileData> = None;
     let mut file_builders: Vec<VersionFileBuilder> = Vec::new();
+    let mut warnings = Vec::new();
 
     let user = get_user_from_headers(req.headers(), &mut *transaction).await?;
 
@@ -553,6 +607,17 @@ async fn upload_file_to_version_inner(
     let all_game_versions =
         models::categories::GameVersion::list(&mut *transaction).await?;
 
+    let mut dependencies: Vec<DependencyBuilder> = version
+        .dependencies
+        .iter()
+        .map(|x| models::version_item::DependencyBuilder {
+            project_id: x.project_id,
+            version_id: x.version_id,
+            file_name: x.file_name.clone(),
+            dependency_type: x.dependency_type.clone(),
+        })
+        .collect();
+
     while let Some(item) = payload.next().await {
         let mut field: Field = item.map_err(CreateError::MultipartError)?;
         let content_disposition = field.content_disposition().clone();
@@ -580,23 +645,13 @@ async fn upload_file_to_version_inner(
             ))
         })?;
 
-        let mut dependencies = version
-            .dependencies
-            .iter()
-            .map(|x| models::version_item::DependencyBuilder {
-                project_id: x.project_id,
-                version_id: x.version_id,
-                file_name: None,
-                dependency_type: x.dependency_type.clone(),
-            })
-            .collect();
-
         upload_file(
             &mut field,
             file_host,
             uploaded_files,
             &mut file_builders,
             &mut dependencies,
+            &mut warnings,
             &cdn_url,
             &content_disposition,
             project_id,
@@ -612,6 +667,7 @@ async fn upload_file_to_version_inner(
             all_game_versions.clone(),
             true,
             false,
+            &**client,
             transaction,
         )
         .await?;
@@ -626,7 +682,20 @@ async fn upload_file_to_version_inner(
         file_builder.insert(version_id, &mut *transaction).await?;
     }
 
-    Ok(HttpResponse::Ok().into())
+    // Dependencies found in the manifests of the uploaded files
+    for dependency in dependencies.split_off(version.dependencies.len()) {
+        dependency.insert(version_id, &mut *transaction).await?;
+    }
+
+    models::change_item::ChangeBuilder::version(
+        version.project_id,
+        version_id,
+        ChangeType::Updated.as_str(),
+    )
+    .insert(&mut *transaction)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(FileUploadResponse { warnings }))
 }
 
 // This function is used for adding a file to a version, uploading the initial
@@ -638,6 +707,7 @@ pub async fn upload_file(
     uploaded_files: &mut Vec<UploadedFile>,
     version_files: &mut Vec<models::version_item::VersionFileBuilder>,
     dependencies: &mut Vec<models::version_item::DependencyBuilder>,
+    warnings: &mut Vec<String>,
     cdn_url: &str,
     content_disposition: &actix_web::http::header::ContentDisposition,
     project_id: crate::models::ids::ProjectId,
@@ -648,28 +718,88 @@ pub async fn upload_file(
     all_game_versions: Vec<models::categories::GameVersion>,
     ignore_primary: bool,
     force_primary: bool,
+    pool: &PgPool,
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
 ) -> Result<(), CreateError> {
     let (file_name, file_extension) = get_name_ext(content_disposition)?;
 
+    // Reject unsupported files before reading them
+    if crate::util::ext::project_file_type(file_extension).is_none() {
+        return Err(CreateError::InvalidFileType(file_extension.to_string()));
+    }
+
+    let data = spool_field(
+        field, MAX_FILE_SIZE,
+        "Project file exceeds the maximum of 500MiB. Contact a moderator or admin to request permission to upload larger files."
+    ).await?;
+
+    upload_file_data(
+        data,
+        file_name,
+        file_extension,
+        file_host,
+        uploaded_files,
+        version_files,
+        dependencies,
+        warnings,
+        cdn_url,
+        project_id,
+        version_number,
+        project_type,
+        loaders,
+        game_versions,
+        all_game_versions,
+        ignore_primary,
+        force_primary,
+        pool,
+        transaction,
+    )
+    .await
+}
+
+/// Validates and uploads a version file whose contents have already been
+/// received, either from a multipart field or from an upload session
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_file_data(
+    data: SpooledFile,
+    file_name: &str,
+    file_extension: &str,
+    file_host: &dyn FileHost,
+    uploaded_files: &mut Vec<UploadedFile>,
+    version_files: &mut Vec<models::version_item::VersionFileBuilder>,
+    dependencies: &mut Vec<models::version_item::DependencyBuilder>,
+    warnings: &mut Vec<String>,
+    cdn_url: &str,
+    project_id: crate::models::ids::ProjectId,
+    version_number: &str,
+    project_type: &str,
+    loaders: Vec<Loader>,
+    game_versions: Vec<GameVersion>,
+    all_game_versions: Vec<models::categories::GameVersion>,
+    ignore_primary: bool,
+    force_primary: bool,
+    pool: &PgPool,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<(), CreateError> {
     let content_type = crate::util::ext::project_file_type(file_extension)
         .ok_or_else(|| {
             CreateError::InvalidFileType(file_extension.to_string())
         })?;
 
-    let data = read_from_field(
-        field, 500 * (1 << 20),
-        "Project file exceeds the maximum of 500MiB. Contact a moderator or admin to request permission to upload larger files."
-    ).await?;
-
-    let hash = sha1::Sha1::from(&data).hexdigest();
+    // Files stored by their contents can be shared between the versions of
+    // a project, but not with other projects
+    let content_addressed = crate::util::blobs::content_addressed_storage();
     let exists = sqlx::query!(
         "
         SELECT EXISTS(SELECT 1 FROM hashes h
-        WHERE h.algorithm = $2 AND h.hash = $1)
+        INNER JOIN files f ON f.id = h.file_id
+        INNER JOIN versions v ON v.id = f.version_id
+        WHERE h.algorithm = $2 AND h.hash = $1 AND (NOT $4 OR v.mod_id != $3))
         ",
-        hash.as_bytes(),
-        "sha1"
+        data.sha1().as_bytes(),
+        "sha1",
+        models::ProjectId::from(project_id) as models::ProjectId,
+        content_addressed,
     )
     .fetch_one(&mut *transaction)
     .await?
@@ -683,17 +813,106 @@ pub async fn upload_file(
         ));
     }
 
-    let validation_result = validate_file(
-        data.clone().into(),
+    let mut validation_report = validate_file(
+        data.reader().map_err(ValidationError::Io)?,
         file_extension.to_string(),
         project_type.to_string(),
         loaders,
-        game_versions,
-        all_game_versions,
+        game_versions.clone(),
+        all_game_versions.clone(),
     )
     .await?;
 
-    if let ValidationResult::PassWithPackData(ref data) = validation_result {
+    if let ValidationResult::PassWithPackData(_, summary) =
+        &validation_report.result
+    {
+        if let Some(loader) = summary.loader.clone() {
+            let loader_versions = models::categories::LoaderVersion::list(
+                &loader,
+                &mut *transaction,
+            )
+            .await?;
+            crate::validate::pack::check_pack_loader_version(
+                &mut validation_report,
+                &loader_versions,
+            );
+        }
+    }
+
+    // Only moderators get to see what the scanner found, so that the authors
+    // of malicious files can't learn what gave them away
+    let scan_findings =
+        crate::scanning::scan_file(data.reader().map_err(ValidationError::Io)?)
+            .await?;
+
+    let (nested_jars, nested_findings) =
+        crate::validate::nested::find_nested_jars(
+            data.reader().map_err(ValidationError::Io)?,
+        )
+        .await?;
+    validation_report.findings.extend(nested_findings);
+
+    for finding in &validation_report.findings {
+        if finding.severity >= FindingSeverity::Warning {
+            warnings.push(format!("{}: {}", file_name, finding.message));
+        }
+    }
+
+    if scan_findings
+        .iter()
+        .any(|x| x.severity == FindingSeverity::Error)
+    {
+        warnings.push(format!(
+            "{} needs to be reviewed by a moderator. Its version is hidden until then.",
+            file_name
+        ));
+    }
+
+    let mut mod_id = None;
+
+    if let ValidationResult::PassWithModMetadata(ref metadata) =
+        validation_report.result
+    {
+        check_mod_metadata(metadata, file_name, version_number, warnings);
+        check_game_versions(
+            metadata,
+            file_name,
+            &game_versions,
+            &all_game_versions,
+            warnings,
+        )?;
+        add_manifest_dependencies(
+            metadata,
+            file_name,
+            project_id.into(),
+            dependencies,
+            warnings,
+            transaction,
+        )
+        .await?;
+
+        // The project of new projects isn't inserted yet, so the mod id is
+        // recorded along with the file
+        mod_id = Some(metadata.mod_id.clone());
+    }
+
+    let mut pack_summary = None;
+
+    if let ValidationResult::PassWithPackData(ref data, ref summary) =
+        validation_report.result
+    {
+        pack_summary = Some(models::version_item::PackSummary {
+            file_count: summary.file_count as i32,
+            total_file_size: summary.total_file_size as i64,
+            override_count: summary.override_count as i32,
+            client_override_count: summary.client_override_count as i32,
+            server_override_count: summary.server_override_count as i32,
+            override_jar_count: summary.override_jar_count as i32,
+            minecraft_version: summary.minecraft_version.clone(),
+            loader: summary.loader.clone(),
+            loader_version: summary.loader_version.clone(),
+        });
+
         if dependencies.is_empty() {
             let hashes: Vec<Vec<u8>> = data
                 .files
@@ -707,7 +926,7 @@ pub async fn upload_file(
                     SELECT v.id version_id, v.mod_id project_id, h.hash hash FROM hashes h
                     INNER JOIN files f on h.file_id = f.id
                     INNER JOIN versions v on f.version_id = v.id
-                    WHERE h.algorithm = 'sha1' AND h.hash = ANY($1)
+                    WHERE h.algorithm = 'sha1' AND h.hash = ANY($1) AND NOT v.quarantined
                     ",
                     &*hashes
                 )
@@ -750,28 +969,107 @@ pub async fn upload_file(
         }
     }
 
-    let file_path_encode = format!(
-        "data/{}/versions/{}/{}",
-        project_id,
-        version_number,
-        urlencoding::encode(file_name)
-    );
-    let file_path = format!(
-        "data/{}/versions/{}/{}",
-        project_id, version_number, &file_name
-    );
-
-    let upload_data = file_host
-        .upload_file(content_type, &file_path, data.freeze())
-        .await?;
-
-    uploaded_files.push(UploadedFile {
-        file_id: upload_data.file_id,
-        file_name: file_path,
-    });
+    let (url, size, sha1_bytes, sha512_bytes, blob_sha512) =
+        if content_addressed {
+            let sha512 = data.sha512().to_string();
+            #[allow(clippy::cast_possible_truncation)]
+            let size = data.size() as u32;
+
+            // New blobs are recorded right after they're stored, outside of
+            // the transaction, so that they're removed by the reconciliation
+            // if the version file is never added. Files sharing a blob are
+            // downloaded under the name the blob was first uploaded as.
+            let existing = models::blob_item::StoredBlob::add_reference(
+                &sha512,
+                transaction,
+            )
+            .await?;
+            let file_path = match existing {
+                Some(blob) => blob.file_name,
+                None => {
+                    let file_path =
+                        crate::util::blobs::blob_path(&sha512, file_name);
+                    let upload_data = data
+                        .upload(file_host, content_type, &file_path)
+                        .await?;
+
+                    let stored = models::blob_item::StoredBlobBuilder {
+                        sha512: sha512.clone(),
+                        file_id: upload_data.file_id.clone(),
+                        file_name: file_path.clone(),
+                        size: i64::from(size),
+                    }
+                    .insert(pool)
+                    .await;
+
+                    // The contents may have been stored concurrently under
+                    // another name, in which case this copy isn't needed
+                    if stored.as_ref().ok() != Some(&file_path) {
+                        if let Err(e) = file_host
+                            .delete_file_version(
+                                &upload_data.file_id,
+                                &file_path,
+                            )
+                            .await
+                        {
+                            warn!(
+                                "Deleting the unrecorded blob {} failed: {}",
+                                file_path, e
+                            );
+                        }
+                    }
+                    stored?;
+
+                    models::blob_item::StoredBlob::add_reference(
+                        &sha512,
+                        transaction,
+                    )
+                    .await?
+                    .ok_or_else(|| {
+                        CreateError::InvalidInput(
+                            "The file was removed while it was uploaded, please try again"
+                                .to_string(),
+                        )
+                    })?
+                    .file_name
+                }
+            };
+
+            (
+                crate::util::blobs::blob_url(cdn_url, &file_path),
+                size,
+                data.sha1().as_bytes().to_vec(),
+                sha512.clone().into_bytes(),
+                Some(sha512),
+            )
+        } else {
+            let file_path_encode = format!(
+                "data/{}/versions/{}/{}",
+                project_id,
+                version_number,
+                urlencoding::encode(file_name)
+            );
+            let file_path = format!(
+                "data/{}/versions/{}/{}",
+                project_id, version_number, &file_name
+            );
+
+            let upload_data =
+                data.upload(file_host, content_type, &file_path).await?;
+
+            uploaded_files.push(UploadedFile {
+                file_id: upload_data.file_id,
+                file_name: file_path,
+            });
 
-    let sha1_bytes = upload_data.content_sha1.into_bytes();
-    let sha512_bytes = upload_data.content_sha512.into_bytes();
+            (
+                format!("{}/{}", cdn_url, file_path_encode),
+                upload_data.content_length,
+                upload_data.content_sha1.into_bytes(),
+                upload_data.content_sha512.into_bytes(),
+                None,
+            )
+        };
 
     if version_files.iter().any(|x| {
         x.hashes
@@ -786,7 +1084,7 @@ pub async fn upload_file(
 
     version_files.push(models::version_item::VersionFileBuilder {
         filename: file_name.to_string(),
-        url: format!("{}/{}", cdn_url, file_path_encode),
+        url,
         hashes: vec![
             models::version_item::HashBuilder {
                 algorithm: "sha1".to_string(),
@@ -800,17 +1098,185 @@ pub async fn upload_file(
                 // bytes, but this is the string version.
                 hash: sha512_bytes,
             },
+            // Maven clients verify downloads with these
+            models::version_item::HashBuilder {
+                algorithm: "md5".to_string(),
+                hash: data.md5().as_bytes().to_vec(),
+            },
+            models::version_item::HashBuilder {
+                algorithm: "sha256".to_string(),
+                hash: data.sha256().as_bytes().to_vec(),
+            },
         ],
-        primary: (validation_result.is_passed()
+        primary: (validation_report.is_passed()
             && version_files.iter().all(|x| !x.primary)
             && !ignore_primary)
             || force_primary,
-        size: upload_data.content_length,
+        size,
+        findings: validation_report
+            .findings
+            .into_iter()
+            .map(models::version_item::ValidationFinding::from)
+            .collect(),
+        scan_findings: scan_findings
+            .into_iter()
+            .map(models::version_item::ValidationFinding::from)
+            .collect(),
+        nested_files: nested_jars
+            .into_iter()
+            .map(|x| models::version_item::NestedFileBuilder {
+                path: x.path,
+                mod_id: x.mod_id,
+                version_number: x.version,
+                sha1: x.sha1.into_bytes(),
+                sha512: x.sha512.into_bytes(),
+            })
+            .collect(),
+        pack_summary,
+        blob_sha512,
+        mod_id,
     });
 
     Ok(())
 }
 
+/// Compares the data declared in a mod's manifest with the data given for
+/// its version, reporting mismatches to the uploader
+fn check_mod_metadata(
+    metadata: &ModMetadata,
+    file_name: &str,
+    version_number: &str,
+    warnings: &mut Vec<String>,
+) {
+    if let Some(version) = &metadata.version {
+        // Version numbers often add the Minecraft version or loader to the
+        // mod's own version, such as `1.2.0+1.18.2`
+        if !version_number.contains(&**version) {
+            warnings.push(format!(
+                "The manifest of {} declares version {}, but the version number is {}",
+                file_name, version, version_number
+            ));
+        }
+    }
+}
+
+/// Compares the Minecraft versions declared in a mod's manifest with the game
+/// versions selected for its version. Files which support none of the selected
+/// versions are rejected, partial mismatches are reported to the uploader.
+fn check_game_versions(
+    metadata: &ModMetadata,
+    file_name: &str,
+    game_versions: &[GameVersion],
+    all_game_versions: &[models::categories::GameVersion],
+    warnings: &mut Vec<String>,
+) -> Result<(), CreateError> {
+    let range = match &metadata.minecraft_range {
+        Some(range) => range,
+        None => return Ok(()),
+    };
+
+    let outside =
+        versions_outside_range(range, game_versions, all_game_versions);
+
+    if outside.is_empty() {
+        return Ok(());
+    }
+
+    if outside.len() == game_versions.len() {
+        return Err(CreateError::InvalidInput(format!(
+            "The manifest of {} declares support for Minecraft {}, which includes none of the selected game versions",
+            file_name, range
+        )));
+    }
+
+    let outside = outside
+        .iter()
+        .map(|x| x.0.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    warnings.push(format!(
+        "The manifest of {} declares support for Minecraft {}, which doesn't include {}",
+        file_name, range, outside
+    ));
+
+    Ok(())
+}
+
+/// Adds dependencies on the projects known to provide the mod ids declared in
+/// a mod's manifest. Optional dependencies are only suggested to the uploader.
+async fn add_manifest_dependencies(
+    metadata: &ModMetadata,
+    file_name: &str,
+    project_id: models::ProjectId,
+    dependencies: &mut Vec<DependencyBuilder>,
+    warnings: &mut Vec<String>,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<(), CreateError> {
+    let mod_ids = metadata
+        .dependencies
+        .iter()
+        .map(|x| x.mod_id.clone())
+        .collect::<Vec<_>>();
+    let statuses = [ProjectStatus::Approved, ProjectStatus::Archived]
+        .iter()
+        .map(|x| x.as_str().to_string())
+        .collect::<Vec<_>>();
+
+    // If several projects declare the same mod id, the most downloaded one wins
+    let known_projects = sqlx::query!(
+        "
+        SELECT DISTINCT ON (mi.mod_id)
+            mi.mod_id, mi.project_id
+        FROM mod_ids mi
+        INNER JOIN mods m ON m.id = mi.project_id
+        INNER JOIN statuses s ON s.id = m.status
+        WHERE mi.mod_id = ANY($1) AND mi.project_id != $2 AND s.status = ANY($3)
+        ORDER BY mi.mod_id, m.downloads DESC
+        ",
+        &mod_ids,
+        project_id as models::ProjectId,
+        &statuses,
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    for dependency in &metadata.dependencies {
+        let dependency_project_id = match known_projects
+            .iter()
+            .find(|x| x.mod_id == dependency.mod_id)
+        {
+            Some(known) => models::ProjectId(known.project_id),
+            None => continue,
+        };
+
+        if dependencies
+            .iter()
+            .any(|x| x.project_id == Some(dependency_project_id))
+        {
+            continue;
+        }
+
+        if dependency.dependency_type == DependencyType::Optional {
+            warnings.push(format!(
+                "The manifest of {} declares an optional dependency on {}, which is provided by project {}",
+                file_name,
+                dependency.mod_id,
+                ProjectId::from(dependency_project_id)
+            ));
+        } else {
+            dependencies.push(DependencyBuilder {
+                project_id: Some(dependency_project_id),
+                version_id: None,
+                file_name: None,
+                dependency_type: dependency.dependency_type.to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
 pub fn get_name_ext(
     content_disposition: &actix_web::http::header::ContentDisposition,
 ) -> Result<(&str, &str), CreateError> {
@@ -2,7 +2,10 @@ use super::ApiError;
 use crate::database::models::VersionId;
 use crate::database::models::{version_item::QueryVersion, DatabaseError};
 use crate::file_hosting::FileHost;
-use crate::models::projects::{GameVersion, Loader, Version};
+use crate::models::changes::ChangeType;
+use crate::models::projects::{
+    deserialize_hash_algorithm, GameVersion, Loader, Version,
+};
 use crate::models::teams::Permissions;
 use crate::util::auth::get_user_from_headers;
 use crate::util::routes::ok_or_not_found;
@@ -16,7 +19,10 @@ use std::sync::Arc;
 
 #[derive(Deserialize)]
 pub struct Algorithm {
-    #[serde(default = "default_algorithm")]
+    #[serde(
+        default = "default_algorithm",
+        deserialize_with = "deserialize_hash_algorithm"
+    )]
     algorithm: String,
 }
 
@@ -37,7 +43,8 @@ pub async fn get_version_from_hash(
         "
         SELECT f.version_id version_id FROM hashes h
         INNER JOIN files f ON h.file_id = f.id
-        WHERE h.algorithm = $2 AND h.hash = $1
+        INNER JOIN versions v ON v.id = f.version_id
+        WHERE h.algorithm = $2 AND h.hash = $1 AND NOT v.quarantined
         ",
         hash.as_bytes(),
         algorithm.algorithm
@@ -62,9 +69,65 @@ pub async fn get_version_from_hash(
     }
 }
 
+/// A version file which bundles the file with the requested hash
+#[derive(Serialize)]
+pub struct BundlingFile {
+    pub project_id: models::ids::ProjectId,
+    pub version_id: models::ids::VersionId,
+    pub filename: String,
+    /// The path of the bundled file inside of this file
+    pub path: String,
+    pub mod_id: Option<String>,
+    pub version_number: Option<String>,
+}
+
+// under /api/v1/version_file/{hash}/bundled
+#[get("{version_id}/bundled")]
+pub async fn get_bundling_files(
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    algorithm: web::Query<Algorithm>,
+) -> Result<HttpResponse, ApiError> {
+    let hash = info.into_inner().0.to_lowercase();
+
+    let result = sqlx::query!(
+        "
+        SELECT v.mod_id project_id, f.version_id version_id, f.filename filename,
+        nf.path path, nf.mod_id mod_id, nf.version_number version_number
+        FROM nested_files nf
+        INNER JOIN files f ON nf.file_id = f.id
+        INNER JOIN versions v ON v.id = f.version_id
+        WHERE (($2 = 'sha1' AND nf.sha1 = $1) OR ($2 = 'sha512' AND nf.sha512 = $1))
+        AND NOT v.quarantined
+        ORDER BY v.date_published DESC
+        ",
+        hash.as_bytes(),
+        algorithm.algorithm
+    )
+    .fetch_all(&**pool)
+    .await?;
+
+    let files = result
+        .into_iter()
+        .map(|x| BundlingFile {
+            project_id: database::models::ProjectId(x.project_id).into(),
+            version_id: database::models::VersionId(x.version_id).into(),
+            filename: x.filename,
+            path: x.path,
+            mod_id: x.mod_id,
+            version_number: x.version_number,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(files))
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct DownloadRedirect {
     pub url: String,
+    /// The URLs the file can be downloaded from if `url` fails, including
+    /// `url` itself
+    pub mirrors: Vec<String>,
 }
 
 // under /api/v1/version_file/{hash}/download
@@ -82,7 +145,7 @@ pub async fn download_version(
         SELECT f.url url, f.id id, f.version_id version_id, v.mod_id project_id FROM hashes h
         INNER JOIN files f ON h.file_id = f.id
         INNER JOIN versions v ON v.id = f.version_id
-        WHERE h.algorithm = $2 AND h.hash = $1
+        WHERE h.algorithm = $2 AND h.hash = $1 AND NOT v.quarantined
         ",
         hash.as_bytes(),
         algorithm.algorithm
@@ -91,11 +154,16 @@ pub async fn download_version(
     .await?;
 
     if let Some(id) = result {
+        let mirrors =
+            crate::util::cdn::file_mirrors(&id.url, &mut *transaction).await?;
         transaction.commit().await?;
 
-        Ok(HttpResponse::TemporaryRedirect()
-            .append_header(("Location", &*id.url))
-            .json(DownloadRedirect { url: id.url }))
+        Ok(crate::util::cdn::mirrored_redirect(&mirrors).json(
+            DownloadRedirect {
+                url: id.url,
+                mirrors,
+            },
+        ))
     } else {
         Ok(HttpResponse::NotFound().body(""))
     }
@@ -116,7 +184,7 @@ pub async fn delete_file(
 
     let result = sqlx::query!(
         "
-        SELECT f.id id, f.version_id version_id, f.filename filename, v.version_number version_number, v.mod_id project_id FROM hashes h
+        SELECT f.id id, f.version_id version_id, f.filename filename, f.blob_sha512 blob_sha512, v.version_number version_number, v.mod_id project_id FROM hashes h
         INNER JOIN files f ON h.file_id = f.id
         INNER JOIN versions v ON v.id = f.version_id
         WHERE h.algorithm = $2 AND h.hash = $1
@@ -177,6 +245,46 @@ pub async fn delete_file(
 
         let mut transaction = pool.begin().await?;
 
+        sqlx::query!(
+            "
+            DELETE FROM file_pack_summaries
+            WHERE file_id = $1
+            ",
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM nested_files
+            WHERE file_id = $1
+            ",
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM file_validation_findings
+            WHERE file_id = $1
+            ",
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM file_scan_findings
+            WHERE file_id = $1
+            ",
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
         sqlx::query!(
             "
             DELETE FROM hashes
@@ -197,20 +305,46 @@ pub async fn delete_file(
         .execute(&mut *transaction)
         .await?;
 
-        let project_id: models::projects::ProjectId =
-            database::models::ids::ProjectId(row.project_id).into();
-        file_host
-            .delete_file_version(
-                "",
-                &format!(
-                    "data/{}/versions/{}/{}",
-                    project_id, row.version_number, row.filename
-                ),
+        database::models::change_item::ChangeBuilder::version(
+            database::models::ProjectId(row.project_id),
+            database::models::VersionId(row.version_id),
+            ChangeType::Updated.as_str(),
+        )
+        .insert(&mut transaction)
+        .await?;
+
+        if let Some(blob_sha512) = &row.blob_sha512 {
+            database::models::blob_item::StoredBlob::remove_reference(
+                blob_sha512,
+                &mut transaction,
             )
             .await?;
+        } else {
+            let project_id: models::projects::ProjectId =
+                database::models::ids::ProjectId(row.project_id).into();
+            file_host
+                .delete_file_version(
+                    "",
+                    &format!(
+                        "data/{}/versions/{}/{}",
+                        project_id, row.version_number, row.filename
+                    ),
+                )
+                .await?;
+        }
 
         transaction.commit().await?;
 
+        // Other files may still be stored in the same blob
+        if let Some(blob_sha512) = row.blob_sha512 {
+            crate::util::blobs::delete_unreferenced_blobs(
+                &[blob_sha512],
+                &***file_host,
+                &pool,
+            )
+            .await?;
+        }
+
         Ok(HttpResponse::NoContent().body(""))
     } else {
         Ok(HttpResponse::NotFound().body(""))
@@ -241,7 +375,7 @@ pub async fn get_update_from_hash(
         SELECT v.mod_id project_id FROM hashes h
         INNER JOIN files f ON h.file_id = f.id
         INNER JOIN versions v ON v.id = f.version_id
-        WHERE h.algorithm = $2 AND h.hash = $1
+        WHERE h.algorithm = $2 AND h.hash = $1 AND NOT v.quarantined
         ",
         hash.as_bytes(),
         algorithm.algorithm
@@ -291,6 +425,7 @@ pub async fn get_update_from_hash(
 // Requests above with multiple versions below
 #[derive(Deserialize)]
 pub struct FileHashes {
+    #[serde(deserialize_with = "deserialize_hash_algorithm")]
     pub algorithm: String,
     pub hashes: Vec<String>,
 }
@@ -311,7 +446,8 @@ pub async fn get_versions_from_hashes(
         "
         SELECT h.hash hash, h.algorithm algorithm, f.version_id version_id FROM hashes h
         INNER JOIN files f ON h.file_id = f.id
-        WHERE h.algorithm = $2 AND h.hash = ANY($1::bytea[])
+        INNER JOIN versions v ON v.id = f.version_id
+        WHERE h.algorithm = $2 AND h.hash = ANY($1::bytea[]) AND NOT v.quarantined
         ",
         hashes_parsed.as_slice(),
         file_data.algorithm
@@ -371,7 +507,7 @@ pub async fn download_files(
         SELECT f.url url, h.hash hash, h.algorithm algorithm, f.version_id version_id, v.mod_id project_id FROM hashes h
         INNER JOIN files f ON h.file_id = f.id
         INNER JOIN versions v ON v.id = f.version_id
-        WHERE h.algorithm = $2 AND h.hash = ANY($1::bytea[])
+        WHERE h.algorithm = $2 AND h.hash = ANY($1::bytea[]) AND NOT v.quarantined
         ",
         hashes_parsed.as_slice(),
         file_data.algorithm
@@ -398,6 +534,7 @@ pub async fn download_files(
 
 #[derive(Deserialize)]
 pub struct ManyUpdateData {
+    #[serde(deserialize_with = "deserialize_hash_algorithm")]
     pub algorithm: String,
     pub hashes: Vec<String>,
     pub loaders: Vec<Loader>,
@@ -422,7 +559,7 @@ pub async fn update_files(
         SELECT f.url url, h.hash hash, h.algorithm algorithm, f.version_id version_id, v.mod_id project_id FROM hashes h
         INNER JOIN files f ON h.file_id = f.id
         INNER JOIN versions v ON v.id = f.version_id
-        WHERE h.algorithm = $2 AND h.hash = ANY($1::bytea[])
+        WHERE h.algorithm = $2 AND h.hash = ANY($1::bytea[]) AND NOT v.quarantined
         ",
         hashes_parsed.as_slice(),
         update_data.algorithm
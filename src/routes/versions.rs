@@ -1,13 +1,17 @@
 use super::ApiError;
 use crate::database;
+use crate::file_hosting::FileHost;
 use crate::models;
-use crate::models::projects::{Dependency, Version};
+use crate::models::changes::ChangeType;
+use crate::models::projects::{Dependency, ProjectStatus, Version};
 use crate::models::teams::Permissions;
 use crate::util::auth::{get_user_from_headers, is_authorized};
+use crate::util::cache::CacheValidators;
 use crate::util::validate::validation_errors_to_string;
 use actix_web::{delete, get, patch, web, HttpRequest, HttpResponse};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::sync::Arc;
 use validator::Validate;
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -141,6 +145,7 @@ pub async fn versions_get(
 
 #[get("{version_id}")]
 pub async fn version_get(
+    req: HttpRequest,
     info: web::Path<(models::ids::VersionId,)>,
     pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, ApiError> {
@@ -149,7 +154,27 @@ pub async fn version_get(
         database::models::Version::get_full(id.into(), &**pool).await?;
 
     if let Some(data) = version_data {
-        Ok(HttpResponse::Ok().json(models::projects::Version::from(data)))
+        let status = sqlx::query!(
+            "
+            SELECT s.status FROM mods m
+            INNER JOIN statuses s ON s.id = m.status
+            WHERE m.id = $1
+            ",
+            data.project_id as database::models::ids::ProjectId,
+        )
+        .fetch_one(&**pool)
+        .await?
+        .status;
+
+        let public = !ProjectStatus::from_str(&status).is_hidden();
+        let body = serde_json::to_vec(&models::projects::Version::from(data))?;
+
+        // Versions have no timestamp which changes when they are edited
+        Ok(CacheValidators::new(&body, None, public).respond(
+            &req,
+            "application/json",
+            body,
+        ))
     } else {
         Ok(HttpResponse::NotFound().body(""))
     }
@@ -489,6 +514,14 @@ pub async fn version_edit(
                 .await?;
             }
 
+            database::models::change_item::ChangeBuilder::version(
+                version_item.project_id,
+                id,
+                ChangeType::Updated.as_str(),
+            )
+            .insert(&mut transaction)
+            .await?;
+
             transaction.commit().await?;
             Ok(HttpResponse::NoContent().body(""))
         } else {
@@ -506,6 +539,7 @@ pub async fn version_delete(
     req: HttpRequest,
     info: web::Path<(models::ids::VersionId,)>,
     pool: web::Data<PgPool>,
+    file_host: web::Data<Arc<dyn FileHost + Send + Sync>>,
 ) -> Result<HttpResponse, ApiError> {
     let user = get_user_from_headers(req.headers(), &**pool).await?;
     let id = info.into_inner().0;
@@ -537,12 +571,20 @@ pub async fn version_delete(
 
     let mut transaction = pool.begin().await?;
 
+    let blobs = database::models::blob_item::StoredBlob::hashes_of_version(
+        id.into(),
+        &mut *transaction,
+    )
+    .await?;
     let result =
         database::models::Version::remove_full(id.into(), &mut transaction)
             .await?;
 
     transaction.commit().await?;
 
+    crate::util::blobs::delete_unreferenced_blobs(&blobs, &***file_host, &pool)
+        .await?;
+
     if result.is_some() {
         Ok(HttpResponse::NoContent().body(""))
     } else {
@@ -0,0 +1,243 @@
+use crate::models::projects::{FindingSeverity, ValidationFinding};
+use crate::scanning::signatures::SignatureScanner;
+use crate::util::spool::FileReader;
+use crate::validate::{match_extension_ignore_case, ValidationError};
+use lazy_static::lazy_static;
+use std::io::{Cursor, Read, Seek};
+use zip::ZipArchive;
+
+mod signatures;
+
+/// How deep nested jars are opened, such as jars bundled through Jar-in-Jar
+const MAX_NESTING_DEPTH: usize = 3;
+/// Files larger than this are only scanned up to this size, and get their
+/// version quarantined
+const MAX_SCANNED_FILE_SIZE: u64 = 32 * (1 << 20);
+/// How many bytes are decompressed for scanning an upload at most, including
+/// the files of nested archives, so that zip bombs can't keep a worker busy.
+/// Uploads which don't fit are quarantined, so that padding can't be used to
+/// hide files from the scanner.
+const MAX_SCANNED_ARCHIVE_SIZE: u64 = 256 * (1 << 20);
+
+/// A stage looking for malicious contents in the files inside of an upload,
+/// run after the upload passed validation
+pub trait Scanner: Sync + Send {
+    /// Scans a file by its path, before its contents are read
+    fn scan_name(&self, path: &str, findings: &mut Vec<ValidationFinding>);
+    /// Whether the contents of the file at this path need to be scanned
+    fn wants_contents(&self, path: &str) -> bool;
+    fn scan_contents(
+        &self,
+        path: &str,
+        contents: &[u8],
+        findings: &mut Vec<ValidationFinding>,
+    );
+}
+
+lazy_static! {
+    pub static ref SCANNERS: Vec<Box<dyn Scanner>> =
+        vec![Box::new(SignatureScanner::load())];
+}
+
+/// Scans an uploaded archive and the archives nested inside of it. Findings of
+/// the same kind are merged, keeping the path of the first file they were
+/// found in.
+pub async fn scan_file(
+    data: FileReader,
+) -> Result<Vec<ValidationFinding>, ValidationError> {
+    actix_web::web::block(move || {
+        let mut archive = ZipArchive::new(data)?;
+        let mut findings = Vec::new();
+        let mut budget = MAX_SCANNED_ARCHIVE_SIZE;
+
+        scan_archive(&mut archive, "", 0, &mut budget, &mut findings)?;
+
+        Ok(merge_findings(findings))
+    })
+    .await?
+}
+
+fn scan_archive<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    prefix: &str,
+    depth: usize,
+    budget: &mut u64,
+    findings: &mut Vec<ValidationFinding>,
+) -> Result<(), ValidationError> {
+    for index in 0..archive.len() {
+        let file = archive.by_index(index)?;
+
+        if file.is_dir() {
+            continue;
+        }
+
+        let path = format!("{}{}", prefix, file.name());
+
+        for scanner in SCANNERS.iter() {
+            scanner.scan_name(&path, findings);
+        }
+
+        let nested = depth < MAX_NESTING_DEPTH
+            && match_extension_ignore_case(&path, &[".jar", ".zip"]);
+        let scanners = SCANNERS
+            .iter()
+            .filter(|x| x.wants_contents(&path))
+            .collect::<Vec<_>>();
+
+        if scanners.is_empty() && !nested {
+            continue;
+        }
+
+        if *budget == 0 {
+            findings.push(scan_incomplete(&path));
+            return Ok(());
+        }
+
+        // One byte more than the limit is read to find out whether the file
+        // is larger, as the sizes in the archive can't be trusted
+        let limit = MAX_SCANNED_FILE_SIZE.min(*budget);
+        let mut contents = Vec::new();
+        file.take(limit + 1).read_to_end(&mut contents)?;
+        if contents.len() as u64 > limit {
+            contents.truncate(limit as usize);
+            findings.push(scan_incomplete(&path));
+        }
+        *budget -= contents.len() as u64;
+
+        for scanner in scanners {
+            scanner.scan_contents(&path, &contents, findings);
+        }
+
+        if nested {
+            // Files which only look like archives are still scanned as files
+            if let Ok(mut nested_archive) =
+                ZipArchive::new(Cursor::new(contents))
+            {
+                scan_archive(
+                    &mut nested_archive,
+                    &format!("{}!/", path),
+                    depth + 1,
+                    budget,
+                    findings,
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Files which couldn't be scanned completely could hide anything past the
+/// part that was scanned, so their versions are quarantined as well
+fn scan_incomplete(path: &str) -> ValidationFinding {
+    ValidationFinding::new(
+        FindingSeverity::Error,
+        "scan_incomplete",
+        "Too large to be scanned completely when decompressed",
+    )
+    .at(path)
+}
+
+fn merge_findings(findings: Vec<ValidationFinding>) -> Vec<ValidationFinding> {
+    let mut merged: Vec<(ValidationFinding, usize)> = Vec::new();
+
+    for finding in findings {
+        match merged.iter_mut().find(|(x, _)| x.code == finding.code) {
+            Some((_, count)) => *count += 1,
+            None => merged.push((finding, 1)),
+        }
+    }
+
+    merged
+        .into_iter()
+        .map(|(mut finding, count)| {
+            if count > 1 {
+                finding.message =
+                    format!("{} (found in {} files)", finding.message, count);
+            }
+            finding
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_findings, scan_archive};
+    use crate::models::projects::{FindingSeverity, ValidationFinding};
+    use crate::validate::test_util::make_dummy_zip_bytes;
+    use std::io::Cursor;
+    use zip::ZipArchive;
+
+    fn scan(
+        files: &[(&str, &[u8])],
+        mut budget: u64,
+    ) -> Vec<ValidationFinding> {
+        let data = make_dummy_zip_bytes(files).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(data)).unwrap();
+        let mut findings = Vec::new();
+
+        scan_archive(&mut archive, "", 0, &mut budget, &mut findings).unwrap();
+
+        merge_findings(findings)
+    }
+
+    fn scan_codes(files: &[(&str, &[u8])], budget: u64) -> Vec<String> {
+        scan(files, budget).into_iter().map(|x| x.code).collect()
+    }
+
+    #[test]
+    fn scans_within_the_budget() {
+        let class = [0u8; 100];
+
+        assert!(scan_codes(
+            &[("a/A.class", &class), ("a/B.class", &class)],
+            200
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn stops_once_the_budget_is_spent() {
+        let class = [0u8; 100];
+        let nested = make_dummy_zip_bytes(&[("a/C.class", &class)]).unwrap();
+
+        assert_eq!(
+            scan_codes(
+                &[
+                    ("a/A.class", &class),
+                    ("nested.jar", &nested),
+                    ("a/B.class", &class),
+                ],
+                150
+            ),
+            vec!["scan_incomplete"]
+        );
+    }
+
+    #[test]
+    fn padding_does_not_hide_files() {
+        let padding = [0u8; 1000];
+        let payload = b"java/net/URLClassLoader https://example.com/a.jar";
+
+        let findings = scan(
+            &[("a/Padding.class", &padding), ("a/Payload.class", payload)],
+            1000,
+        );
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].code, "scan_incomplete");
+        assert_eq!(findings[0].severity, FindingSeverity::Error);
+        assert_eq!(findings[0].path.as_deref(), Some("a/Payload.class"));
+    }
+
+    #[test]
+    fn truncated_files_are_incomplete() {
+        let padding = [0u8; 1000];
+
+        let findings = scan(&[("a/Padding.class", &padding)], 500);
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].code, "scan_incomplete");
+        assert_eq!(findings[0].severity, FindingSeverity::Error);
+    }
+}
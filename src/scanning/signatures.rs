@@ -0,0 +1,335 @@
+use crate::models::projects::{FindingSeverity, ValidationFinding};
+use crate::scanning::Scanner;
+use crate::validate::match_extension_ignore_case;
+use regex::bytes::RegexSet;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// The rules used unless `SCANNER_RULES_PATH` points to another rule file
+const DEFAULT_RULES: &str = include_str!("../../scanner_rules.toml");
+
+#[derive(Error, Debug)]
+pub enum RuleError {
+    #[error("Unable to read rule file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Unable to parse rule file: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Invalid pattern: {0}")]
+    Regex(#[from] regex::Error),
+    #[error("Rule {0} has no patterns")]
+    Empty(String),
+}
+
+#[derive(Deserialize)]
+struct RuleFile {
+    #[serde(rename = "rule", default)]
+    rules: Vec<RuleDefinition>,
+}
+
+#[derive(Deserialize)]
+struct RuleDefinition {
+    code: String,
+    severity: FindingSeverity,
+    message: String,
+    #[serde(default)]
+    target: RuleTarget,
+    #[serde(default = "default_extensions")]
+    extensions: Vec<String>,
+    #[serde(default)]
+    all: Vec<String>,
+    #[serde(default)]
+    any: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum RuleTarget {
+    Contents,
+    Name,
+}
+
+impl Default for RuleTarget {
+    fn default() -> Self {
+        RuleTarget::Contents
+    }
+}
+
+fn default_extensions() -> Vec<String> {
+    vec!["class".to_string()]
+}
+
+struct Rule {
+    definition: RuleDefinition,
+    /// Extensions of the files the rule applies to, such as `.class`
+    extensions: Vec<String>,
+    /// Indices into the patterns of the scanner
+    all: Vec<usize>,
+    any: Vec<usize>,
+}
+
+/// Looks for the byte patterns of a signature rule file
+pub struct SignatureScanner {
+    rules: Vec<Rule>,
+    /// The patterns of all rules, so each file is only searched once
+    patterns: RegexSet,
+}
+
+impl SignatureScanner {
+    /// Loads the rule file at `SCANNER_RULES_PATH`, or the default rules if it
+    /// isn't set. Panics if the rules are invalid.
+    pub fn load() -> Self {
+        let result = match dotenv::var("SCANNER_RULES_PATH") {
+            Ok(path) => std::fs::read_to_string(path)
+                .map_err(RuleError::from)
+                .and_then(|x| Self::from_rules(&x)),
+            Err(_) => Self::from_rules(DEFAULT_RULES),
+        };
+
+        result.expect("Unable to load scanner rules")
+    }
+
+    pub fn from_rules(rules: &str) -> Result<Self, RuleError> {
+        let file: RuleFile = toml::from_str(rules)?;
+
+        let mut patterns: Vec<String> = Vec::new();
+        let mut index_of = |pattern: &str| {
+            let pattern = regex::escape(pattern);
+            match patterns.iter().position(|x| *x == pattern) {
+                Some(index) => index,
+                None => {
+                    patterns.push(pattern);
+                    patterns.len() - 1
+                }
+            }
+        };
+
+        let mut rules = Vec::new();
+        for definition in file.rules {
+            if definition.all.is_empty() && definition.any.is_empty() {
+                return Err(RuleError::Empty(definition.code));
+            }
+
+            let all = definition.all.iter().map(|x| index_of(x)).collect();
+            let any = definition.any.iter().map(|x| index_of(x)).collect();
+
+            rules.push(Rule {
+                extensions: definition
+                    .extensions
+                    .iter()
+                    .map(|x| format!(".{}", x))
+                    .collect(),
+                definition,
+                all,
+                any,
+            });
+        }
+
+        Ok(SignatureScanner {
+            rules,
+            patterns: RegexSet::new(patterns)?,
+        })
+    }
+
+    fn scan(
+        &self,
+        target: RuleTarget,
+        path: &str,
+        haystack: &[u8],
+        findings: &mut Vec<ValidationFinding>,
+    ) {
+        let rules = self
+            .rules
+            .iter()
+            .filter(|x| x.definition.target == target && x.applies_to(path))
+            .collect::<Vec<_>>();
+
+        if rules.is_empty() {
+            return;
+        }
+
+        let matches = self.patterns.matches(haystack);
+
+        for rule in rules {
+            if rule.all.iter().all(|x| matches.matched(*x))
+                && (rule.any.is_empty()
+                    || rule.any.iter().any(|x| matches.matched(*x)))
+            {
+                findings.push(
+                    ValidationFinding::new(
+                        rule.definition.severity,
+                        &rule.definition.code,
+                        rule.definition.message.clone(),
+                    )
+                    .at(path),
+                );
+            }
+        }
+    }
+}
+
+impl Rule {
+    fn applies_to(&self, path: &str) -> bool {
+        let extensions =
+            self.extensions.iter().map(|x| &**x).collect::<Vec<_>>();
+
+        match_extension_ignore_case(path, &extensions)
+    }
+}
+
+impl Scanner for SignatureScanner {
+    fn scan_name(&self, path: &str, findings: &mut Vec<ValidationFinding>) {
+        // Only the name of the file itself, not of the folders or archives
+        // it is in
+        let name = path.rsplit('/').next().unwrap_or(path).to_lowercase();
+
+        self.scan(RuleTarget::Name, path, name.as_bytes(), findings);
+    }
+
+    fn wants_contents(&self, path: &str) -> bool {
+        self.rules.iter().any(|x| {
+            x.definition.target == RuleTarget::Contents && x.applies_to(path)
+        })
+    }
+
+    fn scan_contents(
+        &self,
+        path: &str,
+        contents: &[u8],
+        findings: &mut Vec<ValidationFinding>,
+    ) {
+        self.scan(RuleTarget::Contents, path, contents, findings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SignatureScanner;
+    use crate::models::projects::FindingSeverity;
+    use crate::scanning::Scanner;
+
+    const RULES: &str = r#"
+        [[rule]]
+        code = "runtime_exec"
+        severity = "warning"
+        message = "Runs system commands"
+        all = ["java/lang/Runtime", "exec"]
+
+        [[rule]]
+        code = "discord_webhook"
+        severity = "error"
+        message = "Contains a Discord webhook URL"
+        any = ["discord.com/api/webhooks", "skoohbew/ipa/moc.drocsid"]
+
+        [[rule]]
+        code = "suspicious_nested_jar"
+        severity = "error"
+        message = "Contains a suspicious nested jar"
+        target = "name"
+        extensions = ["jar"]
+        any = ["stealer"]
+    "#;
+
+    fn codes(
+        scanner: &SignatureScanner,
+        path: &str,
+        contents: &[u8],
+    ) -> Vec<String> {
+        let mut findings = Vec::new();
+
+        scanner.scan_name(path, &mut findings);
+        if scanner.wants_contents(path) {
+            scanner.scan_contents(path, contents, &mut findings);
+        }
+
+        findings.into_iter().map(|x| x.code).collect()
+    }
+
+    #[test]
+    fn default_rules_are_valid() {
+        SignatureScanner::from_rules(super::DEFAULT_RULES).unwrap();
+    }
+    #[test]
+    fn all_patterns_must_match() {
+        let scanner = SignatureScanner::from_rules(RULES).unwrap();
+
+        assert_eq!(
+            codes(&scanner, "a/B.class", b"..java/lang/Runtime..exec.."),
+            vec!["runtime_exec"]
+        );
+        assert!(
+            codes(&scanner, "a/B.class", b"..java/lang/Runtime..").is_empty()
+        );
+    }
+    #[test]
+    fn any_pattern_matches() {
+        let scanner = SignatureScanner::from_rules(RULES).unwrap();
+
+        assert_eq!(
+            codes(&scanner, "a/B.CLASS", b"https://skoohbew/ipa/moc.drocsid"),
+            vec!["discord_webhook"]
+        );
+    }
+    #[test]
+    fn rules_only_apply_to_their_extensions() {
+        let scanner = SignatureScanner::from_rules(RULES).unwrap();
+
+        assert!(codes(&scanner, "README.md", b"discord.com/api/webhooks")
+            .is_empty());
+    }
+    #[test]
+    fn name_rules() {
+        let scanner = SignatureScanner::from_rules(RULES).unwrap();
+
+        assert_eq!(
+            codes(&scanner, "META-INF/jars/Token-Stealer.jar", b""),
+            vec!["suspicious_nested_jar"]
+        );
+        assert!(codes(&scanner, "stealer/library.jar", b"").is_empty());
+    }
+    #[test]
+    fn severity_is_kept() {
+        let scanner = SignatureScanner::from_rules(RULES).unwrap();
+        let mut findings = Vec::new();
+
+        scanner.scan_contents(
+            "a/B.class",
+            b"discord.com/api/webhooks",
+            &mut findings,
+        );
+
+        assert_eq!(findings[0].severity, FindingSeverity::Error);
+        assert_eq!(findings[0].path.as_deref(), Some("a/B.class"));
+    }
+    #[test]
+    fn runtime_exec_needs_a_method_reference() {
+        let scanner =
+            SignatureScanner::from_rules(super::DEFAULT_RULES).unwrap();
+
+        assert!(codes(
+            &scanner,
+            "a/B.class",
+            b"java/lang/Runtime..\x01\x00\x0bavailableProcessors..executor"
+        )
+        .is_empty());
+        assert_eq!(
+            codes(
+                &scanner,
+                "a/B.class",
+                b"java/lang/Runtime..\x01\x00\x04exec..\x01\x00\x27(Ljava/lang/String;)Ljava/lang/Process;"
+            ),
+            vec!["runtime_exec"]
+        );
+    }
+    #[test]
+    fn empty_rules_are_rejected() {
+        assert!(SignatureScanner::from_rules(
+            r#"
+            [[rule]]
+            code = "empty"
+            severity = "info"
+            message = "Matches nothing"
+            "#
+        )
+        .is_err());
+    }
+}
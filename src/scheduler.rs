@@ -58,6 +58,13 @@ pub fn schedule_versions(
                 warn!("Version update failed: {}", e);
             }
             info!("Done indexing game versions");
+
+            info!("Indexing mod loader versions");
+            let result = update_loader_versions(&pool_ref).await;
+            if let Err(e) = result {
+                warn!("Loader version update failed: {}", e);
+            }
+            info!("Done indexing mod loader versions");
         }
     });
 }
@@ -70,13 +77,17 @@ pub enum VersionIndexingError {
     NetworkError(#[from] reqwest::Error),
     #[error("Database error while updating game versions list: {0}")]
     DatabaseError(#[from] crate::database::models::DatabaseError),
+    #[error("Error while parsing Maven metadata: {0}")]
+    XmlError(String),
 }
 
+use crate::models::pack::PackDependency;
 use crate::util::env::parse_var;
 use serde::Deserialize;
 use time::Format::Rfc3339;
 use time::OffsetDateTime;
 use tokio_stream::wrappers::IntervalStream;
+use yaserde_derive::YaDeserialize;
 
 #[derive(Deserialize)]
 struct InputFormat<'a> {
@@ -159,6 +170,85 @@ async fn update_versions(
     Ok(())
 }
 
+#[derive(Deserialize)]
+struct LoaderVersionFormat {
+    version: String,
+}
+
+#[derive(Default, YaDeserialize)]
+#[yaserde(rename = "metadata")]
+struct MavenMetadataFormat {
+    versioning: MavenVersioningFormat,
+}
+
+#[derive(Default, YaDeserialize)]
+#[yaserde(rename = "versioning")]
+struct MavenVersioningFormat {
+    versions: MavenVersionsFormat,
+}
+
+#[derive(Default, YaDeserialize)]
+#[yaserde(rename = "versions")]
+struct MavenVersionsFormat {
+    #[yaserde(rename = "version")]
+    versions: Vec<String>,
+}
+
+async fn update_loader_versions(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+) -> Result<(), VersionIndexingError> {
+    let fabric = reqwest::get("https://meta.fabricmc.net/v2/versions/loader")
+        .await?
+        .json::<Vec<LoaderVersionFormat>>()
+        .await?;
+    let quilt = reqwest::get("https://meta.quiltmc.org/v3/versions/loader")
+        .await?
+        .json::<Vec<LoaderVersionFormat>>()
+        .await?;
+    let forge = reqwest::get(
+        "https://maven.minecraftforge.net/net/minecraftforge/forge/maven-metadata.xml",
+    )
+    .await?
+    .text()
+    .await?;
+    let forge: MavenMetadataFormat = yaserde::de::from_str(&forge)
+        .map_err(VersionIndexingError::XmlError)?;
+
+    let loaders = [
+        (
+            PackDependency::FabricLoader,
+            fabric.into_iter().map(|x| x.version).collect::<Vec<_>>(),
+        ),
+        (
+            PackDependency::QuiltLoader,
+            quilt.into_iter().map(|x| x.version).collect(),
+        ),
+        (
+            PackDependency::Forge,
+            // Forge versions start with the version of Minecraft they are
+            // for, such as 1.18.2-40.1.0, which packs leave out
+            forge
+                .versioning
+                .versions
+                .versions
+                .iter()
+                .filter_map(|x| x.split_once('-').map(|(_, x)| x.to_string()))
+                .collect(),
+        ),
+    ];
+
+    for (loader, versions) in &loaders {
+        crate::database::models::categories::LoaderVersion::insert_many(
+            loader.as_str(),
+            versions,
+            pool,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
 // A list of version names that contains spaces.
 // Generated using the command
 // ```sh
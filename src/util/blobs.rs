@@ -0,0 +1,64 @@
+use crate::database::models::blob_item::StoredBlob;
+use crate::file_hosting::FileHost;
+use crate::routes::ApiError;
+use crate::util::env::parse_var;
+use sqlx::PgPool;
+
+/// Whether new version files are stored by the hash of their contents, so
+/// that files with the same contents share one stored blob
+pub fn content_addressed_storage() -> bool {
+    parse_var("CONTENT_ADDRESSED_STORAGE").unwrap_or(false)
+}
+
+/// The path a blob is stored at. Blobs are split into folders by the first
+/// byte of their hash to keep folders small. The name of the file the blob
+/// was first uploaded as is kept as the last segment, so that downloads of
+/// the blob are saved under that name.
+pub fn blob_path(sha512: &str, file_name: &str) -> String {
+    format!("blobs/{}/{}/{}", &sha512[..2], sha512, file_name)
+}
+
+/// The URL a blob stored at the given path is downloaded from
+pub fn blob_url(cdn_url: &str, file_path: &str) -> String {
+    crate::util::cdn::stored_file_url(cdn_url, file_path)
+}
+
+/// Deletes the given blobs from the file host if no file references them
+/// anymore. This must run after the transaction which removed the
+/// references was committed.
+pub async fn delete_unreferenced_blobs(
+    sha512s: &[String],
+    file_host: &dyn FileHost,
+    pool: &PgPool,
+) -> Result<(), ApiError> {
+    if sha512s.is_empty() {
+        return Ok(());
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    for blob in StoredBlob::take_unreferenced(sha512s, &mut transaction).await?
+    {
+        file_host
+            .delete_file_version(&blob.file_id, &blob.file_name)
+            .await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blobs_keep_their_file_name() {
+        assert_eq!(blob_path("abcdef", "mod.jar"), "blobs/ab/abcdef/mod.jar");
+        assert_eq!(
+            blob_url("https://cdn.modrinth.com", "blobs/ab/abcdef/my mod.jar"),
+            "https://cdn.modrinth.com/blobs/ab/abcdef/my%20mod.jar"
+        );
+    }
+}
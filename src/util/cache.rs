@@ -0,0 +1,184 @@
+use actix_web::http::header::{
+    CacheControl, CacheDirective, ETag, EntityTag, HttpDate, IfModifiedSince,
+    IfNoneMatch, LastModified, VARY,
+};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse};
+use std::time::{Duration, SystemTime};
+use time::OffsetDateTime;
+
+/// How long (in seconds) shared caches may reuse a response for publicly visible content
+const PUBLIC_MAX_AGE: u32 = 60;
+
+/// Validators used to answer conditional GET requests (`If-None-Match` and
+/// `If-Modified-Since`) for a single representation of a resource.
+pub struct CacheValidators {
+    etag: EntityTag,
+    last_modified: Option<HttpDate>,
+    public: bool,
+}
+
+impl CacheValidators {
+    /// Builds the validators for a response body. The strong ETag combines
+    /// the resource's timestamp with a hash of the body, so edits which don't
+    /// touch the timestamp (downloads, descriptions, ...) still change it.
+    ///
+    /// `last_modified` should only be given if it changes whenever the
+    /// resource does, since `If-Modified-Since` is answered from it alone.
+    /// Without it, only the ETag is used.
+    ///
+    /// `public` should be false whenever the content is only visible to
+    /// authorized users, so that shared caches never store it.
+    pub fn new(
+        body: &[u8],
+        last_modified: Option<OffsetDateTime>,
+        public: bool,
+    ) -> Self {
+        let timestamp = last_modified.map(|x| x.unix_timestamp().max(0) as u64);
+        let body_hash = sha1::Sha1::from(body).hexdigest();
+
+        CacheValidators {
+            etag: EntityTag::new_strong(format!(
+                "{:x}-{}",
+                timestamp.unwrap_or_default(),
+                &body_hash[..20]
+            )),
+            last_modified: timestamp.map(|timestamp| {
+                HttpDate::from(
+                    SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp),
+                )
+            }),
+            public,
+        }
+    }
+
+    /// Whether the client's cached copy is still current
+    pub fn is_not_modified(&self, req: &HttpRequest) -> bool {
+        // If-None-Match takes precedence over If-Modified-Since (RFC 7232, section 6)
+        if let Some(if_none_match) = req.get_header::<IfNoneMatch>() {
+            return match if_none_match {
+                IfNoneMatch::Any => true,
+                IfNoneMatch::Items(tags) => {
+                    tags.iter().any(|tag| tag.weak_eq(&self.etag))
+                }
+            };
+        }
+
+        if let (Some(last_modified), Some(IfModifiedSince(since))) =
+            (self.last_modified, req.get_header::<IfModifiedSince>())
+        {
+            return last_modified <= since;
+        }
+
+        false
+    }
+
+    /// Responds with either `304 Not Modified` or the full body, carrying the
+    /// validators and caching headers in both cases.
+    pub fn respond(
+        &self,
+        req: &HttpRequest,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> HttpResponse {
+        let not_modified = self.is_not_modified(req);
+        let mut response = if not_modified {
+            HttpResponse::NotModified()
+        } else {
+            HttpResponse::Ok()
+        };
+
+        response.insert_header(ETag(self.etag.clone()));
+        if let Some(last_modified) = self.last_modified {
+            response.insert_header(LastModified(last_modified));
+        }
+
+        if self.public {
+            response.insert_header(CacheControl(vec![
+                CacheDirective::Public,
+                CacheDirective::MaxAge(PUBLIC_MAX_AGE),
+            ]));
+        } else {
+            response
+                .insert_header(CacheControl(vec![
+                    CacheDirective::Private,
+                    CacheDirective::NoCache,
+                ]))
+                .insert_header((VARY, "Authorization"));
+        }
+
+        if not_modified {
+            response.finish()
+        } else {
+            response.content_type(content_type).body(body)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CacheValidators;
+    use actix_web::http::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH};
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use time::OffsetDateTime;
+
+    fn validators() -> CacheValidators {
+        CacheValidators::new(
+            b"{}",
+            Some(OffsetDateTime::from_unix_timestamp(1650000000)),
+            true,
+        )
+    }
+
+    #[test]
+    fn no_conditional_headers() {
+        let req = TestRequest::default().to_http_request();
+
+        let response = validators().respond(&req, "application/json", vec![]);
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn matching_etag() {
+        let validators = validators();
+        let etag = validators.etag.to_string();
+        let req = TestRequest::default()
+            .insert_header((IF_NONE_MATCH, etag))
+            .to_http_request();
+
+        assert!(validators.is_not_modified(&req));
+    }
+
+    #[test]
+    fn stale_etag() {
+        let req = TestRequest::default()
+            .insert_header((IF_NONE_MATCH, "\"outdated\""))
+            .insert_header((IF_MODIFIED_SINCE, "Sun, 01 Jan 2040 00:00:00 GMT"))
+            .to_http_request();
+
+        assert!(!validators().is_not_modified(&req));
+    }
+
+    #[test]
+    fn modified_since() {
+        let before = TestRequest::default()
+            .insert_header((IF_MODIFIED_SINCE, "Sat, 01 Jan 2022 00:00:00 GMT"))
+            .to_http_request();
+        let after = TestRequest::default()
+            .insert_header((IF_MODIFIED_SINCE, "Sun, 01 Jan 2040 00:00:00 GMT"))
+            .to_http_request();
+
+        assert!(!validators().is_not_modified(&before));
+        assert!(validators().is_not_modified(&after));
+    }
+
+    #[test]
+    fn ignores_modified_since_without_timestamp() {
+        let validators = CacheValidators::new(b"{}", None, true);
+        let req = TestRequest::default()
+            .insert_header((IF_MODIFIED_SINCE, "Sun, 01 Jan 2040 00:00:00 GMT"))
+            .to_http_request();
+
+        assert!(!validators.is_not_modified(&req));
+    }
+}
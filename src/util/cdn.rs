@@ -0,0 +1,162 @@
+use crate::database::models::replica_item::FileReplica;
+use crate::util::env::{parse_strings_from_var, parse_var};
+use actix_web::{HttpResponse, HttpResponseBuilder};
+use std::collections::HashMap;
+
+/// The names of the secondary storage backends, along with the CDN URLs
+/// their files are served at
+fn secondary_cdn_urls() -> Vec<(String, String)> {
+    let backends = parse_strings_from_var("SECONDARY_STORAGE_BACKENDS")
+        .unwrap_or_default();
+    let cdn_urls =
+        parse_strings_from_var("SECONDARY_CDN_URLS").unwrap_or_default();
+
+    backends.into_iter().zip(cdn_urls).collect()
+}
+
+/// Gets the URLs each of the given CDN URLs can be downloaded from: the URL
+/// itself, followed by the URLs of the same file on the secondary storage
+/// backends which hold a copy of it. URLs which aren't on the CDN only have
+/// themselves as a mirror.
+pub async fn mirrored_urls<'a, E>(
+    urls: &[String],
+    exec: E,
+) -> Result<HashMap<String, Vec<String>>, sqlx::Error>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let mut mirrors = urls
+        .iter()
+        .map(|x| (x.clone(), vec![x.clone()]))
+        .collect::<HashMap<_, _>>();
+
+    let secondaries = secondary_cdn_urls();
+    if secondaries.is_empty() {
+        return Ok(mirrors);
+    }
+
+    // File names are encoded in the URLs, but not in the stored file names
+    let cdn_prefix =
+        format!("{}/", parse_var::<String>("CDN_URL").unwrap_or_default());
+    let paths = urls
+        .iter()
+        .filter_map(|url| {
+            let path = url.strip_prefix(&cdn_prefix)?;
+            let file_name = urlencoding::decode(path).ok()?.into_owned();
+            Some((file_name, (url, path)))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let file_names = paths.keys().cloned().collect::<Vec<_>>();
+    for (file_name, replica) in
+        FileReplica::get_stored_replicas(&file_names, exec).await?
+    {
+        let cdn_url = secondaries
+            .iter()
+            .find(|(name, _)| *name == replica)
+            .map(|(_, cdn_url)| cdn_url);
+
+        if let (Some(cdn_url), Some((url, path))) =
+            (cdn_url, paths.get(&file_name))
+        {
+            if let Some(urls) = mirrors.get_mut(*url) {
+                urls.push(format!("{}/{}", cdn_url, path));
+            }
+        }
+    }
+
+    Ok(mirrors)
+}
+
+/// Gets the URLs a single file can be downloaded from, as `mirrored_urls`
+pub async fn file_mirrors<'a, E>(
+    url: &str,
+    exec: E,
+) -> Result<Vec<String>, sqlx::Error>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    Ok(mirrored_urls(&[url.to_string()], exec)
+        .await?
+        .remove(url)
+        .unwrap_or_else(|| vec![url.to_string()]))
+}
+
+/// Redirects to the first of the URLs of a file. The others are listed as
+/// `Link` headers as described in RFC 6249, so that clients can fall back to
+/// them if the download fails.
+pub fn mirrored_redirect(mirrors: &[String]) -> HttpResponseBuilder {
+    let mut response = HttpResponse::TemporaryRedirect();
+
+    if let Some((url, others)) = mirrors.split_first() {
+        response.append_header(("Location", &**url));
+
+        for mirror in others {
+            response.append_header((
+                "Link",
+                format!("<{}>; rel=duplicate", mirror),
+            ));
+        }
+    }
+
+    response
+}
+
+/// Gets the name a file on the CDN is stored under, or `None` if the URL
+/// isn't on the CDN
+pub fn stored_file_name(url: &str) -> Option<String> {
+    let cdn_url = parse_var::<String>("CDN_URL")?;
+    let path = url.strip_prefix(&cdn_url)?.strip_prefix('/')?;
+
+    urlencoding::decode(path).ok().map(|x| x.into_owned())
+}
+
+/// Gets the URL a stored file is downloaded from. Only the last segment of
+/// the name is encoded, like in the URLs of uploaded version files.
+pub fn stored_file_url(cdn_url: &str, file_name: &str) -> String {
+    match file_name.rsplit_once('/') {
+        Some((folder, name)) => {
+            format!("{}/{}/{}", cdn_url, folder, urlencoding::encode(name))
+        }
+        None => format!("{}/{}", cdn_url, file_name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mirrored_redirect, stored_file_url};
+    use actix_web::http::header;
+
+    #[test]
+    fn redirects_list_their_mirrors() {
+        let response = mirrored_redirect(&[
+            "https://cdn.modrinth.com/data/AABBCCDD/mod.jar".to_string(),
+            "https://cdn-2.modrinth.com/data/AABBCCDD/mod.jar".to_string(),
+        ])
+        .finish();
+
+        assert_eq!(
+            response.headers().get(header::LOCATION).unwrap(),
+            "https://cdn.modrinth.com/data/AABBCCDD/mod.jar"
+        );
+        assert_eq!(
+            response
+                .headers()
+                .get_all(header::LINK)
+                .map(|x| x.to_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["<https://cdn-2.modrinth.com/data/AABBCCDD/mod.jar>; rel=duplicate"]
+        );
+    }
+
+    #[test]
+    fn only_file_names_are_encoded_in_urls() {
+        assert_eq!(
+            stored_file_url(
+                "https://cdn.modrinth.com",
+                "data/AABBCCDD/versions/1.0 beta/my mod.jar"
+            ),
+            "https://cdn.modrinth.com/data/AABBCCDD/versions/1.0 beta/my%20mod.jar"
+        );
+    }
+}
@@ -1,8 +1,13 @@
 pub mod auth;
+pub mod blobs;
+pub mod cache;
+pub mod cdn;
 pub mod env;
 pub mod ext;
 pub mod guards;
+pub mod range;
 pub mod routes;
+pub mod spool;
 pub mod time_ser;
 pub mod validate;
 pub mod webhook;
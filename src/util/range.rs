@@ -0,0 +1,67 @@
+use actix_web::http::header::Range;
+use actix_web::{HttpMessage, HttpRequest};
+
+/// The part of a resource a request asks for through its `Range` header
+#[derive(Debug, Eq, PartialEq)]
+pub enum RequestedRange {
+    Full,
+    /// The first and last byte of the range, both inclusive
+    Partial(u64, u64),
+    /// The range starts after the end of the resource
+    Unsatisfiable,
+}
+
+/// Reads the range a request asks for from a resource of `length` bytes.
+/// Requests for several ranges are answered with the whole resource, which
+/// servers are allowed to do instead of sending a multipart response.
+pub fn requested_range(req: &HttpRequest, length: u64) -> RequestedRange {
+    match req.get_header::<Range>() {
+        Some(Range::Bytes(ranges)) if ranges.len() == 1 => {
+            match ranges[0].to_satisfiable_range(length) {
+                Some((start, end)) => RequestedRange::Partial(start, end),
+                None => RequestedRange::Unsatisfiable,
+            }
+        }
+        _ => RequestedRange::Full,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{requested_range, RequestedRange};
+    use actix_web::http::header::RANGE;
+    use actix_web::test::TestRequest;
+
+    fn range(header: &str, length: u64) -> RequestedRange {
+        let req = TestRequest::default()
+            .insert_header((RANGE, header))
+            .to_http_request();
+
+        requested_range(&req, length)
+    }
+
+    #[test]
+    fn no_range() {
+        let req = TestRequest::default().to_http_request();
+
+        assert_eq!(requested_range(&req, 100), RequestedRange::Full);
+    }
+
+    #[test]
+    fn single_ranges() {
+        assert_eq!(range("bytes=0-9", 100), RequestedRange::Partial(0, 9));
+        assert_eq!(range("bytes=90-", 100), RequestedRange::Partial(90, 99));
+        assert_eq!(range("bytes=-10", 100), RequestedRange::Partial(90, 99));
+        assert_eq!(range("bytes=50-500", 100), RequestedRange::Partial(50, 99));
+    }
+
+    #[test]
+    fn unsatisfiable_range() {
+        assert_eq!(range("bytes=100-", 100), RequestedRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn several_ranges() {
+        assert_eq!(range("bytes=0-9,20-29", 100), RequestedRange::Full);
+    }
+}
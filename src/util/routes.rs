@@ -1,5 +1,6 @@
 use crate::routes::project_creation::CreateError;
 use crate::routes::ApiError;
+use crate::util::spool::{SpoolWriter, SpooledFile};
 use actix_multipart::Field;
 use actix_web::web::Payload;
 use actix_web::HttpResponse;
@@ -41,6 +42,25 @@ pub async fn read_from_field(
     Ok(bytes)
 }
 
+/// Like `read_from_field`, but writes the field to a temporary file instead of
+/// holding it in memory
+pub async fn spool_field(
+    field: &mut Field,
+    cap: usize,
+    err_msg: &'static str,
+) -> Result<SpooledFile, CreateError> {
+    let mut spool = SpoolWriter::new().await?;
+    while let Some(chunk) = field.next().await {
+        if spool.size() >= cap as u64 {
+            return Err(CreateError::InvalidInput(String::from(err_msg)));
+        }
+        spool
+            .write(chunk.map_err(CreateError::MultipartError)?)
+            .await?;
+    }
+    Ok(spool.finish()?)
+}
+
 pub(crate) fn ok_or_not_found<T, U>(version_data: Option<T>) -> HttpResponse
 where
     U: From<T> + Serialize,
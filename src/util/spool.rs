@@ -0,0 +1,270 @@
+//! Temporary files uploads are written to while they are received, so that
+//! large files never have to be held in memory as a whole
+use crate::file_hosting::{
+    blocking, FileHost, FileHostingError, FileStream, StreamHasher,
+    UploadFileData,
+};
+use bytes::Bytes;
+use sha2::Digest;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// How much of a spooled file is read into memory at once while streaming it
+const STREAM_CHUNK_SIZE: u64 = 8 * (1 << 20);
+
+/// A path which is deleted when it's dropped
+struct TempPath(PathBuf);
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0) {
+            log::warn!(
+                "Deleting temporary file {} failed: {}",
+                self.0.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Writes a file to a new temporary file piece by piece, hashing it on the way
+pub struct SpoolWriter {
+    path: TempPath,
+    file: Option<std::fs::File>,
+    hasher: StreamHasher,
+    /// Hashes only Maven clients ask for, which file hosts don't compute
+    md5: md5::Md5,
+    sha256: sha2::Sha256,
+    length: u64,
+}
+
+impl SpoolWriter {
+    pub async fn new() -> Result<Self, FileHostingError> {
+        let path =
+            TempPath(std::env::temp_dir().join(format!(
+                "labrinth-upload-{:016x}",
+                rand::random::<u64>()
+            )));
+        let file_path = path.0.clone();
+        let file = blocking(move || std::fs::File::create(file_path)).await?;
+
+        Ok(SpoolWriter {
+            path,
+            file: Some(file),
+            hasher: StreamHasher::new(),
+            md5: md5::Md5::new(),
+            sha256: sha2::Sha256::new(),
+            length: 0,
+        })
+    }
+
+    /// The number of bytes written so far
+    pub fn size(&self) -> u64 {
+        self.length
+    }
+
+    pub async fn write(&mut self, data: Bytes) -> Result<(), FileHostingError> {
+        let mut file = self.file.take().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "An earlier write to the file failed",
+            )
+        })?;
+
+        self.hasher.update(&data);
+        self.md5.update(&data);
+        self.sha256.update(&data);
+        self.length += data.len() as u64;
+
+        self.file = Some(
+            blocking(move || {
+                file.write_all(&data)?;
+                Ok(file)
+            })
+            .await?,
+        );
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<SpooledFile, FileHostingError> {
+        let hashes = self.hasher.finish(self.length)?;
+
+        Ok(SpooledFile {
+            path: self.path,
+            length: self.length,
+            sha1: hashes.content_sha1,
+            sha512: hashes.content_sha512,
+            md5: format!("{:x}", self.md5.finalize()),
+            sha256: format!("{:x}", self.sha256.finalize()),
+        })
+    }
+}
+
+/// A file which was written to disk completely, deleted once it's dropped
+pub struct SpooledFile {
+    path: TempPath,
+    length: u64,
+    sha1: String,
+    sha512: String,
+    md5: String,
+    sha256: String,
+}
+
+impl SpooledFile {
+    pub fn size(&self) -> u64 {
+        self.length
+    }
+
+    /// The SHA1 hash of the file as a hex string
+    pub fn sha1(&self) -> &str {
+        &self.sha1
+    }
+
+    /// The SHA512 hash of the file as a hex string
+    pub fn sha512(&self) -> &str {
+        &self.sha512
+    }
+
+    /// The MD5 hash of the file as a hex string
+    pub fn md5(&self) -> &str {
+        &self.md5
+    }
+
+    /// The SHA256 hash of the file as a hex string
+    pub fn sha256(&self) -> &str {
+        &self.sha256
+    }
+
+    /// Opens the file for blocking reads, such as by the validators
+    pub fn reader(&self) -> std::io::Result<FileReader> {
+        Ok(FileReader::File(std::fs::File::open(&self.path.0)?))
+    }
+
+    /// Reads the file in pieces of a bounded size
+    pub fn stream(&self) -> Result<FileStream, FileHostingError> {
+        let file = std::fs::File::open(&self.path.0)?;
+
+        Ok(Box::pin(futures::stream::try_unfold(
+            file,
+            |file| async move {
+                let (file, data) = blocking(move || {
+                    let mut file = file;
+                    let mut data = Vec::new();
+                    (&mut file)
+                        .take(STREAM_CHUNK_SIZE)
+                        .read_to_end(&mut data)?;
+                    Ok((file, data))
+                })
+                .await?;
+
+                Ok(if data.is_empty() {
+                    None
+                } else {
+                    Some((Bytes::from(data), file))
+                })
+            },
+        )))
+    }
+
+    /// Uploads the file without reading it into memory at once
+    pub async fn upload(
+        &self,
+        file_host: &dyn FileHost,
+        content_type: &str,
+        file_name: &str,
+    ) -> Result<UploadFileData, FileHostingError> {
+        file_host
+            .upload_file_streaming(
+                content_type,
+                file_name,
+                self.length,
+                self.stream()?,
+            )
+            .await
+    }
+}
+
+/// The contents of an archive being validated, either held in memory or read
+/// from a spooled file
+pub enum FileReader {
+    Memory(Cursor<Bytes>),
+    File(std::fs::File),
+}
+
+impl From<Bytes> for FileReader {
+    fn from(data: Bytes) -> Self {
+        FileReader::Memory(Cursor::new(data))
+    }
+}
+
+impl Read for FileReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            FileReader::Memory(x) => x.read(buf),
+            FileReader::File(x) => x.read(buf),
+        }
+    }
+}
+
+impl Seek for FileReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            FileReader::Memory(x) => x.seek(pos),
+            FileReader::File(x) => x.seek(pos),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpoolWriter;
+    use futures::TryStreamExt;
+    use std::io::Read;
+
+    #[actix_rt::test]
+    async fn spools_and_streams_files() {
+        let mut writer = SpoolWriter::new().await.unwrap();
+        writer.write("hello ".into()).await.unwrap();
+        writer.write("world".into()).await.unwrap();
+        let file = writer.finish().unwrap();
+
+        assert_eq!(file.size(), 11);
+        assert_eq!(file.sha1(), sha1::Sha1::from("hello world").hexdigest());
+        assert_eq!(file.md5(), "5eb63bbbe01eeed093cb22bb8f5acdc3");
+        assert_eq!(
+            file.sha256(),
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+
+        let mut contents = String::new();
+        file.reader()
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "hello world");
+
+        let streamed = file
+            .stream()
+            .unwrap()
+            .try_fold(Vec::new(), |mut data, chunk| async move {
+                data.extend_from_slice(&chunk);
+                Ok(data)
+            })
+            .await
+            .unwrap();
+        assert_eq!(streamed, b"hello world");
+    }
+
+    #[actix_rt::test]
+    async fn deletes_dropped_files() {
+        let mut writer = SpoolWriter::new().await.unwrap();
+        writer.write("data".into()).await.unwrap();
+        let file = writer.finish().unwrap();
+        let path = file.path.0.clone();
+
+        assert!(path.exists());
+        drop(file);
+        assert!(!path.exists());
+    }
+}
@@ -1,7 +1,15 @@
+use crate::models::projects::DependencyType;
+use crate::util::spool::FileReader;
+use crate::validate::metadata::{
+    concrete_version, parse_manifest, ModDependency, ModEnvironment,
+    ModMetadata, VersionPredicates, PLATFORM_MOD_IDS,
+};
+use crate::models::projects::ValidationFinding;
 use crate::validate::{
-    SupportedGameVersions, ValidationError, ValidationResult,
+    SupportedGameVersions, ValidationError, ValidationReport,
 };
-use std::io::Cursor;
+use serde::Deserialize;
+use std::collections::BTreeMap;
 use time::OffsetDateTime;
 use zip::ZipArchive;
 
@@ -31,30 +39,107 @@ impl super::Validator for FabricValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
-    ) -> Result<ValidationResult, ValidationError> {
+        archive: &mut ZipArchive<FileReader>,
+    ) -> Result<ValidationReport, ValidationError> {
         archive.by_name("fabric.mod.json").map_err(|_| {
             ValidationError::InvalidInput(
                 "No fabric.mod.json present for Fabric file.".into(),
             )
         })?;
 
+        let mut findings = Vec::new();
+
         if !archive.file_names().any(|name| {
             match_extension_ignore_case(name, &[".refmap.json", ".class"])
         }) {
-            return Ok(ValidationResult::Warning(
+            findings.push(ValidationFinding::warning(
+                "source_file",
                 "Fabric mod file is a source file!",
             ));
         }
 
-        Ok(ValidationResult::Pass)
+        let result = parse_manifest(
+            archive,
+            "fabric.mod.json",
+            parse_fabric_mod_json,
+            &mut findings,
+        );
+
+        Ok(ValidationReport { result, findings })
     }
 }
 
+#[derive(Deserialize)]
+struct FabricModJson {
+    id: String,
+    version: Option<String>,
+    environment: Option<String>,
+    #[serde(default)]
+    depends: BTreeMap<String, VersionPredicates>,
+    #[serde(default)]
+    recommends: BTreeMap<String, VersionPredicates>,
+    #[serde(default)]
+    suggests: BTreeMap<String, VersionPredicates>,
+    #[serde(default)]
+    breaks: BTreeMap<String, VersionPredicates>,
+}
+
+/// Returns `None` if the manifest can't be parsed, as Fabric itself is lenient
+/// about parts of it which don't matter here
+pub(super) fn parse_fabric_mod_json(contents: &str) -> Option<ModMetadata> {
+    let mut manifest: FabricModJson = serde_json::from_str(contents).ok()?;
+
+    let minecraft_range = manifest
+        .depends
+        .remove("minecraft")
+        .map(VersionPredicates::into_range);
+    let loader_range = manifest
+        .depends
+        .remove("fabricloader")
+        .map(VersionPredicates::into_range);
+
+    let dependencies = vec![
+        (manifest.depends, DependencyType::Required),
+        (manifest.recommends, DependencyType::Optional),
+        (manifest.suggests, DependencyType::Optional),
+        (manifest.breaks, DependencyType::Incompatible),
+    ]
+    .into_iter()
+    .flat_map(|(mods, dependency_type)| {
+        mods.into_iter()
+            .map(move |(mod_id, predicates)| ModDependency {
+                mod_id,
+                range: Some(predicates.into_range()),
+                dependency_type: dependency_type.clone(),
+            })
+    })
+    .filter(|x| !PLATFORM_MOD_IDS.contains(&&*x.mod_id))
+    .collect();
+
+    Some(ModMetadata {
+        mod_id: manifest.id,
+        version: concrete_version(manifest.version),
+        minecraft_range,
+        loader_range,
+        dependencies,
+        environment: match manifest.environment.as_deref() {
+            Some("client") => ModEnvironment::Client,
+            Some("server") => ModEnvironment::Server,
+            Some("*") | None => ModEnvironment::Both,
+            Some(_) => ModEnvironment::Unknown,
+        },
+    })
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::models::projects::{DependencyType, ValidationFinding};
+    use crate::validate::metadata::{
+        ModDependency, ModEnvironment, ModMetadata,
+    };
     use crate::validate::{
-        test_util::make_dummy_zip, ValidationError, ValidationResult, Validator,
+        test_util::{make_dummy_zip, make_dummy_zip_with_contents},
+        ValidationError, ValidationResult, Validator,
     };
 
     use super::FabricValidator;
@@ -69,7 +154,7 @@ mod tests {
         .unwrap();
 
         assert_eq!(
-            FabricValidator.validate(&mut zip).unwrap(),
+            FabricValidator.validate(&mut zip).unwrap().result,
             ValidationResult::Pass
         );
     }
@@ -83,7 +168,7 @@ mod tests {
         .unwrap();
 
         assert_eq!(
-            FabricValidator.validate(&mut zip).unwrap(),
+            FabricValidator.validate(&mut zip).unwrap().result,
             ValidationResult::Pass
         );
     }
@@ -100,12 +185,79 @@ mod tests {
     }
     #[test]
     fn missing_refmap_and_class_files() {
-        let mut zip =
-            make_dummy_zip(&["fabric.mod.json"]).unwrap();
+        let mut zip = make_dummy_zip(&["fabric.mod.json"]).unwrap();
+
+        let report = FabricValidator.validate(&mut zip).unwrap();
+
+        assert!(!report.is_passed());
+        assert!(report.findings.iter().any(|x| x.code == "source_file"));
+    }
+    #[test]
+    fn unreadable_manifest() {
+        let mut zip = make_dummy_zip_with_contents(&[
+            ("fabric.mod.json", "{ not json"),
+            ("Test.class", ""),
+        ])
+        .unwrap();
+
+        let report = FabricValidator.validate(&mut zip).unwrap();
+
+        assert!(report.is_passed());
+        assert_eq!(report.result, ValidationResult::Pass);
+        assert_eq!(
+            report.findings,
+            vec![ValidationFinding::info(
+                "unreadable_manifest",
+                "fabric.mod.json could not be read"
+            )
+            .at("fabric.mod.json")]
+        );
+    }
+    #[test]
+    fn mod_metadata() {
+        let mut zip = make_dummy_zip_with_contents(&[
+            (
+                "fabric.mod.json",
+                r#"{
+                    "schemaVersion": 1,
+                    "id": "example",
+                    "version": "1.2.0",
+                    "environment": "client",
+                    "depends": {
+                        "fabricloader": ">=0.14.0",
+                        "minecraft": ["1.18.x", "1.19"],
+                        "fabric-api": "*"
+                    },
+                    "breaks": {
+                        "optifabric": "*"
+                    }
+                }"#,
+            ),
+            ("Test.class", ""),
+        ])
+        .unwrap();
 
         assert_eq!(
-            FabricValidator.validate(&mut zip).unwrap(),
-            ValidationResult::Warning("Fabric mod file is a source file!")
+            FabricValidator.validate(&mut zip).unwrap().result,
+            ValidationResult::PassWithModMetadata(ModMetadata {
+                mod_id: "example".to_string(),
+                version: Some("1.2.0".to_string()),
+                minecraft_range: Some("1.18.x || 1.19".to_string()),
+                loader_range: Some(">=0.14.0".to_string()),
+                dependencies: vec![
+                    ModDependency {
+                        mod_id: "fabric-api".to_string(),
+                        range: Some("*".to_string()),
+                        dependency_type: DependencyType::Required,
+                    },
+                    ModDependency {
+                        mod_id: "optifabric".to_string(),
+                        range: Some("*".to_string()),
+                        dependency_type: DependencyType::Incompatible,
+                    },
+                ],
+                environment: ModEnvironment::Client,
+            })
         );
     }
 }
@@ -1,7 +1,15 @@
+use crate::models::projects::DependencyType;
+use crate::util::spool::FileReader;
+use crate::validate::metadata::{
+    concrete_version, parse_manifest, ModDependency, ModEnvironment,
+    ModMetadata, PLATFORM_MOD_IDS,
+};
+use crate::models::projects::ValidationFinding;
 use crate::validate::{
-    SupportedGameVersions, ValidationError, ValidationResult,
+    SupportedGameVersions, ValidationError, ValidationReport,
 };
-use std::io::Cursor;
+use serde::Deserialize;
+use std::collections::HashMap;
 use time::OffsetDateTime;
 use zip::ZipArchive;
 
@@ -31,29 +39,124 @@ impl super::Validator for ForgeValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
-    ) -> Result<ValidationResult, ValidationError> {
+        archive: &mut ZipArchive<FileReader>,
+    ) -> Result<ValidationReport, ValidationError> {
+        let mut findings = Vec::new();
+
         if archive.by_name("META-INF/mods.toml").is_err() {
-            return Ok(ValidationResult::Warning(
-                "No mods.toml present for Forge file.",
-            ));
+            findings.push(
+                ValidationFinding::warning(
+                    "missing_manifest",
+                    "No mods.toml present for Forge file.",
+                )
+                .at("META-INF/mods.toml"),
+            );
         }
 
         if !archive
             .file_names()
             .any(|name| match_extension_ignore_case(name, &[".class"]))
         {
-            return Ok(ValidationResult::Warning(
+            findings.push(ValidationFinding::warning(
+                "source_file",
                 "Forge mod file is a source file!",
             ));
         }
 
         //TODO: Check if file is a dev JAR?
 
-        Ok(ValidationResult::Pass)
+        let result = parse_manifest(
+            archive,
+            "META-INF/mods.toml",
+            parse_mods_toml,
+            &mut findings,
+        );
+
+        Ok(ValidationReport { result, findings })
     }
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModsToml {
+    loader_version: Option<String>,
+    mods: Vec<ModsTomlMod>,
+    #[serde(default)]
+    dependencies: HashMap<String, Vec<ModsTomlDependency>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModsTomlMod {
+    mod_id: String,
+    version: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModsTomlDependency {
+    mod_id: String,
+    #[serde(default)]
+    mandatory: bool,
+    version_range: Option<String>,
+    side: Option<String>,
+}
+
+/// Returns the metadata of the first mod declared in the manifest, or `None`
+/// if it can't be parsed
+pub(super) fn parse_mods_toml(contents: &str) -> Option<ModMetadata> {
+    let mut manifest: ModsToml = toml::from_str(contents).ok()?;
+    let first_mod = manifest.mods.into_iter().next()?;
+    let declared_dependencies = manifest
+        .dependencies
+        .remove(&first_mod.mod_id)
+        .unwrap_or_default();
+
+    let range_of = |mod_id: &str| {
+        declared_dependencies
+            .iter()
+            .find(|x| x.mod_id == mod_id)
+            .and_then(|x| x.version_range.clone())
+    };
+    let minecraft_range = range_of("minecraft");
+    let loader_range = range_of("forge").or(manifest.loader_version);
+
+    // Forge doesn't declare an environment, but mods which only need to be on
+    // one side mark their dependency on Minecraft with that side
+    let environment = match declared_dependencies
+        .iter()
+        .find(|x| x.mod_id == "minecraft")
+        .and_then(|x| x.side.as_deref())
+    {
+        Some("CLIENT") => ModEnvironment::Client,
+        Some("SERVER") => ModEnvironment::Server,
+        _ => ModEnvironment::Unknown,
+    };
+
+    let dependencies = declared_dependencies
+        .into_iter()
+        .filter(|x| !PLATFORM_MOD_IDS.contains(&&*x.mod_id))
+        .map(|x| ModDependency {
+            mod_id: x.mod_id,
+            range: x.version_range,
+            dependency_type: if x.mandatory {
+                DependencyType::Required
+            } else {
+                DependencyType::Optional
+            },
+        })
+        .collect();
+
+    Some(ModMetadata {
+        mod_id: first_mod.mod_id,
+        version: concrete_version(first_mod.version),
+        minecraft_range,
+        loader_range,
+        dependencies,
+        environment,
+    })
+}
+
 pub struct LegacyForgeValidator;
 
 impl super::Validator for LegacyForgeValidator {
@@ -79,35 +182,134 @@ impl super::Validator for LegacyForgeValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
-    ) -> Result<ValidationResult, ValidationError> {
+        archive: &mut ZipArchive<FileReader>,
+    ) -> Result<ValidationReport, ValidationError> {
+        let mut findings = Vec::new();
+
         if archive.by_name("mcmod.info").is_err() {
-            return Ok(ValidationResult::Warning(
-                "Forge mod file does not contain mcmod.info!",
-            ));
-        };
+            findings.push(
+                ValidationFinding::warning(
+                    "missing_manifest",
+                    "Forge mod file does not contain mcmod.info!",
+                )
+                .at("mcmod.info"),
+            );
+        }
 
         if !archive
             .file_names()
             .any(|name| match_extension_ignore_case(name, &[".class"]))
         {
-            return Ok(ValidationResult::Warning(
+            findings.push(ValidationFinding::warning(
+                "source_file",
                 "Forge mod file is a source file!",
             ));
         }
 
         //TODO: Check if file is a dev JAR?
 
-        Ok(ValidationResult::Pass)
+        let result = parse_manifest(
+            archive,
+            "mcmod.info",
+            parse_mcmod_info,
+            &mut findings,
+        );
+
+        Ok(ValidationReport { result, findings })
+    }
+}
+
+/// mcmod.info is either a list of mods, or an object containing that list
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum McModInfo {
+    List(Vec<McModInfoMod>),
+    Object {
+        #[serde(rename = "modList")]
+        mod_list: Vec<McModInfoMod>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct McModInfoMod {
+    #[serde(rename = "modid")]
+    mod_id: String,
+    version: Option<String>,
+    #[serde(rename = "mcversion")]
+    mc_version: Option<String>,
+    #[serde(default)]
+    required_mods: Vec<String>,
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+/// Returns the metadata of the first mod declared in the manifest, or `None`
+/// if it can't be parsed
+fn parse_mcmod_info(contents: &str) -> Option<ModMetadata> {
+    let manifest: McModInfo = serde_json::from_str(contents).ok()?;
+    let first_mod = match manifest {
+        McModInfo::List(mods) => mods,
+        McModInfo::Object { mod_list } => mod_list,
     }
+    .into_iter()
+    .next()?;
+
+    // Dependencies are written as `modid` or `modid@versionRange`
+    let split = |dependency: &str| match dependency.split_once('@') {
+        Some((mod_id, range)) => (mod_id.to_string(), Some(range.to_string())),
+        None => (dependency.to_string(), None),
+    };
+
+    let mut loader_range = None;
+    let mut dependencies: Vec<ModDependency> = Vec::new();
+    for (dependency, dependency_type) in first_mod
+        .required_mods
+        .iter()
+        .map(|x| (x, DependencyType::Required))
+        .chain(
+            first_mod
+                .dependencies
+                .iter()
+                .map(|x| (x, DependencyType::Optional)),
+        )
+    {
+        let (mod_id, range) = split(dependency);
+
+        if mod_id == "Forge" || mod_id == "forge" {
+            loader_range = loader_range.or(range);
+        } else if !PLATFORM_MOD_IDS.contains(&&*mod_id)
+            && dependencies.iter().all(|x| x.mod_id != mod_id)
+        {
+            dependencies.push(ModDependency {
+                mod_id,
+                range,
+                dependency_type,
+            });
+        }
+    }
+
+    Some(ModMetadata {
+        mod_id: first_mod.mod_id,
+        version: concrete_version(first_mod.version),
+        minecraft_range: concrete_version(first_mod.mc_version),
+        loader_range,
+        dependencies,
+        environment: ModEnvironment::Unknown,
+    })
 }
 
 
 #[cfg(test)]
 mod tests {
     mod modern {
+        use crate::models::projects::DependencyType;
+        use crate::validate::metadata::{
+            ModDependency, ModEnvironment, ModMetadata,
+        };
         use crate::validate::{
-            test_util::make_dummy_zip, ValidationResult, Validator,
+            test_util::{make_dummy_zip, make_dummy_zip_with_contents},
+            ValidationResult, Validator,
         };
         use crate::validate::forge::ForgeValidator;
 
@@ -120,7 +322,7 @@ mod tests {
             .unwrap();
 
             assert_eq!(
-                ForgeValidator.validate(&mut zip).unwrap(),
+                ForgeValidator.validate(&mut zip).unwrap().result,
                 ValidationResult::Pass
             );
         }
@@ -133,7 +335,7 @@ mod tests {
             .unwrap();
 
             assert_eq!(
-                ForgeValidator.validate(&mut zip).unwrap(),
+                ForgeValidator.validate(&mut zip).unwrap().result,
                 ValidationResult::Pass
             );
         }
@@ -142,25 +344,83 @@ mod tests {
             let mut zip =
                 make_dummy_zip(&["Test.class"]).unwrap();
 
-            assert_eq!(
-                ForgeValidator.validate(&mut zip).unwrap(),
-                ValidationResult::Warning("No mods.toml present for Forge file.")
-            );
+            let report = ForgeValidator.validate(&mut zip).unwrap();
+
+            assert!(!report.is_passed());
+            assert!(report
+                .findings
+                .iter()
+                .any(|x| x.code == "missing_manifest"));
         }
         #[test]
         fn missing_class_files() {
             let mut zip =
                 make_dummy_zip(&["META-INF/mods.toml"]).unwrap();
 
+            let report = ForgeValidator.validate(&mut zip).unwrap();
+
+            assert!(!report.is_passed());
+            assert!(report.findings.iter().any(|x| x.code == "source_file"));
+        }
+        #[test]
+        fn mod_metadata() {
+            let mut zip = make_dummy_zip_with_contents(&[
+                (
+                    "META-INF/mods.toml",
+                    r#"
+                    modLoader = "javafml"
+                    loaderVersion = "[40,)"
+
+                    [[mods]]
+                    modId = "example"
+                    version = "${file.jarVersion}"
+
+                    [[dependencies.example]]
+                    modId = "forge"
+                    mandatory = true
+                    versionRange = "[40.1.0,)"
+
+                    [[dependencies.example]]
+                    modId = "minecraft"
+                    mandatory = true
+                    versionRange = "[1.18.2,1.19)"
+                    side = "CLIENT"
+
+                    [[dependencies.example]]
+                    modId = "jei"
+                    mandatory = false
+                    versionRange = "[9.7,)"
+                    "#,
+                ),
+                ("Test.class", ""),
+            ])
+            .unwrap();
+
             assert_eq!(
-                ForgeValidator.validate(&mut zip).unwrap(),
-                ValidationResult::Warning("Forge mod file is a source file!")
+                ForgeValidator.validate(&mut zip).unwrap().result,
+                ValidationResult::PassWithModMetadata(ModMetadata {
+                    mod_id: "example".to_string(),
+                    version: None,
+                    minecraft_range: Some("[1.18.2,1.19)".to_string()),
+                    loader_range: Some("[40.1.0,)".to_string()),
+                    dependencies: vec![ModDependency {
+                        mod_id: "jei".to_string(),
+                        range: Some("[9.7,)".to_string()),
+                        dependency_type: DependencyType::Optional,
+                    }],
+                    environment: ModEnvironment::Client,
+                })
             );
         }
     }
     mod legacy {
+        use crate::models::projects::DependencyType;
+        use crate::validate::metadata::{
+            ModDependency, ModEnvironment, ModMetadata,
+        };
         use crate::validate::{
-            test_util::make_dummy_zip, ValidationResult, Validator,
+            test_util::{make_dummy_zip, make_dummy_zip_with_contents},
+            ValidationResult, Validator,
         };
         use crate::validate::forge::LegacyForgeValidator;
 
@@ -173,7 +433,7 @@ mod tests {
             .unwrap();
 
             assert_eq!(
-                LegacyForgeValidator.validate(&mut zip).unwrap(),
+                LegacyForgeValidator.validate(&mut zip).unwrap().result,
                 ValidationResult::Pass
             );
         }
@@ -186,7 +446,7 @@ mod tests {
             .unwrap();
 
             assert_eq!(
-                LegacyForgeValidator.validate(&mut zip).unwrap(),
+                LegacyForgeValidator.validate(&mut zip).unwrap().result,
                 ValidationResult::Pass
             );
         }
@@ -196,19 +456,62 @@ mod tests {
                 make_dummy_zip(&["Test.class"]).unwrap();
 
 
-            assert_eq!(
-                LegacyForgeValidator.validate(&mut zip).unwrap(),
-                ValidationResult::Warning("Forge mod file does not contain mcmod.info!")
-            );
+            let report = LegacyForgeValidator.validate(&mut zip).unwrap();
+
+            assert!(!report.is_passed());
+            assert!(report
+                .findings
+                .iter()
+                .any(|x| x.code == "missing_manifest"));
         }
         #[test]
         fn missing_class_files() {
             let mut zip =
                 make_dummy_zip(&["mcmod.info"]).unwrap();
 
+            let report = LegacyForgeValidator.validate(&mut zip).unwrap();
+
+            assert!(!report.is_passed());
+            assert!(report.findings.iter().any(|x| x.code == "source_file"));
+        }
+        #[test]
+        fn mod_metadata() {
+            let mut zip = make_dummy_zip_with_contents(&[
+                (
+                    "mcmod.info",
+                    r#"[{
+                        "modid": "example",
+                        "version": "2.0.1",
+                        "mcversion": "1.12.2",
+                        "requiredMods": ["Forge@[14.23.5.2768,)", "baubles"],
+                        "dependencies": ["baubles", "jei@[4.15,)"]
+                    }]"#,
+                ),
+                ("Test.class", ""),
+            ])
+            .unwrap();
+
             assert_eq!(
-                LegacyForgeValidator.validate(&mut zip).unwrap(),
-                ValidationResult::Warning("Forge mod file is a source file!")
+                LegacyForgeValidator.validate(&mut zip).unwrap().result,
+                ValidationResult::PassWithModMetadata(ModMetadata {
+                    mod_id: "example".to_string(),
+                    version: Some("2.0.1".to_string()),
+                    minecraft_range: Some("1.12.2".to_string()),
+                    loader_range: Some("[14.23.5.2768,)".to_string()),
+                    dependencies: vec![
+                        ModDependency {
+                            mod_id: "baubles".to_string(),
+                            range: None,
+                            dependency_type: DependencyType::Required,
+                        },
+                        ModDependency {
+                            mod_id: "jei".to_string(),
+                            range: Some("[4.15,)".to_string()),
+                            dependency_type: DependencyType::Optional,
+                        },
+                    ],
+                    environment: ModEnvironment::Unknown,
+                })
             );
         }
     }
@@ -0,0 +1,327 @@
+use crate::database::models::categories::GameVersion as KnownGameVersion;
+use crate::models::projects::GameVersion;
+
+/// A range of Minecraft versions declared in a mod manifest. Supports the
+/// version predicates used by Fabric and Quilt (`>=1.18.2`, `1.18.x`, `~1.18`,
+/// alternatives joined by `||`) and the Maven ranges used by Forge
+/// (`[1.18.2,1.19)`). Bounds are compared by the release dates of the versions.
+struct GameVersionRange {
+    /// The range contains a version if all predicates of any alternative match
+    alternatives: Vec<Vec<Predicate>>,
+}
+
+enum Predicate {
+    Any,
+    Exact(String),
+    /// Matches the version itself and all versions starting with `{version}.`
+    Prefix(String),
+    AtLeast {
+        version: String,
+        inclusive: bool,
+    },
+    AtMost {
+        version: String,
+        inclusive: bool,
+    },
+}
+
+/// Returns the selected game versions which are outside of a range declared in
+/// a manifest. Versions which can't be compared, because either they or the
+/// bounds of the range are unknown, are assumed to be inside of it.
+pub fn versions_outside_range<'a>(
+    range: &str,
+    game_versions: &'a [GameVersion],
+    all_game_versions: &[KnownGameVersion],
+) -> Vec<&'a GameVersion> {
+    let range = match GameVersionRange::parse(range) {
+        Some(range) => range,
+        None => return Vec::new(),
+    };
+
+    game_versions
+        .iter()
+        .filter(|x| {
+            all_game_versions
+                .iter()
+                .find(|y| y.version == x.0)
+                .and_then(|version| range.contains(version, all_game_versions))
+                == Some(false)
+        })
+        .collect()
+}
+
+impl GameVersionRange {
+    fn parse(range: &str) -> Option<Self> {
+        let range = range.trim();
+
+        let alternatives = if range.starts_with('[') || range.starts_with('(') {
+            parse_maven_range(range)?
+        } else {
+            range
+                .split("||")
+                .map(|alternative| {
+                    alternative
+                        .split_whitespace()
+                        .map(parse_predicate)
+                        .collect::<Option<Vec<_>>>()
+                        .map(|x| x.into_iter().flatten().collect())
+                })
+                .collect::<Option<Vec<_>>>()?
+        };
+
+        Some(GameVersionRange { alternatives })
+    }
+
+    /// Returns `None` if the version can't be compared to the range
+    fn contains(
+        &self,
+        version: &KnownGameVersion,
+        all_game_versions: &[KnownGameVersion],
+    ) -> Option<bool> {
+        let mut unknown = false;
+
+        for alternative in &self.alternatives {
+            let mut matches = Some(true);
+            for predicate in alternative {
+                match predicate.matches(version, all_game_versions) {
+                    Some(true) => {}
+                    Some(false) => {
+                        matches = Some(false);
+                        break;
+                    }
+                    None => matches = None,
+                }
+            }
+
+            match matches {
+                Some(true) => return Some(true),
+                Some(false) => {}
+                None => unknown = true,
+            }
+        }
+
+        if unknown {
+            None
+        } else {
+            Some(false)
+        }
+    }
+}
+
+impl Predicate {
+    fn matches(
+        &self,
+        version: &KnownGameVersion,
+        all_game_versions: &[KnownGameVersion],
+    ) -> Option<bool> {
+        let date_of = |bound: &str| {
+            all_game_versions
+                .iter()
+                .find(|x| x.version == bound)
+                .map(|x| x.date)
+        };
+
+        match self {
+            Predicate::Any => Some(true),
+            Predicate::Exact(exact) => Some(&version.version == exact),
+            Predicate::Prefix(prefix) => Some(
+                &version.version == prefix
+                    || version.version.starts_with(&format!("{}.", prefix)),
+            ),
+            Predicate::AtLeast {
+                version: bound,
+                inclusive,
+            } => date_of(bound).map(|date| {
+                version.date > date || (*inclusive && version.date == date)
+            }),
+            Predicate::AtMost {
+                version: bound,
+                inclusive,
+            } => date_of(bound).map(|date| {
+                version.date < date || (*inclusive && version.date == date)
+            }),
+        }
+    }
+}
+
+/// Parses a single predicate, which may expand into several
+fn parse_predicate(predicate: &str) -> Option<Vec<Predicate>> {
+    let at_least = |version: &str, inclusive| Predicate::AtLeast {
+        version: version.to_string(),
+        inclusive,
+    };
+    let at_most = |version: &str, inclusive| Predicate::AtMost {
+        version: version.to_string(),
+        inclusive,
+    };
+    // The first `parts` components of a version, such as `1.18` for `1.18.2`
+    let prefix = |version: &str, parts| {
+        Predicate::Prefix(
+            version.split('.').take(parts).collect::<Vec<_>>().join("."),
+        )
+    };
+
+    let predicates = if predicate == "*" || predicate == "x" {
+        vec![Predicate::Any]
+    } else if let Some(version) = predicate.strip_prefix(">=") {
+        vec![at_least(version, true)]
+    } else if let Some(version) = predicate.strip_prefix("<=") {
+        vec![at_most(version, true)]
+    } else if let Some(version) = predicate.strip_prefix('>') {
+        vec![at_least(version, false)]
+    } else if let Some(version) = predicate.strip_prefix('<') {
+        vec![at_most(version, false)]
+    } else if let Some(version) = predicate.strip_prefix('~') {
+        vec![at_least(version, true), prefix(version, 2)]
+    } else if let Some(version) = predicate.strip_prefix('^') {
+        vec![at_least(version, true), prefix(version, 1)]
+    } else {
+        let version = predicate.strip_prefix('=').unwrap_or(predicate);
+
+        match version
+            .strip_suffix(".x")
+            .or_else(|| version.strip_suffix(".X"))
+            .or_else(|| version.strip_suffix(".*"))
+        {
+            Some(version) => vec![Predicate::Prefix(version.to_string())],
+            None => vec![Predicate::Exact(version.to_string())],
+        }
+    };
+
+    if predicates.iter().any(|x| match x {
+        Predicate::Exact(version)
+        | Predicate::Prefix(version)
+        | Predicate::AtLeast { version, .. }
+        | Predicate::AtMost { version, .. } => version.is_empty(),
+        Predicate::Any => false,
+    }) {
+        return None;
+    }
+
+    Some(predicates)
+}
+
+/// Parses Maven version ranges, such as `[1.16.5,1.17),[1.18,)` or `[1.18.2]`
+fn parse_maven_range(range: &str) -> Option<Vec<Vec<Predicate>>> {
+    let mut alternatives = Vec::new();
+    let mut rest = range;
+
+    while !rest.is_empty() {
+        let inclusive_lower = match rest.chars().next()? {
+            '[' => true,
+            '(' => false,
+            _ => return None,
+        };
+        let end = rest.find(|c| c == ']' || c == ')')?;
+        let inclusive_upper = rest[end..].starts_with(']');
+        let bounds = &rest[1..end];
+
+        let mut alternative = Vec::new();
+        match bounds.split_once(',') {
+            Some((lower, upper)) => {
+                let (lower, upper) = (lower.trim(), upper.trim());
+                if !lower.is_empty() {
+                    alternative.push(Predicate::AtLeast {
+                        version: lower.to_string(),
+                        inclusive: inclusive_lower,
+                    });
+                }
+                if !upper.is_empty() {
+                    alternative.push(Predicate::AtMost {
+                        version: upper.to_string(),
+                        inclusive: inclusive_upper,
+                    });
+                }
+            }
+            None => {
+                alternative.push(Predicate::Exact(bounds.trim().to_string()))
+            }
+        }
+        alternatives.push(alternative);
+
+        rest = rest[end + 1..]
+            .trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    }
+
+    Some(alternatives)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::versions_outside_range;
+    use crate::database::models::categories::GameVersion as KnownGameVersion;
+    use crate::database::models::GameVersionId;
+    use crate::models::projects::GameVersion;
+    use time::OffsetDateTime;
+
+    fn known_versions() -> Vec<KnownGameVersion> {
+        [
+            ("1.16.5", 1610546400),
+            ("1.17", 1623146400),
+            ("1.17.1", 1625490000),
+            ("1.18", 1638280800),
+            ("1.18.1", 1639058400),
+            ("1.18.2", 1646060400),
+            ("22w14a", 1649253600),
+            ("1.19", 1654610400),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(id, (version, date))| KnownGameVersion {
+            id: GameVersionId(id as i32),
+            version: version.to_string(),
+            version_type: "release".to_string(),
+            date: OffsetDateTime::from_unix_timestamp(*date),
+            major: false,
+        })
+        .collect()
+    }
+
+    fn outside(range: &str, versions: &[&str]) -> Vec<String> {
+        let versions = versions
+            .iter()
+            .map(|x| GameVersion(x.to_string()))
+            .collect::<Vec<_>>();
+
+        versions_outside_range(range, &versions, &known_versions())
+            .into_iter()
+            .map(|x| x.0.clone())
+            .collect()
+    }
+
+    #[test]
+    fn fabric_predicates() {
+        assert!(outside("1.18.x", &["1.18", "1.18.2"]).is_empty());
+        assert_eq!(outside("1.18.x", &["1.16.5", "1.18.1"]), vec!["1.16.5"]);
+        assert_eq!(outside(">=1.18.2", &["1.18.1", "1.19"]), vec!["1.18.1"]);
+        assert_eq!(outside("~1.18.1", &["1.18.2", "1.19"]), vec!["1.19"]);
+        assert!(outside("*", &["1.16.5"]).is_empty());
+    }
+
+    #[test]
+    fn fabric_alternatives() {
+        assert_eq!(
+            outside("1.17.x || >=1.18.2 <1.19", &["1.17.1", "1.18", "22w14a"]),
+            vec!["1.18"]
+        );
+    }
+
+    #[test]
+    fn maven_ranges() {
+        assert_eq!(
+            outside("[1.18.2,1.19)", &["1.18.2", "22w14a", "1.19"]),
+            vec!["1.19"]
+        );
+        assert_eq!(
+            outside("[1.16.5],[1.18,)", &["1.16.5", "1.17", "1.19"]),
+            vec!["1.17"]
+        );
+    }
+
+    #[test]
+    fn unknown_versions_are_ignored() {
+        assert!(outside(">=1.20", &["1.16.5"]).is_empty());
+        assert!(outside("1.18.x", &["1.12.2"]).is_empty());
+        assert!(outside("[1.18", &["1.16.5"]).is_empty());
+    }
+}
@@ -1,7 +1,13 @@
+use crate::models::projects::DependencyType;
+use crate::util::spool::FileReader;
+use crate::validate::metadata::{
+    concrete_version, parse_manifest, ModDependency, ModEnvironment,
+    ModMetadata,
+};
 use crate::validate::{
-    SupportedGameVersions, ValidationError, ValidationResult,
+    SupportedGameVersions, ValidationError, ValidationReport,
 };
-use std::io::Cursor;
+use serde::Deserialize;
 use zip::ZipArchive;
 
 pub struct LiteLoaderValidator;
@@ -25,22 +31,69 @@ impl super::Validator for LiteLoaderValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
-    ) -> Result<ValidationResult, ValidationError> {
+        archive: &mut ZipArchive<FileReader>,
+    ) -> Result<ValidationReport, ValidationError> {
         archive.by_name("litemod.json").map_err(|_| {
             ValidationError::InvalidInput(
                 "No litemod.json present for LiteLoader file.".into(),
             )
         })?;
 
-        Ok(ValidationResult::Pass)
+        let mut findings = Vec::new();
+        let result = parse_manifest(
+            archive,
+            "litemod.json",
+            parse_litemod_json,
+            &mut findings,
+        );
+
+        Ok(ValidationReport { result, findings })
     }
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LiteModJson {
+    name: String,
+    version: Option<String>,
+    #[serde(rename = "mcversion")]
+    mc_version: Option<String>,
+    #[serde(default)]
+    depends_on: Vec<String>,
+}
+
+/// LiteLoader mods are identified by their name, and only declare the ids of
+/// their dependencies. Returns `None` if the manifest can't be parsed.
+fn parse_litemod_json(contents: &str) -> Option<ModMetadata> {
+    let manifest: LiteModJson = serde_json::from_str(contents).ok()?;
+
+    Some(ModMetadata {
+        mod_id: manifest.name,
+        version: concrete_version(manifest.version),
+        minecraft_range: concrete_version(manifest.mc_version),
+        loader_range: None,
+        dependencies: manifest
+            .depends_on
+            .into_iter()
+            .map(|mod_id| ModDependency {
+                mod_id,
+                range: None,
+                dependency_type: DependencyType::Required,
+            })
+            .collect(),
+        environment: ModEnvironment::Client,
+    })
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::models::projects::DependencyType;
+    use crate::validate::metadata::{
+        ModDependency, ModEnvironment, ModMetadata,
+    };
     use crate::validate::{
-        test_util::make_dummy_zip, ValidationError, ValidationResult, Validator,
+        test_util::{make_dummy_zip, make_dummy_zip_with_contents},
+        ValidationError, ValidationResult, Validator,
     };
 
     use super::LiteLoaderValidator;
@@ -50,7 +103,7 @@ mod tests {
         let mut zip = make_dummy_zip(&["litemod.json"]).unwrap();
 
         assert_eq!(
-            LiteLoaderValidator.validate(&mut zip).unwrap(),
+            LiteLoaderValidator.validate(&mut zip).unwrap().result,
             ValidationResult::Pass
         );
     }
@@ -64,4 +117,34 @@ mod tests {
             if error == "No litemod.json present for LiteLoader file."
         ));
     }
+    #[test]
+    fn mod_metadata() {
+        let mut zip = make_dummy_zip_with_contents(&[(
+            "litemod.json",
+            r#"{
+                "name": "example",
+                "version": "1.0",
+                "mcversion": "1.12.2",
+                "revision": "1",
+                "dependsOn": ["voxelmap"]
+            }"#,
+        )])
+        .unwrap();
+
+        assert_eq!(
+            LiteLoaderValidator.validate(&mut zip).unwrap().result,
+            ValidationResult::PassWithModMetadata(ModMetadata {
+                mod_id: "example".to_string(),
+                version: Some("1.0".to_string()),
+                minecraft_range: Some("1.12.2".to_string()),
+                loader_range: None,
+                dependencies: vec![ModDependency {
+                    mod_id: "voxelmap".to_string(),
+                    range: None,
+                    dependency_type: DependencyType::Required,
+                }],
+                environment: ModEnvironment::Client,
+            })
+        );
+    }
 }
@@ -0,0 +1,114 @@
+use crate::models::projects::{DependencyType, ValidationFinding};
+use crate::util::spool::FileReader;
+use crate::validate::ValidationResult;
+use serde::Deserialize;
+use std::io::Read;
+use zip::ZipArchive;
+
+/// Data declared in the manifest of a mod file, such as `fabric.mod.json` or
+/// `META-INF/mods.toml`. Version ranges are kept in the loader's own syntax.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ModMetadata {
+    pub mod_id: String,
+    /// The version of the mod, if the manifest declares a concrete one
+    pub version: Option<String>,
+    /// The range of Minecraft versions the mod declares support for
+    pub minecraft_range: Option<String>,
+    /// The range of loader versions the mod declares support for
+    pub loader_range: Option<String>,
+    pub dependencies: Vec<ModDependency>,
+    pub environment: ModEnvironment,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ModDependency {
+    pub mod_id: String,
+    pub range: Option<String>,
+    pub dependency_type: DependencyType,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ModEnvironment {
+    Client,
+    Server,
+    Both,
+    Unknown,
+}
+
+/// Mod ids which refer to the game or the loader itself rather than to a
+/// dependency on another mod
+pub const PLATFORM_MOD_IDS: &[&str] = &[
+    "minecraft",
+    "java",
+    "fabricloader",
+    "fabric-loader",
+    "quilt_loader",
+    "forge",
+    "liteloader",
+];
+
+/// Fabric and Quilt accept either a single version predicate or a list of
+/// them, any of which has to match
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(super) enum VersionPredicates {
+    Single(String),
+    Any(Vec<String>),
+}
+
+impl VersionPredicates {
+    pub fn into_range(self) -> String {
+        match self {
+            VersionPredicates::Single(predicate) => predicate,
+            VersionPredicates::Any(predicates) => predicates.join(" || "),
+        }
+    }
+}
+
+/// Reads a manifest from the archive, returning `None` if it doesn't exist
+/// or isn't valid UTF-8
+pub(super) fn read_manifest(
+    archive: &mut ZipArchive<FileReader>,
+    name: &str,
+) -> Option<String> {
+    let mut file = archive.by_name(name).ok()?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).ok()?;
+
+    Some(contents)
+}
+
+/// Reads and parses a manifest from the archive. Manifests which exist but
+/// can't be parsed are reported, but don't fail validation as the loader may
+/// still accept them.
+pub(super) fn parse_manifest(
+    archive: &mut ZipArchive<FileReader>,
+    name: &str,
+    parse: impl FnOnce(&str) -> Option<ModMetadata>,
+    findings: &mut Vec<ValidationFinding>,
+) -> ValidationResult {
+    let contents = match read_manifest(archive, name) {
+        Some(contents) => contents,
+        None => return ValidationResult::Pass,
+    };
+
+    match parse(&contents) {
+        Some(metadata) => ValidationResult::PassWithModMetadata(metadata),
+        None => {
+            findings.push(
+                ValidationFinding::info(
+                    "unreadable_manifest",
+                    format!("{} could not be read", name),
+                )
+                .at(name),
+            );
+            ValidationResult::Pass
+        }
+    }
+}
+
+/// Placeholders such as `${file.jarVersion}` are filled in by build tools or
+/// the loader and don't say anything about the actual version
+pub(super) fn concrete_version(version: Option<String>) -> Option<String> {
+    version.filter(|x| !x.trim().is_empty() && !x.contains("${"))
+}
@@ -1,22 +1,38 @@
-use crate::models::pack::PackFormat;
-use crate::models::projects::{GameVersion, Loader};
+use crate::models::pack::{PackFormat, PackSummary};
+use crate::models::projects::{
+    FindingSeverity, GameVersion, Loader, ValidationFinding,
+};
 use crate::validate::fabric::FabricValidator;
 use crate::validate::forge::{ForgeValidator, LegacyForgeValidator};
 use crate::validate::liteloader::LiteLoaderValidator;
+use crate::validate::metadata::ModMetadata;
 use crate::validate::pack::PackValidator;
+use crate::validate::plugin::{
+    BukkitValidator, BungeeCordValidator, VelocityValidator,
+};
 use crate::validate::quilt::QuiltValidator;
-use std::io::Cursor;
+use crate::validate::resourcepack::{
+    DataPackValidator, PackKind, ResourcePackValidator,
+};
+use crate::validate::shader::ShaderPackValidator;
+use crate::util::spool::FileReader;
 use thiserror::Error;
 use time::OffsetDateTime;
 use zip::ZipArchive;
 
 mod fabric;
 mod forge;
+pub mod game_version_range;
 mod liteloader;
-mod pack;
+pub mod metadata;
+pub mod nested;
+pub mod pack;
+mod plugin;
 mod quilt;
+mod resourcepack;
+mod shader;
 #[cfg(test)]
-mod test_util;
+pub(crate) mod test_util;
 
 #[derive(Error, Debug)]
 pub enum ValidationError {
@@ -34,25 +50,44 @@ pub enum ValidationError {
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum ValidationResult {
-    /// File should be marked as primary with pack file data
-    PassWithPackData(PackFormat),
-    /// File should be marked as primary
+    /// File passed validation with pack file data, summarizing what the pack
+    /// installs
+    PassWithPackData(PackFormat, PackSummary),
+    /// File passed validation with the data declared in its mod manifest
+    PassWithModMetadata(ModMetadata),
+    /// File passed validation with the format declared in its pack.mcmeta
+    PassWithPackFormat { kind: PackKind, pack_format: u32 },
+    /// File passed validation
     Pass,
-    /// File should not be marked primary, the reason for which is inside the String
-    Warning(&'static str),
 }
 
-impl ValidationResult {
-    pub fn is_passed(&self) -> bool {
-        match self {
-            ValidationResult::PassWithPackData(_) | ValidationResult::Pass => {
-                true
-            }
-            ValidationResult::Warning(_) => false,
+/// The result of validating a file, along with everything noteworthy found
+/// while doing so
+#[derive(Debug, Eq, PartialEq)]
+pub struct ValidationReport {
+    pub result: ValidationResult,
+    pub findings: Vec<ValidationFinding>,
+}
+
+impl From<ValidationResult> for ValidationReport {
+    fn from(result: ValidationResult) -> Self {
+        ValidationReport {
+            result,
+            findings: Vec::new(),
         }
     }
 }
 
+impl ValidationReport {
+    /// Whether the file should be marked as primary, which it shouldn't be
+    /// if any of the findings is a warning or worse
+    pub fn is_passed(&self) -> bool {
+        self.findings
+            .iter()
+            .all(|x| x.severity < FindingSeverity::Warning)
+    }
+}
+
 pub enum SupportedGameVersions {
     All,
     PastDate(OffsetDateTime),
@@ -68,31 +103,36 @@ pub trait Validator: Sync {
     fn get_supported_game_versions(&self) -> SupportedGameVersions;
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
-    ) -> Result<ValidationResult, ValidationError>;
+        archive: &mut ZipArchive<FileReader>,
+    ) -> Result<ValidationReport, ValidationError>;
 }
 
-static VALIDATORS: [&dyn Validator; 6] = [
+static VALIDATORS: [&dyn Validator; 12] = [
     &PackValidator,
     &FabricValidator,
     &ForgeValidator,
     &LegacyForgeValidator,
     &QuiltValidator,
     &LiteLoaderValidator,
+    &ResourcePackValidator,
+    &DataPackValidator,
+    &ShaderPackValidator,
+    &BukkitValidator,
+    &BungeeCordValidator,
+    &VelocityValidator,
 ];
 
-/// The return value is whether this file should be marked as primary or not, based on the analysis of the file
+/// The returned report says whether this file should be marked as primary or not, based on the analysis of the file
 pub async fn validate_file(
-    data: bytes::Bytes,
+    data: FileReader,
     file_extension: String,
     project_type: String,
     loaders: Vec<Loader>,
     game_versions: Vec<GameVersion>,
     all_game_versions: Vec<crate::database::models::categories::GameVersion>,
-) -> Result<ValidationResult, ValidationError> {
+) -> Result<ValidationReport, ValidationError> {
     actix_web::web::block(move || {
-        let reader = std::io::Cursor::new(data);
-        let mut zip = zip::ZipArchive::new(reader)?;
+        let mut zip = zip::ZipArchive::new(data)?;
 
         let mut visited = false;
         for validator in &VALIDATORS {
@@ -107,7 +147,19 @@ pub async fn validate_file(
                 )
             {
                 if validator.get_file_extensions().contains(&&*file_extension) {
-                    return validator.validate(&mut zip);
+                    let mut report = validator.validate(&mut zip)?;
+                    resourcepack::check_pack_format(
+                        &mut report,
+                        &game_versions,
+                        &all_game_versions,
+                    );
+                    pack::check_pack_game_version(
+                        &mut report,
+                        &game_versions,
+                        &all_game_versions,
+                    );
+
+                    return Ok(report);
                 }
                 visited = true;
                 break;
@@ -123,7 +175,7 @@ pub async fn validate_file(
                 .into(),
             ))
         } else {
-            Ok(ValidationResult::Pass)
+            Ok(ValidationResult::Pass.into())
         }
     })
     .await?
@@ -158,7 +210,7 @@ fn game_version_supported(
     }
 }
 
-fn match_extension_ignore_case(name: &str, exts: &[&str]) -> bool {
+pub(crate) fn match_extension_ignore_case(name: &str, exts: &[&str]) -> bool {
     exts.iter().any(|ext| {
         name.chars()
             .rev()
@@ -0,0 +1,273 @@
+use crate::models::projects::ValidationFinding;
+use crate::util::spool::FileReader;
+use crate::validate::fabric::parse_fabric_mod_json;
+use crate::validate::forge::parse_mods_toml;
+use crate::validate::metadata::{read_manifest, ModMetadata};
+use crate::validate::quilt::parse_quilt_mod_json;
+use crate::validate::{match_extension_ignore_case, ValidationError};
+use sha2::Digest;
+use std::io::Read;
+use zip::ZipArchive;
+
+/// The folders loaders bundle other jars in: Fabric and Quilt's Jar-in-Jar,
+/// and Forge's JarJar
+const NESTED_JAR_FOLDERS: &[&str] = &["META-INF/jars/", "META-INF/jarjar/"];
+/// How deep jars bundled inside of bundled jars are followed
+const MAX_NESTING_DEPTH: usize = 3;
+/// Bundled jars larger than this when decompressed aren't read
+const MAX_NESTED_JAR_SIZE: u64 = 64 * (1 << 20);
+
+/// A jar bundled inside of an uploaded file
+#[derive(Debug, Eq, PartialEq)]
+pub struct NestedJar {
+    /// The path of the jar inside of the upload, with `!/` separating the
+    /// paths inside of nested jars
+    pub path: String,
+    pub mod_id: Option<String>,
+    pub version: Option<String>,
+    pub sha1: String,
+    pub sha512: String,
+}
+
+/// Lists the jars bundled inside of an uploaded file, including the jars
+/// bundled inside of those, along with findings about jars which couldn't
+/// be read
+pub async fn find_nested_jars(
+    data: FileReader,
+) -> Result<(Vec<NestedJar>, Vec<ValidationFinding>), ValidationError> {
+    actix_web::web::block(move || {
+        let mut archive = ZipArchive::new(data)?;
+        let mut jars = Vec::new();
+        let mut findings = Vec::new();
+
+        read_nested_jars(
+            &mut archive,
+            "",
+            0,
+            MAX_NESTED_JAR_SIZE,
+            &mut jars,
+            &mut findings,
+        )?;
+
+        Ok((jars, findings))
+    })
+    .await?
+}
+
+fn read_nested_jars(
+    archive: &mut ZipArchive<FileReader>,
+    prefix: &str,
+    depth: usize,
+    max_size: u64,
+    jars: &mut Vec<NestedJar>,
+    findings: &mut Vec<ValidationFinding>,
+) -> Result<(), ValidationError> {
+    let names = archive
+        .file_names()
+        .filter(|name| {
+            NESTED_JAR_FOLDERS.iter().any(|x| name.starts_with(x))
+                && match_extension_ignore_case(name, &[".jar"])
+        })
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    for name in names {
+        let path = format!("{}{}", prefix, name);
+
+        // The sizes in the archive can't be trusted, so reading stops right
+        // after the limit
+        let mut contents = Vec::new();
+        archive
+            .by_name(&name)?
+            .take(max_size + 1)
+            .read_to_end(&mut contents)?;
+
+        if contents.len() as u64 > max_size {
+            findings.push(
+                ValidationFinding::info(
+                    "nested_jar_too_large",
+                    format!(
+                        "Bundled jar is larger than {}MiB, so its contents weren't read",
+                        max_size >> 20
+                    ),
+                )
+                .at(path),
+            );
+            continue;
+        }
+        let sha1 = sha1::Sha1::from(&contents).hexdigest();
+        let sha512 = format!("{:x}", sha2::Sha512::digest(&contents));
+
+        // Bundled files which aren't archives are still indexed by their
+        // hashes, they just can't say what they are
+        let mut metadata = None;
+        if let Ok(mut nested) =
+            ZipArchive::new(FileReader::from(bytes::Bytes::from(contents)))
+        {
+            metadata = read_metadata(&mut nested);
+
+            if depth + 1 < MAX_NESTING_DEPTH {
+                read_nested_jars(
+                    &mut nested,
+                    &format!("{}!/", path),
+                    depth + 1,
+                    max_size,
+                    jars,
+                    findings,
+                )?;
+            }
+        }
+
+        let (mod_id, version) = match metadata {
+            Some(metadata) => (Some(metadata.mod_id), metadata.version),
+            None => (None, None),
+        };
+
+        jars.push(NestedJar {
+            path,
+            mod_id,
+            version,
+            sha1,
+            sha512,
+        });
+    }
+
+    Ok(())
+}
+
+fn read_metadata(archive: &mut ZipArchive<FileReader>) -> Option<ModMetadata> {
+    let manifests: [(&str, fn(&str) -> Option<ModMetadata>); 3] = [
+        ("fabric.mod.json", parse_fabric_mod_json),
+        ("quilt.mod.json", parse_quilt_mod_json),
+        ("META-INF/mods.toml", parse_mods_toml),
+    ];
+
+    manifests.iter().find_map(|(name, parse)| {
+        read_manifest(archive, name).and_then(|x| parse(&x))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_nested_jars, NestedJar};
+    use crate::models::projects::ValidationFinding;
+    use crate::util::spool::FileReader;
+    use crate::validate::test_util::make_dummy_zip_bytes;
+    use zip::ZipArchive;
+
+    fn nested_jars_limited(
+        files: &[(&str, &[u8])],
+        max_size: u64,
+    ) -> (Vec<NestedJar>, Vec<ValidationFinding>) {
+        let data = make_dummy_zip_bytes(files).unwrap();
+        let mut archive =
+            ZipArchive::new(FileReader::from(bytes::Bytes::from(data)))
+                .unwrap();
+        let mut jars = Vec::new();
+        let mut findings = Vec::new();
+
+        read_nested_jars(
+            &mut archive,
+            "",
+            0,
+            max_size,
+            &mut jars,
+            &mut findings,
+        )
+        .unwrap();
+
+        (jars, findings)
+    }
+
+    fn nested_jars(files: &[(&str, &[u8])]) -> Vec<NestedJar> {
+        nested_jars_limited(files, super::MAX_NESTED_JAR_SIZE).0
+    }
+
+    #[test]
+    fn fabric_jar_in_jar() {
+        let library = make_dummy_zip_bytes(&[(
+            "fabric.mod.json",
+            br#"{"id": "library", "version": "1.2.0"}"#,
+        )])
+        .unwrap();
+
+        let jars = nested_jars(&[
+            ("fabric.mod.json", br#"{"id": "example"}"#),
+            ("META-INF/jars/library-1.2.0.jar", &library),
+        ]);
+
+        assert_eq!(jars.len(), 1);
+        assert_eq!(jars[0].path, "META-INF/jars/library-1.2.0.jar");
+        assert_eq!(jars[0].mod_id.as_deref(), Some("library"));
+        assert_eq!(jars[0].version.as_deref(), Some("1.2.0"));
+        assert_eq!(jars[0].sha1, sha1::Sha1::from(&library).hexdigest());
+    }
+    #[test]
+    fn forge_jarjar() {
+        let library = make_dummy_zip_bytes(&[(
+            "META-INF/mods.toml",
+            br#"
+            [[mods]]
+            modId = "library"
+            version = "3.0.1"
+            "#,
+        )])
+        .unwrap();
+
+        let jars = nested_jars(&[("META-INF/jarjar/library.jar", &library)]);
+
+        assert_eq!(jars[0].mod_id.as_deref(), Some("library"));
+        assert_eq!(jars[0].version.as_deref(), Some("3.0.1"));
+    }
+    #[test]
+    fn jars_inside_nested_jars() {
+        let inner = make_dummy_zip_bytes(&[]).unwrap();
+        let outer =
+            make_dummy_zip_bytes(&[("META-INF/jars/inner.jar", &inner)])
+                .unwrap();
+
+        let jars = nested_jars(&[("META-INF/jars/outer.jar", &outer)]);
+        let paths = jars.iter().map(|x| &*x.path).collect::<Vec<_>>();
+
+        assert_eq!(
+            paths,
+            vec![
+                "META-INF/jars/outer.jar!/META-INF/jars/inner.jar",
+                "META-INF/jars/outer.jar"
+            ]
+        );
+        assert!(jars.iter().all(|x| x.mod_id.is_none()));
+    }
+    #[test]
+    fn jars_over_the_size_limit_are_skipped() {
+        let small = make_dummy_zip_bytes(&[]).unwrap();
+        let large =
+            make_dummy_zip_bytes(&[("data.bin", &[0u8; 4096])]).unwrap();
+
+        let (jars, findings) = nested_jars_limited(
+            &[
+                ("META-INF/jars/small.jar", &small),
+                ("META-INF/jars/large.jar", &large),
+            ],
+            small.len() as u64,
+        );
+
+        assert_eq!(jars.len(), 1);
+        assert_eq!(jars[0].path, "META-INF/jars/small.jar");
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].code, "nested_jar_too_large");
+        assert_eq!(
+            findings[0].path.as_deref(),
+            Some("META-INF/jars/large.jar")
+        );
+    }
+    #[test]
+    fn other_jars_are_ignored() {
+        let jars = nested_jars(&[
+            ("libs/library.jar", b""),
+            ("META-INF/jars/readme.txt", b""),
+        ]);
+
+        assert!(jars.is_empty());
+    }
+}
@@ -1,13 +1,24 @@
-use crate::models::pack::{PackFileHash, PackFormat};
+use crate::models::pack::{
+    PackDependency, PackFileHash, PackFormat, PackSummary,
+};
+use crate::models::projects::{GameVersion, ValidationFinding};
+use crate::util::spool::FileReader;
 use crate::util::validate::validation_errors_to_string;
 use crate::validate::{
-    SupportedGameVersions, ValidationError, ValidationResult,
+    match_extension_ignore_case, SupportedGameVersions, ValidationError,
+    ValidationReport, ValidationResult,
 };
-use std::io::{Cursor, Read};
-use std::path::Component;
+use std::collections::HashSet;
+use std::io::Read;
 use validator::Validate;
 use zip::ZipArchive;
 
+/// The folders of the archive which are copied into the instance
+const OVERRIDE_FOLDERS: &[&str] =
+    &["overrides/", "client-overrides/", "server-overrides/"];
+/// Pack files declared to be larger than this are reported to moderators
+const MAX_PLAUSIBLE_FILE_SIZE: u32 = 500 * (1 << 20);
+
 pub struct PackValidator;
 
 impl super::Validator for PackValidator {
@@ -29,19 +40,21 @@ impl super::Validator for PackValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
-    ) -> Result<ValidationResult, ValidationError> {
-        let mut file =
-            archive.by_name("modrinth.index.json").map_err(|_| {
-                ValidationError::InvalidInput(
-                    "Pack manifest is missing.".into(),
-                )
-            })?;
+        archive: &mut ZipArchive<FileReader>,
+    ) -> Result<ValidationReport, ValidationError> {
+        let pack: PackFormat = {
+            let mut file =
+                archive.by_name("modrinth.index.json").map_err(|_| {
+                    ValidationError::InvalidInput(
+                        "Pack manifest is missing.".into(),
+                    )
+                })?;
 
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
 
-        let pack: PackFormat = serde_json::from_str(&contents)?;
+            serde_json::from_str(&contents)?
+        };
 
         pack.validate().map_err(|err| {
             ValidationError::InvalidInput(
@@ -55,6 +68,13 @@ impl super::Validator for PackValidator {
             ));
         }
 
+        let mut findings = Vec::new();
+        let mut summary = summarize_dependencies(&pack, &mut findings)?;
+
+        // The paths the pack downloads files to, to find duplicates and
+        // overrides which replace downloaded files
+        let mut paths = HashSet::new();
+
         for file in &pack.files {
             if file.hashes.get(&PackFileHash::Sha1).is_none() {
                 return Err(ValidationError::InvalidInput(
@@ -68,25 +88,449 @@ impl super::Validator for PackValidator {
                 ));
             }
 
-            let path = std::path::Path::new(&file.path)
-                .components()
-                .next()
-                .ok_or_else(|| {
-                    ValidationError::InvalidInput(
-                        "Invalid pack file path!".into(),
+            if !is_safe_path(&file.path) {
+                return Err(ValidationError::InvalidInput(
+                    "Invalid pack file path!".into(),
+                ));
+            }
+
+            if !paths.insert(normalize_path(&file.path)) {
+                return Err(ValidationError::InvalidInput(
+                    format!("Duplicate pack file path {}!", file.path).into(),
+                ));
+            }
+
+            if file.file_size == 0 {
+                findings.push(
+                    ValidationFinding::warning(
+                        "empty_pack_file",
+                        format!("{} is declared to be empty", file.path),
                     )
-                })?;
+                    .at(&file.path),
+                );
+            } else if file.file_size > MAX_PLAUSIBLE_FILE_SIZE {
+                findings.push(
+                    ValidationFinding::warning(
+                        "implausible_file_size",
+                        format!(
+                            "{} is declared to be {} bytes large",
+                            file.path, file.file_size
+                        ),
+                    )
+                    .at(&file.path),
+                );
+            }
+
+            summary.file_count += 1;
+            summary.total_file_size += u64::from(file.file_size);
+        }
+
+        inspect_overrides(archive, &paths, &mut summary, &mut findings)?;
+
+        Ok(ValidationReport {
+            result: ValidationResult::PassWithPackData(pack, summary),
+            findings,
+        })
+    }
+}
+
+/// Checks the dependencies of the pack, starting a summary with the versions
+/// of Minecraft and the mod loader it depends on
+fn summarize_dependencies(
+    pack: &PackFormat,
+    findings: &mut Vec<ValidationFinding>,
+) -> Result<PackSummary, ValidationError> {
+    if let Some((dependency, _)) =
+        pack.dependencies.iter().find(|(_, x)| x.trim().is_empty())
+    {
+        return Err(ValidationError::InvalidInput(
+            format!("Pack dependency {} has no version!", dependency).into(),
+        ));
+    }
+
+    let minecraft_version = pack
+        .dependencies
+        .get(&PackDependency::Minecraft)
+        .cloned()
+        .ok_or_else(|| {
+            ValidationError::InvalidInput(
+                "Pack must depend on a version of Minecraft!".into(),
+            )
+        })?;
+
+    let loader_dependencies = [
+        PackDependency::Forge,
+        PackDependency::FabricLoader,
+        PackDependency::QuiltLoader,
+    ];
+    let loaders = loader_dependencies
+        .iter()
+        .filter_map(|x| pack.dependencies.get(x).map(|version| (x, version)))
+        .collect::<Vec<_>>();
+
+    if loaders.len() > 1 {
+        findings.push(
+            ValidationFinding::warning(
+                "conflicting_loaders",
+                format!(
+                    "Pack depends on several mod loaders: {}",
+                    loaders
+                        .iter()
+                        .map(|(x, _)| x.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            )
+            .at("modrinth.index.json"),
+        );
+    }
+
+    let loader = loaders.first();
+
+    Ok(PackSummary {
+        file_count: 0,
+        total_file_size: 0,
+        override_count: 0,
+        client_override_count: 0,
+        server_override_count: 0,
+        override_jar_count: 0,
+        minecraft_version,
+        loader: loader.map(|(x, _)| x.as_str().to_string()),
+        loader_version: loader.map(|(_, version)| version.to_string()),
+    })
+}
+
+/// Counts the files in the override folders, reporting jars which are
+/// included in them instead of being downloaded from an allowed source
+fn inspect_overrides(
+    archive: &ZipArchive<FileReader>,
+    paths: &HashSet<String>,
+    summary: &mut PackSummary,
+    findings: &mut Vec<ValidationFinding>,
+) -> Result<(), ValidationError> {
+    for name in archive.file_names() {
+        if !is_safe_path(name) {
+            return Err(ValidationError::InvalidInput(
+                "Invalid pack file path!".into(),
+            ));
+        }
+
+        if name == "modrinth.index.json" || name.ends_with('/') {
+            continue;
+        }
+
+        let (folder, path) = match OVERRIDE_FOLDERS
+            .iter()
+            .find_map(|x| name.strip_prefix(x).map(|path| (*x, path)))
+        {
+            Some(x) => x,
+            None => {
+                findings.push(
+                    ValidationFinding::info(
+                        "unknown_pack_entry",
+                        format!(
+                            "{} is not in an override folder and won't be installed",
+                            name
+                        ),
+                    )
+                    .at(name),
+                );
+                continue;
+            }
+        };
+
+        match folder {
+            "client-overrides/" => summary.client_override_count += 1,
+            "server-overrides/" => summary.server_override_count += 1,
+            _ => summary.override_count += 1,
+        }
+
+        if match_extension_ignore_case(path, &[".jar"]) {
+            summary.override_jar_count += 1;
+            findings.push(
+                ValidationFinding::warning(
+                    "override_jar",
+                    format!(
+                        "{} is included in the pack instead of being downloaded",
+                        name
+                    ),
+                )
+                .at(name),
+            );
+        }
+
+        if paths.contains(&normalize_path(path)) {
+            findings.push(
+                ValidationFinding::warning(
+                    "override_conflict",
+                    format!("{} replaces a file downloaded by the pack", name),
+                )
+                .at(name),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a path stays inside of the folder the pack is installed to. Both
+/// separators are checked, as the pack may be installed on Windows.
+fn is_safe_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.starts_with(&['/', '\\'][..])
+        && !path.contains(':')
+        && path.split(&['/', '\\'][..]).all(|x| x != "..")
+}
+
+fn normalize_path(path: &str) -> String {
+    path.replace('\\', "/")
+        .split('/')
+        .filter(|x| !x.is_empty() && *x != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Compares the version of Minecraft the pack depends on with the game
+/// versions selected for its version
+pub(super) fn check_pack_game_version(
+    report: &mut ValidationReport,
+    game_versions: &[GameVersion],
+    all_game_versions: &[crate::database::models::categories::GameVersion],
+) {
+    let version = match &report.result {
+        ValidationResult::PassWithPackData(_, summary) => {
+            &summary.minecraft_version
+        }
+        _ => return,
+    };
+
+    let finding = if !all_game_versions.iter().any(|x| x.version == *version) {
+        ValidationFinding::warning(
+            "unknown_game_version",
+            format!(
+                "Pack depends on Minecraft {}, which isn't a known game version",
+                version
+            ),
+        )
+    } else if !game_versions.iter().any(|x| x.0 == *version) {
+        ValidationFinding::warning(
+            "game_version_mismatch",
+            format!(
+                "Pack depends on Minecraft {}, which isn't one of the selected game versions",
+                version
+            ),
+        )
+    } else {
+        return;
+    };
+
+    report.findings.push(finding.at("modrinth.index.json"));
+}
 
-            match path {
-                Component::CurDir | Component::Normal(_) => {}
-                _ => {
-                    return Err(ValidationError::InvalidInput(
-                        "Invalid pack file path!".into(),
-                    ))
-                }
-            };
+/// Compares the version of the mod loader the pack depends on with the known
+/// versions of that loader. Nothing is reported if no versions of it are
+/// known, such as before they were indexed for the first time.
+pub fn check_pack_loader_version(
+    report: &mut ValidationReport,
+    loader_versions: &[String],
+) {
+    let (loader, version) = match &report.result {
+        ValidationResult::PassWithPackData(_, summary) => {
+            match (&summary.loader, &summary.loader_version) {
+                (Some(loader), Some(version)) => (loader, version),
+                _ => return,
+            }
         }
+        _ => return,
+    };
+
+    if loader_versions.is_empty() || loader_versions.contains(version) {
+        return;
+    }
+
+    let finding = ValidationFinding::warning(
+        "unknown_loader_version",
+        format!(
+            "Pack depends on {} {}, which isn't a known version of it",
+            loader, version
+        ),
+    );
+    report.findings.push(finding.at("modrinth.index.json"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_pack_loader_version, is_safe_path, PackValidator};
+    use crate::validate::{
+        test_util::make_dummy_zip_with_contents, ValidationError,
+        ValidationResult, Validator,
+    };
+
+    const FILE: &str = r#"{
+        "path": "mods/example.jar",
+        "hashes": {"sha1": "a", "sha512": "b"},
+        "downloads": [],
+        "fileSize": 1024
+    }"#;
+
+    fn index(files: &[&str], dependencies: &str) -> String {
+        format!(
+            r#"{{
+                "game": "minecraft",
+                "formatVersion": 1,
+                "versionId": "1.0.0",
+                "name": "Example Pack",
+                "files": [{}],
+                "dependencies": {}
+            }}"#,
+            files.join(","),
+            dependencies
+        )
+    }
+
+    fn validate(
+        files: &[(&str, &str)],
+    ) -> Result<crate::validate::ValidationReport, ValidationError> {
+        let mut zip = make_dummy_zip_with_contents(files).unwrap();
+
+        PackValidator.validate(&mut zip)
+    }
+
+    const DEPENDENCIES: &str =
+        r#"{"minecraft": "1.18.2", "fabric-loader": "0.14.6"}"#;
+
+    #[test]
+    fn all_clear() {
+        let report = validate(&[
+            ("modrinth.index.json", &index(&[FILE], DEPENDENCIES)),
+            ("overrides/config/example.json", "{}"),
+            ("client-overrides/options.txt", ""),
+        ])
+        .unwrap();
+
+        assert!(report.findings.is_empty());
+        match report.result {
+            ValidationResult::PassWithPackData(_, summary) => {
+                assert_eq!(summary.file_count, 1);
+                assert_eq!(summary.total_file_size, 1024);
+                assert_eq!(summary.override_count, 1);
+                assert_eq!(summary.client_override_count, 1);
+                assert_eq!(summary.minecraft_version, "1.18.2");
+                assert_eq!(summary.loader.as_deref(), Some("fabric-loader"));
+                assert_eq!(summary.loader_version.as_deref(), Some("0.14.6"));
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+    #[test]
+    fn unsafe_paths() {
+        assert!(is_safe_path("mods/example.jar"));
+        assert!(is_safe_path("./mods/example..jar"));
+        assert!(!is_safe_path("mods/../../example.jar"));
+        assert!(!is_safe_path("mods\\..\\..\\example.jar"));
+        assert!(!is_safe_path("/mods/example.jar"));
+        assert!(!is_safe_path("C:/mods/example.jar"));
+    }
+    #[test]
+    fn nested_parent_path() {
+        let file = FILE.replace("mods/example.jar", "mods/../../example.jar");
+
+        assert!(matches!(
+            validate(&[("modrinth.index.json", &index(&[&file], DEPENDENCIES))])
+                .unwrap_err(),
+            ValidationError::InvalidInput(error)
+            if error == "Invalid pack file path!"
+        ));
+    }
+    #[test]
+    fn duplicate_paths() {
+        let file = FILE.replace("mods/example.jar", "./mods/example.jar");
+
+        assert!(matches!(
+            validate(&[(
+                "modrinth.index.json",
+                &index(&[FILE, &file], DEPENDENCIES)
+            )])
+            .unwrap_err(),
+            ValidationError::InvalidInput(error)
+            if error == "Duplicate pack file path ./mods/example.jar!"
+        ));
+    }
+    #[test]
+    fn missing_minecraft() {
+        assert!(matches!(
+            validate(&[(
+                "modrinth.index.json",
+                &index(&[FILE], r#"{"forge": "40.1.0"}"#)
+            )])
+            .unwrap_err(),
+            ValidationError::InvalidInput(error)
+            if error == "Pack must depend on a version of Minecraft!"
+        ));
+    }
+    #[test]
+    fn override_jars() {
+        let report = validate(&[
+            ("modrinth.index.json", &index(&[FILE], DEPENDENCIES)),
+            ("overrides/mods/smuggled.jar", ""),
+            ("server-overrides/mods/example.jar", ""),
+        ])
+        .unwrap();
+
+        let codes = report
+            .findings
+            .iter()
+            .map(|x| (&*x.code, x.path.as_deref().unwrap()))
+            .collect::<Vec<_>>();
+
+        assert!(
+            codes.contains(&("override_jar", "overrides/mods/smuggled.jar"))
+        );
+        assert!(codes.contains(&(
+            "override_conflict",
+            "server-overrides/mods/example.jar"
+        )));
+        assert!(!report.is_passed());
+    }
+    #[test]
+    fn unknown_loader_version() {
+        let mut report =
+            validate(&[("modrinth.index.json", &index(&[FILE], DEPENDENCIES))])
+                .unwrap();
+        let known = vec!["0.14.5".to_string(), "0.14.6".to_string()];
+
+        check_pack_loader_version(&mut report, &known);
+        assert!(report.is_passed());
+
+        let mut report = validate(&[(
+            "modrinth.index.json",
+            &index(
+                &[FILE],
+                r#"{"minecraft": "1.18.2", "fabric-loader": "0.99.0"}"#,
+            ),
+        )])
+        .unwrap();
+
+        check_pack_loader_version(&mut report, &[]);
+        assert!(report.is_passed());
+
+        check_pack_loader_version(&mut report, &known);
+        assert!(report
+            .findings
+            .iter()
+            .any(|x| x.code == "unknown_loader_version"));
+        assert!(!report.is_passed());
+    }
+    #[test]
+    fn implausible_file_size() {
+        let file = FILE.replace("1024", "0");
+
+        let report = validate(&[(
+            "modrinth.index.json",
+            &index(&[&file], DEPENDENCIES),
+        )])
+        .unwrap();
 
-        Ok(ValidationResult::PassWithPackData(pack))
+        assert!(report.findings.iter().any(|x| x.code == "empty_pack_file"));
     }
 }
@@ -0,0 +1,557 @@
+use crate::models::projects::{DependencyType, ValidationFinding};
+use crate::util::spool::FileReader;
+use crate::validate::metadata::{
+    concrete_version, read_manifest, ModDependency, ModEnvironment, ModMetadata,
+};
+use crate::validate::{
+    SupportedGameVersions, ValidationError, ValidationReport, ValidationResult,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use yaml_rust::Yaml;
+use zip::ZipArchive;
+
+use super::match_extension_ignore_case;
+
+pub struct BukkitValidator;
+
+impl super::Validator for BukkitValidator {
+    fn get_file_extensions(&self) -> &[&str] {
+        &["jar"]
+    }
+
+    fn get_project_types(&self) -> &[&str] {
+        &["mod"]
+    }
+
+    fn get_supported_loaders(&self) -> &[&str] {
+        &["bukkit", "spigot", "paper", "purpur"]
+    }
+
+    fn get_supported_game_versions(&self) -> SupportedGameVersions {
+        SupportedGameVersions::All
+    }
+
+    fn validate(
+        &self,
+        archive: &mut ZipArchive<FileReader>,
+    ) -> Result<ValidationReport, ValidationError> {
+        // Paper prefers its own descriptor over plugin.yml if a plugin has both
+        validate_plugin(
+            archive,
+            &[
+                ("paper-plugin.yml", parse_paper_plugin_yml),
+                ("plugin.yml", parse_plugin_yml),
+            ],
+        )
+    }
+}
+
+pub struct BungeeCordValidator;
+
+impl super::Validator for BungeeCordValidator {
+    fn get_file_extensions(&self) -> &[&str] {
+        &["jar"]
+    }
+
+    fn get_project_types(&self) -> &[&str] {
+        &["mod"]
+    }
+
+    fn get_supported_loaders(&self) -> &[&str] {
+        &["bungeecord", "waterfall"]
+    }
+
+    fn get_supported_game_versions(&self) -> SupportedGameVersions {
+        SupportedGameVersions::All
+    }
+
+    fn validate(
+        &self,
+        archive: &mut ZipArchive<FileReader>,
+    ) -> Result<ValidationReport, ValidationError> {
+        // BungeeCord falls back to plugin.yml, reading it with its own schema
+        validate_plugin(
+            archive,
+            &[
+                ("bungee.yml", parse_bungee_yml),
+                ("plugin.yml", parse_bungee_yml),
+            ],
+        )
+    }
+}
+
+pub struct VelocityValidator;
+
+impl super::Validator for VelocityValidator {
+    fn get_file_extensions(&self) -> &[&str] {
+        &["jar"]
+    }
+
+    fn get_project_types(&self) -> &[&str] {
+        &["mod"]
+    }
+
+    fn get_supported_loaders(&self) -> &[&str] {
+        &["velocity"]
+    }
+
+    fn get_supported_game_versions(&self) -> SupportedGameVersions {
+        SupportedGameVersions::All
+    }
+
+    fn validate(
+        &self,
+        archive: &mut ZipArchive<FileReader>,
+    ) -> Result<ValidationReport, ValidationError> {
+        validate_plugin(
+            archive,
+            &[("velocity-plugin.json", parse_velocity_plugin_json)],
+        )
+    }
+}
+
+type DescriptorParser = fn(&str) -> Option<ModMetadata>;
+
+/// Validates a plugin using the first of the descriptors present in it
+fn validate_plugin(
+    archive: &mut ZipArchive<FileReader>,
+    descriptors: &[(&str, DescriptorParser)],
+) -> Result<ValidationReport, ValidationError> {
+    let (name, contents, parse) = descriptors
+        .iter()
+        .find_map(|(name, parse)| {
+            read_manifest(archive, name).map(|x| (name, x, parse))
+        })
+        .ok_or_else(|| {
+            ValidationError::InvalidInput(
+                format!(
+                    "No {} present for plugin file.",
+                    descriptors
+                        .iter()
+                        .map(|(name, _)| *name)
+                        .collect::<Vec<_>>()
+                        .join(" or ")
+                )
+                .into(),
+            )
+        })?;
+
+    let mut findings = Vec::new();
+
+    if !archive
+        .file_names()
+        .any(|name| match_extension_ignore_case(name, &[".class"]))
+    {
+        findings.push(ValidationFinding::warning(
+            "source_file",
+            "Plugin file is a source file!",
+        ));
+    }
+
+    let result = match parse(&contents) {
+        Some(metadata) => ValidationResult::PassWithModMetadata(metadata),
+        None => {
+            findings.push(
+                ValidationFinding::info(
+                    "unreadable_manifest",
+                    format!("{} could not be read", name),
+                )
+                .at(*name),
+            );
+            ValidationResult::Pass
+        }
+    };
+
+    Ok(ValidationReport { result, findings })
+}
+
+/// Reads a top level scalar of a YAML descriptor as it's written. YAML parses
+/// unquoted versions such as `1.10` as numbers, which serde_yaml turns into
+/// `1.1`, while yaml-rust keeps their original text.
+fn yaml_scalar(contents: &str, key: &str) -> Option<String> {
+    let documents = yaml_rust::YamlLoader::load_from_str(contents).ok()?;
+
+    match &documents.first()?[key] {
+        Yaml::String(x) | Yaml::Real(x) => Some(x.clone()),
+        Yaml::Integer(x) => Some(x.to_string()),
+        _ => None,
+    }
+}
+
+fn plugin_dependencies(
+    required: Vec<String>,
+    optional: Vec<String>,
+) -> Vec<ModDependency> {
+    required
+        .into_iter()
+        .map(|x| (x, DependencyType::Required))
+        .chain(optional.into_iter().map(|x| (x, DependencyType::Optional)))
+        .map(|(mod_id, dependency_type)| ModDependency {
+            mod_id,
+            range: None,
+            dependency_type,
+        })
+        .collect()
+}
+
+/// `api-version` is the oldest version of the server API a plugin supports
+fn api_version_range(contents: &str) -> Option<String> {
+    yaml_scalar(contents, "api-version").map(|x| format!(">={}", x))
+}
+
+#[derive(Deserialize)]
+struct PluginYml {
+    name: String,
+    #[serde(default)]
+    depend: Vec<String>,
+    #[serde(default)]
+    softdepend: Vec<String>,
+}
+
+fn parse_plugin_yml(contents: &str) -> Option<ModMetadata> {
+    let manifest: PluginYml = serde_yaml::from_str(contents).ok()?;
+
+    Some(ModMetadata {
+        mod_id: manifest.name,
+        version: concrete_version(yaml_scalar(contents, "version")),
+        minecraft_range: api_version_range(contents),
+        loader_range: None,
+        dependencies: plugin_dependencies(manifest.depend, manifest.softdepend),
+        environment: ModEnvironment::Server,
+    })
+}
+
+#[derive(Deserialize)]
+struct PaperPluginYml {
+    name: String,
+    #[serde(default)]
+    dependencies: PaperDependencies,
+}
+
+/// Dependencies are either split by when they are needed, or listed directly
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PaperDependencies {
+    Phases(BTreeMap<String, BTreeMap<String, PaperDependency>>),
+    List(Vec<PaperListedDependency>),
+}
+
+impl Default for PaperDependencies {
+    fn default() -> Self {
+        PaperDependencies::List(Vec::new())
+    }
+}
+
+#[derive(Deserialize)]
+struct PaperDependency {
+    #[serde(default = "default_required")]
+    required: bool,
+}
+
+#[derive(Deserialize)]
+struct PaperListedDependency {
+    name: String,
+    #[serde(default = "default_required")]
+    required: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+fn parse_paper_plugin_yml(contents: &str) -> Option<ModMetadata> {
+    let manifest: PaperPluginYml = serde_yaml::from_str(contents).ok()?;
+
+    let dependencies = match manifest.dependencies {
+        PaperDependencies::Phases(phases) => phases
+            .into_iter()
+            .flat_map(|(_, dependencies)| dependencies)
+            .map(|(name, dependency)| (name, dependency.required))
+            .collect::<Vec<_>>(),
+        PaperDependencies::List(dependencies) => dependencies
+            .into_iter()
+            .map(|x| (x.name, x.required))
+            .collect(),
+    };
+    let (required, optional): (Vec<_>, Vec<_>) = dependencies
+        .into_iter()
+        .partition(|(_, required)| *required);
+
+    Some(ModMetadata {
+        mod_id: manifest.name,
+        version: concrete_version(yaml_scalar(contents, "version")),
+        minecraft_range: api_version_range(contents),
+        loader_range: None,
+        dependencies: plugin_dependencies(
+            required.into_iter().map(|(name, _)| name).collect(),
+            optional.into_iter().map(|(name, _)| name).collect(),
+        ),
+        environment: ModEnvironment::Server,
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BungeeYml {
+    name: String,
+    #[serde(default)]
+    depends: Vec<String>,
+    #[serde(default)]
+    soft_depends: Vec<String>,
+}
+
+fn parse_bungee_yml(contents: &str) -> Option<ModMetadata> {
+    let manifest: BungeeYml = serde_yaml::from_str(contents).ok()?;
+
+    Some(ModMetadata {
+        mod_id: manifest.name,
+        version: concrete_version(yaml_scalar(contents, "version")),
+        minecraft_range: None,
+        loader_range: None,
+        dependencies: plugin_dependencies(
+            manifest.depends,
+            manifest.soft_depends,
+        ),
+        environment: ModEnvironment::Server,
+    })
+}
+
+#[derive(Deserialize)]
+struct VelocityPluginJson {
+    id: String,
+    version: Option<String>,
+    #[serde(default)]
+    dependencies: Vec<VelocityDependency>,
+}
+
+#[derive(Deserialize)]
+struct VelocityDependency {
+    id: String,
+    #[serde(default)]
+    optional: bool,
+}
+
+fn parse_velocity_plugin_json(contents: &str) -> Option<ModMetadata> {
+    let manifest: VelocityPluginJson = serde_json::from_str(contents).ok()?;
+
+    let (optional, required): (Vec<_>, Vec<_>) =
+        manifest.dependencies.into_iter().partition(|x| x.optional);
+
+    Some(ModMetadata {
+        mod_id: manifest.id,
+        version: concrete_version(manifest.version),
+        minecraft_range: None,
+        loader_range: None,
+        dependencies: plugin_dependencies(
+            required.into_iter().map(|x| x.id).collect(),
+            optional.into_iter().map(|x| x.id).collect(),
+        ),
+        environment: ModEnvironment::Server,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BukkitValidator, BungeeCordValidator, VelocityValidator};
+    use crate::models::projects::DependencyType;
+    use crate::validate::metadata::{
+        ModDependency, ModEnvironment, ModMetadata,
+    };
+    use crate::validate::{
+        test_util::{make_dummy_zip, make_dummy_zip_with_contents},
+        ValidationError, ValidationResult, Validator,
+    };
+
+    fn dependency(
+        mod_id: &str,
+        dependency_type: DependencyType,
+    ) -> ModDependency {
+        ModDependency {
+            mod_id: mod_id.to_string(),
+            range: None,
+            dependency_type,
+        }
+    }
+
+    #[test]
+    fn plugin_yml() {
+        let mut zip = make_dummy_zip_with_contents(&[
+            (
+                "plugin.yml",
+                "name: Example\n\
+                 version: 1.2\n\
+                 main: com.example.Example\n\
+                 api-version: 1.18\n\
+                 depend: [Vault]\n\
+                 softdepend: [PlaceholderAPI]\n",
+            ),
+            ("com/example/Example.class", ""),
+        ])
+        .unwrap();
+
+        let report = BukkitValidator.validate(&mut zip).unwrap();
+
+        assert!(report.is_passed());
+        assert_eq!(
+            report.result,
+            ValidationResult::PassWithModMetadata(ModMetadata {
+                mod_id: "Example".to_string(),
+                version: Some("1.2".to_string()),
+                minecraft_range: Some(">=1.18".to_string()),
+                loader_range: None,
+                dependencies: vec![
+                    dependency("Vault", DependencyType::Required),
+                    dependency("PlaceholderAPI", DependencyType::Optional),
+                ],
+                environment: ModEnvironment::Server,
+            })
+        );
+    }
+    #[test]
+    fn unquoted_versions_keep_trailing_zeros() {
+        let mut zip = make_dummy_zip_with_contents(&[
+            (
+                "plugin.yml",
+                "name: Example\n\
+                 version: 1.10\n\
+                 api-version: 1.20\n",
+            ),
+            ("com/example/Example.class", ""),
+        ])
+        .unwrap();
+
+        let report = BukkitValidator.validate(&mut zip).unwrap();
+
+        match report.result {
+            ValidationResult::PassWithModMetadata(metadata) => {
+                assert_eq!(metadata.version.as_deref(), Some("1.10"));
+                assert_eq!(metadata.minecraft_range.as_deref(), Some(">=1.20"));
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+    #[test]
+    fn paper_plugin_yml() {
+        let mut zip = make_dummy_zip_with_contents(&[
+            ("plugin.yml", "name: Legacy"),
+            (
+                "paper-plugin.yml",
+                "name: Example\n\
+                 version: '${version}'\n\
+                 main: com.example.Example\n\
+                 api-version: '1.19'\n\
+                 dependencies:\n  \
+                   server:\n    \
+                     Vault:\n      \
+                       required: true\n    \
+                     LuckPerms:\n      \
+                       required: false\n",
+            ),
+            ("com/example/Example.class", ""),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            BukkitValidator.validate(&mut zip).unwrap().result,
+            ValidationResult::PassWithModMetadata(ModMetadata {
+                mod_id: "Example".to_string(),
+                version: None,
+                minecraft_range: Some(">=1.19".to_string()),
+                loader_range: None,
+                dependencies: vec![
+                    dependency("Vault", DependencyType::Required),
+                    dependency("LuckPerms", DependencyType::Optional),
+                ],
+                environment: ModEnvironment::Server,
+            })
+        );
+    }
+    #[test]
+    fn bungee_yml() {
+        let mut zip = make_dummy_zip_with_contents(&[
+            (
+                "bungee.yml",
+                "name: Example\n\
+                 version: 2.1.0\n\
+                 main: com.example.Example\n\
+                 softDepends: [LuckPerms]\n",
+            ),
+            ("com/example/Example.class", ""),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            BungeeCordValidator.validate(&mut zip).unwrap().result,
+            ValidationResult::PassWithModMetadata(ModMetadata {
+                mod_id: "Example".to_string(),
+                version: Some("2.1.0".to_string()),
+                minecraft_range: None,
+                loader_range: None,
+                dependencies: vec![dependency(
+                    "LuckPerms",
+                    DependencyType::Optional
+                )],
+                environment: ModEnvironment::Server,
+            })
+        );
+    }
+    #[test]
+    fn velocity_plugin_json() {
+        let mut zip = make_dummy_zip_with_contents(&[
+            (
+                "velocity-plugin.json",
+                r#"{
+                    "id": "example",
+                    "name": "Example",
+                    "version": "1.0.0",
+                    "main": "com.example.Example",
+                    "dependencies": [
+                        {"id": "luckperms", "optional": false},
+                        {"id": "floodgate", "optional": true}
+                    ]
+                }"#,
+            ),
+            ("com/example/Example.class", ""),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            VelocityValidator.validate(&mut zip).unwrap().result,
+            ValidationResult::PassWithModMetadata(ModMetadata {
+                mod_id: "example".to_string(),
+                version: Some("1.0.0".to_string()),
+                minecraft_range: None,
+                loader_range: None,
+                dependencies: vec![
+                    dependency("luckperms", DependencyType::Required),
+                    dependency("floodgate", DependencyType::Optional),
+                ],
+                environment: ModEnvironment::Server,
+            })
+        );
+    }
+    #[test]
+    fn missing_descriptor() {
+        let mut zip = make_dummy_zip(&["com/example/Example.class"]).unwrap();
+
+        assert!(matches!(
+            BukkitValidator.validate(&mut zip).unwrap_err(),
+            ValidationError::InvalidInput(error)
+            if error == "No paper-plugin.yml or plugin.yml present for plugin file."
+        ));
+    }
+    #[test]
+    fn missing_class_files() {
+        let mut zip = make_dummy_zip(&["plugin.yml"]).unwrap();
+
+        let report = BukkitValidator.validate(&mut zip).unwrap();
+
+        assert!(!report.is_passed());
+        assert!(report.findings.iter().any(|x| x.code == "source_file"));
+        assert!(report
+            .findings
+            .iter()
+            .any(|x| x.code == "unreadable_manifest"));
+    }
+}
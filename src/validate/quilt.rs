@@ -1,7 +1,14 @@
+use crate::models::projects::DependencyType;
+use crate::util::spool::FileReader;
+use crate::validate::metadata::{
+    concrete_version, parse_manifest, ModDependency, ModEnvironment,
+    ModMetadata, VersionPredicates, PLATFORM_MOD_IDS,
+};
+use crate::models::projects::ValidationFinding;
 use crate::validate::{
-    SupportedGameVersions, ValidationError, ValidationResult,
+    SupportedGameVersions, ValidationError, ValidationReport,
 };
-use std::io::Cursor;
+use serde::Deserialize;
 use time::OffsetDateTime;
 use zip::ZipArchive;
 
@@ -31,30 +38,162 @@ impl super::Validator for QuiltValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
-    ) -> Result<ValidationResult, ValidationError> {
+        archive: &mut ZipArchive<FileReader>,
+    ) -> Result<ValidationReport, ValidationError> {
         archive.by_name("quilt.mod.json").map_err(|_| {
             ValidationError::InvalidInput(
                 "No quilt.mod.json present for Quilt file.".into(),
             )
         })?;
 
+        let mut findings = Vec::new();
+
         if !archive.file_names().any(|name| {
             match_extension_ignore_case(name, &[".refmap.json", ".class"])
         }) {
-            return Ok(ValidationResult::Warning(
+            findings.push(ValidationFinding::warning(
+                "source_file",
                 "Quilt mod file is a source file!",
             ));
         }
 
-        Ok(ValidationResult::Pass)
+        let result = parse_manifest(
+            archive,
+            "quilt.mod.json",
+            parse_quilt_mod_json,
+            &mut findings,
+        );
+
+        Ok(ValidationReport { result, findings })
+    }
+}
+
+#[derive(Deserialize)]
+struct QuiltModJson {
+    quilt_loader: QuiltLoaderSection,
+    minecraft: Option<QuiltMinecraftSection>,
+}
+
+#[derive(Deserialize)]
+struct QuiltLoaderSection {
+    id: String,
+    version: Option<String>,
+    #[serde(default)]
+    depends: Vec<QuiltDependency>,
+    #[serde(default)]
+    breaks: Vec<QuiltDependency>,
+}
+
+#[derive(Deserialize)]
+struct QuiltMinecraftSection {
+    environment: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum QuiltDependency {
+    Id(String),
+    Object {
+        id: String,
+        // Can also be an object of `any`/`all` predicates, which isn't
+        // representable as a single range
+        versions: Option<serde_json::Value>,
+        #[serde(default)]
+        optional: bool,
+    },
+}
+
+impl QuiltDependency {
+    fn into_parts(self) -> (String, Option<String>, bool) {
+        match self {
+            QuiltDependency::Id(id) => (id, None, false),
+            QuiltDependency::Object {
+                id,
+                versions,
+                optional,
+            } => (
+                id,
+                versions
+                    .and_then(|x| {
+                        serde_json::from_value::<VersionPredicates>(x).ok()
+                    })
+                    .map(VersionPredicates::into_range),
+                optional,
+            ),
+        }
+    }
+}
+
+/// Returns `None` if the manifest can't be parsed
+pub(super) fn parse_quilt_mod_json(contents: &str) -> Option<ModMetadata> {
+    let manifest: QuiltModJson = serde_json::from_str(contents).ok()?;
+    let loader = manifest.quilt_loader;
+
+    let mut minecraft_range = None;
+    let mut loader_range = None;
+    let mut dependencies = Vec::new();
+
+    for (dependency, incompatible) in loader
+        .depends
+        .into_iter()
+        .map(|x| (x, false))
+        .chain(loader.breaks.into_iter().map(|x| (x, true)))
+    {
+        let (mod_id, range, optional) = dependency.into_parts();
+
+        if !incompatible {
+            match &*mod_id {
+                "minecraft" => minecraft_range = range.clone(),
+                "quilt_loader" => loader_range = range.clone(),
+                _ => {}
+            }
+        }
+
+        if PLATFORM_MOD_IDS.contains(&&*mod_id) {
+            continue;
+        }
+
+        dependencies.push(ModDependency {
+            mod_id,
+            range,
+            dependency_type: if incompatible {
+                DependencyType::Incompatible
+            } else if optional {
+                DependencyType::Optional
+            } else {
+                DependencyType::Required
+            },
+        });
     }
+
+    Some(ModMetadata {
+        mod_id: loader.id,
+        version: concrete_version(loader.version),
+        minecraft_range,
+        loader_range,
+        dependencies,
+        environment: match manifest
+            .minecraft
+            .and_then(|x| x.environment)
+            .as_deref()
+        {
+            Some("client") => ModEnvironment::Client,
+            Some("dedicated_server") => ModEnvironment::Server,
+            Some("*") | None => ModEnvironment::Both,
+            Some(_) => ModEnvironment::Unknown,
+        },
+    })
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::models::projects::DependencyType;
+    use crate::validate::metadata::{
+        ModDependency, ModEnvironment, ModMetadata,
+    };
     use crate::validate::{
-        test_util::make_dummy_zip, ValidationError, ValidationResult, Validator,
+        test_util::{make_dummy_zip, make_dummy_zip_with_contents},
+        ValidationError, ValidationResult, Validator,
     };
 
     use super::QuiltValidator;
@@ -69,7 +208,7 @@ mod tests {
         .unwrap();
 
         assert_eq!(
-            QuiltValidator.validate(&mut zip).unwrap(),
+            QuiltValidator.validate(&mut zip).unwrap().result,
             ValidationResult::Pass
         );
     }
@@ -83,7 +222,7 @@ mod tests {
         .unwrap();
 
         assert_eq!(
-            QuiltValidator.validate(&mut zip).unwrap(),
+            QuiltValidator.validate(&mut zip).unwrap().result,
             ValidationResult::Pass
         );
     }
@@ -100,12 +239,58 @@ mod tests {
     }
     #[test]
     fn missing_refmap_and_class_files() {
-        let mut zip =
-            make_dummy_zip(&["quilt.mod.json"]).unwrap();
+        let mut zip = make_dummy_zip(&["quilt.mod.json"]).unwrap();
+
+        let report = QuiltValidator.validate(&mut zip).unwrap();
+
+        assert!(!report.is_passed());
+        assert!(report.findings.iter().any(|x| x.code == "source_file"));
+    }
+    #[test]
+    fn mod_metadata() {
+        let mut zip = make_dummy_zip_with_contents(&[
+            (
+                "quilt.mod.json",
+                r#"{
+                    "schema_version": 1,
+                    "quilt_loader": {
+                        "group": "com.example",
+                        "id": "example",
+                        "version": "${version}",
+                        "depends": [
+                            { "id": "minecraft", "versions": ">=1.18.2" },
+                            "quilted_fabric_api",
+                            { "id": "modmenu", "optional": true }
+                        ]
+                    },
+                    "minecraft": { "environment": "dedicated_server" }
+                }"#,
+            ),
+            ("Test.class", ""),
+        ])
+        .unwrap();
 
         assert_eq!(
-            QuiltValidator.validate(&mut zip).unwrap(),
-            ValidationResult::Warning("Quilt mod file is a source file!")
+            QuiltValidator.validate(&mut zip).unwrap().result,
+            ValidationResult::PassWithModMetadata(ModMetadata {
+                mod_id: "example".to_string(),
+                version: None,
+                minecraft_range: Some(">=1.18.2".to_string()),
+                loader_range: None,
+                dependencies: vec![
+                    ModDependency {
+                        mod_id: "quilted_fabric_api".to_string(),
+                        range: None,
+                        dependency_type: DependencyType::Required,
+                    },
+                    ModDependency {
+                        mod_id: "modmenu".to_string(),
+                        range: None,
+                        dependency_type: DependencyType::Optional,
+                    },
+                ],
+                environment: ModEnvironment::Server,
+            })
         );
     }
 }
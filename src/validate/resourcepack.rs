@@ -0,0 +1,321 @@
+use crate::models::projects::{GameVersion, ValidationFinding};
+use crate::util::spool::FileReader;
+use crate::validate::game_version_range::versions_outside_range;
+use crate::validate::metadata::read_manifest;
+use crate::validate::{
+    SupportedGameVersions, ValidationError, ValidationReport, ValidationResult,
+};
+use serde::Deserialize;
+use zip::ZipArchive;
+
+/// The kinds of packs declaring their format in a `pack.mcmeta`. Resource packs
+/// and data packs number their formats separately.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PackKind {
+    Resources,
+    Data,
+}
+
+pub struct ResourcePackValidator;
+
+impl super::Validator for ResourcePackValidator {
+    fn get_file_extensions(&self) -> &[&str] {
+        &["zip"]
+    }
+
+    fn get_project_types(&self) -> &[&str] {
+        &["resourcepack"]
+    }
+
+    fn get_supported_loaders(&self) -> &[&str] {
+        &["minecraft"]
+    }
+
+    fn get_supported_game_versions(&self) -> SupportedGameVersions {
+        SupportedGameVersions::All
+    }
+
+    fn validate(
+        &self,
+        archive: &mut ZipArchive<FileReader>,
+    ) -> Result<ValidationReport, ValidationError> {
+        validate_pack(archive, PackKind::Resources, "assets/")
+    }
+}
+
+pub struct DataPackValidator;
+
+impl super::Validator for DataPackValidator {
+    fn get_file_extensions(&self) -> &[&str] {
+        &["zip"]
+    }
+
+    fn get_project_types(&self) -> &[&str] {
+        &["datapack"]
+    }
+
+    fn get_supported_loaders(&self) -> &[&str] {
+        &["minecraft"]
+    }
+
+    fn get_supported_game_versions(&self) -> SupportedGameVersions {
+        SupportedGameVersions::All
+    }
+
+    fn validate(
+        &self,
+        archive: &mut ZipArchive<FileReader>,
+    ) -> Result<ValidationReport, ValidationError> {
+        validate_pack(archive, PackKind::Data, "data/")
+    }
+}
+
+#[derive(Deserialize)]
+struct PackMcmeta {
+    pack: PackSection,
+}
+
+#[derive(Deserialize)]
+struct PackSection {
+    pack_format: u32,
+}
+
+fn validate_pack(
+    archive: &mut ZipArchive<FileReader>,
+    kind: PackKind,
+    contents_folder: &str,
+) -> Result<ValidationReport, ValidationError> {
+    let name = match kind {
+        PackKind::Resources => "resource pack",
+        PackKind::Data => "data pack",
+    };
+
+    let mcmeta = read_manifest(archive, "pack.mcmeta").ok_or_else(|| {
+        ValidationError::InvalidInput(
+            format!("No pack.mcmeta present for {} file.", name).into(),
+        )
+    })?;
+    let mcmeta: PackMcmeta = serde_json::from_str(&mcmeta)?;
+
+    let mut findings = Vec::new();
+
+    if !archive
+        .file_names()
+        .any(|file| file.starts_with(contents_folder))
+    {
+        findings.push(
+            ValidationFinding::warning(
+                "empty_pack",
+                format!(
+                    "The {} does not contain a {} folder!",
+                    name, contents_folder
+                ),
+            )
+            .at(contents_folder),
+        );
+    }
+
+    Ok(ValidationReport {
+        result: ValidationResult::PassWithPackFormat {
+            kind,
+            pack_format: mcmeta.pack.pack_format,
+        },
+        findings,
+    })
+}
+
+/// The range of Minecraft versions using a pack format. Snapshots count towards
+/// the format of the release following them.
+fn pack_format_range(kind: PackKind, pack_format: u32) -> Option<&'static str> {
+    match (kind, pack_format) {
+        (PackKind::Resources, 1) => Some("(1.5.2,1.8.9]"),
+        (PackKind::Resources, 2) => Some("(1.8.9,1.10.2]"),
+        (PackKind::Resources, 3) => Some("(1.10.2,1.12.2]"),
+        (PackKind::Resources, 4) | (PackKind::Data, 4) => {
+            Some("(1.12.2,1.14.4]")
+        }
+        (PackKind::Resources, 5) | (PackKind::Data, 5) => {
+            Some("(1.14.4,1.16.1]")
+        }
+        (PackKind::Resources, 6) | (PackKind::Data, 6) => {
+            Some("(1.16.1,1.16.5]")
+        }
+        (PackKind::Resources, 7) | (PackKind::Data, 7) => {
+            Some("(1.16.5,1.17.1]")
+        }
+        (PackKind::Resources, 8) => Some("(1.17.1,1.18.2]"),
+        (PackKind::Resources, 9) => Some("(1.18.2,)"),
+        (PackKind::Data, 8) => Some("(1.17.1,1.18.1]"),
+        (PackKind::Data, 9) => Some("(1.18.1,1.18.2]"),
+        (PackKind::Data, 10) => Some("(1.18.2,)"),
+        _ => None,
+    }
+}
+
+/// Compares the pack format declared in a `pack.mcmeta` with the selected game
+/// versions. Minecraft still loads packs of other formats after warning the
+/// player, so only packs matching none of the versions keep from being primary.
+pub(super) fn check_pack_format(
+    report: &mut ValidationReport,
+    game_versions: &[GameVersion],
+    all_game_versions: &[crate::database::models::categories::GameVersion],
+) {
+    let (kind, pack_format) = match report.result {
+        ValidationResult::PassWithPackFormat { kind, pack_format } => {
+            (kind, pack_format)
+        }
+        _ => return,
+    };
+
+    let range = match pack_format_range(kind, pack_format) {
+        Some(range) => range,
+        None => return,
+    };
+
+    let outside =
+        versions_outside_range(range, game_versions, all_game_versions);
+
+    if outside.is_empty() {
+        return;
+    }
+
+    let message = format!(
+        "pack.mcmeta declares pack format {}, which isn't used by Minecraft {}",
+        pack_format,
+        outside
+            .iter()
+            .map(|x| x.0.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    report.findings.push(
+        if outside.len() == game_versions.len() {
+            ValidationFinding::warning("pack_format_mismatch", message)
+        } else {
+            ValidationFinding::info("pack_format_mismatch", message)
+        }
+        .at("pack.mcmeta"),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_pack_format, DataPackValidator, PackKind, ResourcePackValidator,
+    };
+    use crate::database::models::categories::GameVersion as KnownGameVersion;
+    use crate::database::models::GameVersionId;
+    use crate::models::projects::{FindingSeverity, GameVersion};
+    use crate::validate::{
+        test_util::{make_dummy_zip, make_dummy_zip_with_contents},
+        ValidationError, ValidationResult, Validator,
+    };
+    use time::OffsetDateTime;
+
+    const MCMETA: &str = r#"{"pack": {"pack_format": 8, "description": ""}}"#;
+
+    #[test]
+    fn all_clear() {
+        let mut zip = make_dummy_zip_with_contents(&[
+            ("pack.mcmeta", MCMETA),
+            ("assets/minecraft/textures/block/stone.png", ""),
+        ])
+        .unwrap();
+
+        let report = ResourcePackValidator.validate(&mut zip).unwrap();
+
+        assert!(report.is_passed());
+        assert_eq!(
+            report.result,
+            ValidationResult::PassWithPackFormat {
+                kind: PackKind::Resources,
+                pack_format: 8,
+            }
+        );
+    }
+    #[test]
+    fn missing_mcmeta() {
+        let mut zip =
+            make_dummy_zip(&["data/example/functions/tick.mcfunction"])
+                .unwrap();
+
+        assert!(matches!(
+            DataPackValidator.validate(&mut zip).unwrap_err(),
+            ValidationError::InvalidInput(error)
+            if error == "No pack.mcmeta present for data pack file."
+        ));
+    }
+    #[test]
+    fn invalid_mcmeta() {
+        let mut zip = make_dummy_zip(&["pack.mcmeta", "assets/"]).unwrap();
+
+        assert!(matches!(
+            ResourcePackValidator.validate(&mut zip).unwrap_err(),
+            ValidationError::SerDe(_)
+        ));
+    }
+    #[test]
+    fn missing_data_folder() {
+        let mut zip = make_dummy_zip_with_contents(&[
+            ("pack.mcmeta", MCMETA),
+            ("assets/minecraft/lang/en_us.json", ""),
+        ])
+        .unwrap();
+
+        let report = DataPackValidator.validate(&mut zip).unwrap();
+
+        assert!(!report.is_passed());
+        assert!(report.findings.iter().any(|x| x.code == "empty_pack"));
+    }
+    #[test]
+    fn pack_format_mismatch() {
+        let all_game_versions = vec![
+            ("1.17.1", 1625490000),
+            ("1.18", 1638280800),
+            ("1.18.2", 1646060400),
+            ("1.19", 1654610400),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(id, (version, date))| KnownGameVersion {
+            id: GameVersionId(id as i32),
+            version: version.to_string(),
+            version_type: "release".to_string(),
+            date: OffsetDateTime::from_unix_timestamp(date),
+            major: false,
+        })
+        .collect::<Vec<_>>();
+        let check = |versions: &[&str]| {
+            let mut zip = make_dummy_zip_with_contents(&[
+                ("pack.mcmeta", MCMETA),
+                ("assets/", ""),
+            ])
+            .unwrap();
+            let mut report = ResourcePackValidator.validate(&mut zip).unwrap();
+            let versions = versions
+                .iter()
+                .map(|x| GameVersion(x.to_string()))
+                .collect::<Vec<_>>();
+
+            check_pack_format(&mut report, &versions, &all_game_versions);
+            report.findings
+        };
+
+        assert!(check(&["1.18", "1.18.2"]).is_empty());
+        assert_eq!(
+            check(&["1.18.2", "1.19"])
+                .into_iter()
+                .map(|x| x.severity)
+                .collect::<Vec<_>>(),
+            vec![FindingSeverity::Info]
+        );
+        assert_eq!(
+            check(&["1.17.1"])
+                .into_iter()
+                .map(|x| x.severity)
+                .collect::<Vec<_>>(),
+            vec![FindingSeverity::Warning]
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use crate::models::projects::ValidationFinding;
+use crate::util::spool::FileReader;
+use crate::validate::{
+    SupportedGameVersions, ValidationError, ValidationReport, ValidationResult,
+};
+use zip::ZipArchive;
+
+pub struct ShaderPackValidator;
+
+impl super::Validator for ShaderPackValidator {
+    fn get_file_extensions(&self) -> &[&str] {
+        &["zip"]
+    }
+
+    fn get_project_types(&self) -> &[&str] {
+        &["shader"]
+    }
+
+    fn get_supported_loaders(&self) -> &[&str] {
+        &["iris", "optifine"]
+    }
+
+    fn get_supported_game_versions(&self) -> SupportedGameVersions {
+        SupportedGameVersions::All
+    }
+
+    fn validate(
+        &self,
+        archive: &mut ZipArchive<FileReader>,
+    ) -> Result<ValidationReport, ValidationError> {
+        if !archive
+            .file_names()
+            .any(|name| name.starts_with("shaders/"))
+        {
+            return Err(ValidationError::InvalidInput(
+                "No shaders folder present for shader pack file.".into(),
+            ));
+        }
+
+        let mut findings = Vec::new();
+
+        // Shader packs work without any properties, but almost all of them
+        // configure at least their options through this file
+        if archive.by_name("shaders/shaders.properties").is_err() {
+            findings.push(
+                ValidationFinding::info(
+                    "missing_shader_properties",
+                    "Shader pack file does not contain shaders.properties",
+                )
+                .at("shaders/shaders.properties"),
+            );
+        }
+
+        Ok(ValidationReport {
+            result: ValidationResult::Pass,
+            findings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShaderPackValidator;
+    use crate::validate::{
+        test_util::make_dummy_zip, ValidationError, ValidationResult, Validator,
+    };
+
+    #[test]
+    fn all_clear() {
+        let mut zip = make_dummy_zip(&[
+            "shaders/shaders.properties",
+            "shaders/composite.fsh",
+        ])
+        .unwrap();
+
+        let report = ShaderPackValidator.validate(&mut zip).unwrap();
+
+        assert_eq!(report.result, ValidationResult::Pass);
+        assert!(report.findings.is_empty());
+    }
+    #[test]
+    fn missing_properties() {
+        let mut zip = make_dummy_zip(&["shaders/composite.fsh"]).unwrap();
+
+        let report = ShaderPackValidator.validate(&mut zip).unwrap();
+
+        assert!(report.is_passed());
+        assert!(report
+            .findings
+            .iter()
+            .any(|x| x.code == "missing_shader_properties"));
+    }
+    #[test]
+    fn nested_shaders_folder() {
+        let mut zip =
+            make_dummy_zip(&["Example/shaders/composite.fsh"]).unwrap();
+
+        assert!(matches!(
+            ShaderPackValidator.validate(&mut zip).unwrap_err(),
+            ValidationError::InvalidInput(error)
+            if error == "No shaders folder present for shader pack file."
+        ));
+    }
+}
@@ -1,5 +1,6 @@
+use crate::util::spool::FileReader;
 use bytes::Bytes;
-use std::io::Cursor;
+use std::io::{Cursor, Write};
 use zip::{result::ZipResult, write::FileOptions, ZipArchive, ZipWriter};
 
 /// Makes a dummy ZIP file with named blank files.
@@ -7,7 +8,7 @@ use zip::{result::ZipResult, write::FileOptions, ZipArchive, ZipWriter};
 #[cfg(test)]
 pub fn make_dummy_zip(
     filenames: &[&str],
-) -> ZipResult<ZipArchive<Cursor<bytes::Bytes>>> {
+) -> ZipResult<ZipArchive<FileReader>> {
     let mut zip = ZipWriter::new(Cursor::new(vec![]));
     let options = FileOptions::default();
     for &file in filenames {
@@ -16,5 +17,38 @@ pub fn make_dummy_zip(
     let data = zip.finish()?.into_inner();
 
     let data = Bytes::from(data);
-    ZipArchive::new(Cursor::new(data))
+    ZipArchive::new(FileReader::from(data))
+}
+
+/// Makes a dummy ZIP file with named files and their contents.
+/// Used for unit tests.
+#[cfg(test)]
+pub fn make_dummy_zip_with_contents(
+    files: &[(&str, &str)],
+) -> ZipResult<ZipArchive<FileReader>> {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    let options = FileOptions::default();
+    for &(file, contents) in files {
+        zip.start_file(file, options)?;
+        zip.write_all(contents.as_bytes())?;
+    }
+    let data = zip.finish()?.into_inner();
+
+    let data = Bytes::from(data);
+    ZipArchive::new(FileReader::from(data))
+}
+
+/// Makes the bytes of a dummy ZIP file with named files and their binary
+/// contents, such as other ZIP files.
+/// Used for unit tests.
+#[cfg(test)]
+pub fn make_dummy_zip_bytes(files: &[(&str, &[u8])]) -> ZipResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    let options = FileOptions::default();
+    for &(file, contents) in files {
+        zip.start_file(file, options)?;
+        zip.write_all(contents)?;
+    }
+
+    Ok(zip.finish()?.into_inner())
 }